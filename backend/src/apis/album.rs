@@ -0,0 +1,405 @@
+#[allow(non_snake_case)]
+pub mod Album {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{Config, Error};
+    use crate::apis::{album_utils, media_utils};
+    use crate::database::database::{Album as DBAlbum, Media as DBMedia};
+    use crate::database::database_utils::{DatabaseExtension, DatabaseTreeExtension};
+
+    use chrono::{DateTime, Utc};
+    use rocket::{
+        get, post, delete,
+        FromForm, State,
+        http::Status,
+        serde::json::Json,
+        response::status
+    };
+    use serde::{Deserialize, Serialize};
+    use sled::IVec;
+    use utoipa::{IntoParams, ToSchema};
+
+    #[derive(Serialize, Deserialize, FromForm, IntoParams, ToSchema, Clone)]
+    pub struct Album {
+        #[schema(example = "Xk2PqL9a")]
+        id: String
+    }
+
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct AlbumInfo {
+        #[schema(example = "Etho")]
+        /// Album author's username
+        author_username: String,
+        #[schema(example = "Summer meetup 2023")]
+        album_name: String,
+        album_description: Option<String>,
+        /// Ids of the album's media in order, leaving out media the user can't view
+        media_ids: Vec<String>,
+        /// Id of the media shown for the album
+        cover_id: Option<String>,
+        #[schema(value_type = String)]
+        creation_date: DateTime::<Utc>,
+        /// Whether the album is left out of searches
+        unlisted: bool,
+        /// Whether only the author & admins can view the album
+        private: bool
+    }
+
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct CreateAlbum {
+        #[schema(example = "Summer meetup 2023")]
+        name: String,
+        description: Option<String>,
+        /// Ids of the album's media in the order they're shown
+        media_ids: Option<Vec<String>>,
+        /// Id of the media shown for the album, has to be one of media_ids
+        cover_id: Option<String>,
+        /// Leaves the album out of searches
+        unlisted: Option<bool>,
+        /// Only lets the author & admins view the album
+        private: Option<bool>,
+        /// User's api key
+        api_key: String
+    }
+
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct EditAlbum {
+        /// Id pointing to album
+        #[schema(example = "Xk2PqL9a")]
+        id: String,
+        /// Album's new name, leave as unset to maintain previous value
+        name: Option<String>,
+        /// Album's new description, requires that edit_description is enabled
+        description: Option<String>,
+        /// Whether or not to enable description editing, the description is removed when description is unset
+        edit_description: Option<bool>,
+        /// Album's new ids of media in order, leave as unset to maintain previous value
+        media_ids: Option<Vec<String>>,
+        /// Album's new cover, requires that edit_cover is enabled
+        cover_id: Option<String>,
+        /// Whether or not to enable cover editing, the cover is removed when cover_id is unset
+        edit_cover: Option<bool>,
+        /// Album's new unlisted, leave as unset to maintain previous value
+        unlisted: Option<bool>,
+        /// Album's new private, leave as unset to maintain previous value
+        private: Option<bool>,
+        /// User's api key
+        api_key: String
+    }
+
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct DeleteAlbum {
+        /// Id pointing to album
+        #[schema(example = "Xk2PqL9a")]
+        id: String,
+        /// User's api key
+        api_key: String
+    }
+
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct AlbumQuery {
+        /// Only show albums pertaining to a user
+        username: Option<String>,
+        /// Allows search to include the user's unlisted & private albums
+        api_key: Option<String>
+    }
+
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct AlbumsFound {
+        /// List of album ids found in search
+        ids: Vec<String>
+    }
+
+    fn not_found() -> status::Custom<Json<Error>> {
+        status::Custom(Status::NotFound, Json(Error {
+            error: String::from("Couldn't find album associated with id")
+        }))
+    }
+
+    fn invalid_api_key() -> status::Custom<Json<Error>> {
+        status::Custom(Status::Unauthorized, Json(Error {
+            error: String::from("Api key not valid and or does not exist!")
+        }))
+    }
+
+    /// The cover has to be one of the album's media
+    fn check_cover(cover_id: Option<&str>, media_ids: &[String]) -> Result<(), status::Custom<Json<Error>>> {
+        match cover_id {
+            Some(cover_id) if !media_ids.iter().any(|id| id == cover_id) => Err(status::Custom(Status::BadRequest, Json(Error {
+                error: String::from("Cover has to be one of the album's media")
+            }))),
+            _ => Ok(())
+        }
+    }
+
+    /// Returns an album's details & media
+    #[utoipa::path(
+        get,
+        context_path = "/api/album",
+        responses(
+            (status = 200, description = "Successfully grabbed album information", body = AlbumInfo),
+            (status = 401, description = "Album is private & no valid api key was given", body = Error),
+            (status = 403, description = "Album is private & doesn't belong to the user", body = Error),
+            (status = 404, description = "Album doesn't exist", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            Album,
+            ("api_key" = Option<String>, Query, description = "Api key of the user viewing the album, needed for private albums & private media within it")
+        )
+    )]
+    #[get("/info?<api_key>&<identification..>")]
+    pub async fn info(
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        identification: Album,
+        api_key: Option<String>
+    ) -> Result<Json<AlbumInfo>, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+        let album_database = &database.get_tree("album")?;
+        let user_database = &database.get_tree("user")?;
+        let media_database = &database.get_tree("media")?;
+        let grant_database = &database.get_tree("grant")?;
+
+        let album = album_utils::find_album(album_database, &identification.id).ok_or_else(not_found)?;
+        let user = api_key.and_then(|api_key| media_utils::find_user_by_api_key(user_database, &api_key));
+
+        if !album_utils::can_view_album(&album, user.as_ref()) {
+            return Err(match user {
+                Some(_) => status::Custom(Status::Forbidden, Json(Error {
+                    error: String::from("Album is private")
+                })),
+                None => invalid_api_key()
+            })
+        }
+
+        let media_ids: Vec<String> = album.media_ids.into_iter()
+            .filter(|id| match media_database.get(id) {
+                Ok(Some(media_vec)) => matches!(serde_json::from_str::<DBMedia>(&String::from_utf8_lossy(&media_vec)),
+                    Ok(media) if media_utils::can_view(grant_database, &media, user.as_ref())),
+                _ => false
+            })
+            .collect();
+
+        Ok(Json(AlbumInfo {
+            author_username: album.author_username,
+            album_name: album.name,
+            album_description: album.description,
+            cover_id: album.cover_id.filter(|cover_id| media_ids.contains(cover_id)),
+            media_ids,
+            creation_date: album.creation_date,
+            unlisted: album.unlisted,
+            private: album.private
+        }))
+    }
+
+    /// Searches all album id's in the form of a list
+    #[utoipa::path(
+        post,
+        context_path = "/api/album",
+        request_body = AlbumQuery,
+        responses(
+            (status = 200, description = "Successfully found all albums pertaining to the search query", body = AlbumsFound),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        )
+    )]
+    #[post("/search", data = "<search>")]
+    pub async fn search(
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        search: Json<AlbumQuery>
+    ) -> Result<Json<AlbumsFound>, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+        let album_database = &database.get_tree("album")?;
+        let user_database = &database.get_tree("user")?;
+
+        let user = search.api_key.as_ref().and_then(|api_key| media_utils::find_user_by_api_key(user_database, api_key));
+
+        let ids = album_database.iter()
+            .filter_map(|item| item.ok())
+            .filter_map(|item| serde_json::from_str::<DBAlbum>(&String::from_utf8_lossy(&item.1)).ok())
+            .filter(|album| !matches!(&search.username, Some(username) if &album.author_username != username))
+            // Unlisted & private albums are only found by their author & admins
+            .filter(|album| (!album.unlisted && !album.private) || matches!(&user, Some(user) if album_utils::can_manage_album(album, user)))
+            .map(|album| album.id)
+            .collect();
+
+        Ok(Json(AlbumsFound {
+            ids
+        }))
+    }
+
+    /// Creates an album on a user's account
+    #[utoipa::path(
+        post,
+        context_path = "/api/album",
+        request_body = CreateAlbum,
+        responses(
+            (status = 200, description = "Successfully created album", body = Album),
+            (status = 400, description = "Server received malformed client request", body = Error),
+            (status = 401, description = "An authentication issue has occurred", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        )
+    )]
+    #[post("/create", data = "<body>")]
+    pub async fn create(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        body: Json<CreateAlbum>
+    ) -> Result<Json<Album>, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+        let album_database = &database.get_tree("album")?;
+        let user_database = &database.get_tree("user")?;
+
+        let config = match config_store.lock() {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        let user = media_utils::find_user_by_api_key(user_database, &body.api_key).ok_or_else(invalid_api_key)?;
+
+        album_utils::check_album_details(&config, &body.name, body.description.as_deref())?;
+        let media_ids = album_utils::check_album_media(&config, &database, &user, body.media_ids.as_deref().unwrap_or_default())?;
+        check_cover(body.cover_id.as_deref(), &media_ids)?;
+
+        let album = album_utils::insert_album(&config, album_database, DBAlbum {
+            id: String::new(),
+            name: body.name.clone(),
+            description: body.description.clone(),
+            media_ids,
+            cover_id: body.cover_id.clone(),
+            author_username: user.username,
+            creation_date: Utc::now(),
+            unlisted: body.unlisted.unwrap_or(false),
+            private: body.private.unwrap_or(false)
+        })?;
+
+        Ok(Json(Album {
+            id: album.id
+        }))
+    }
+
+    /// Edit album information
+    /// such as its name, media & their order
+    #[utoipa::path(
+        post,
+        context_path = "/api/album",
+        request_body = EditAlbum,
+        responses(
+            (status = 200, description = "Successfully edited album"),
+            (status = 400, description = "Server received malformed client request", body = Error),
+            (status = 401, description = "An authentication issue has occurred", body = Error),
+            (status = 404, description = "Album doesn't exist", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        )
+    )]
+    #[post("/edit", data = "<body>")]
+    pub async fn edit(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        body: Json<EditAlbum>
+    ) -> Result<Status, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+        let album_database = &database.get_tree("album")?;
+        let user_database = &database.get_tree("user")?;
+
+        let config = match config_store.lock() {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        let user = media_utils::find_user_by_api_key(user_database, &body.api_key).ok_or_else(invalid_api_key)?;
+        let mut edited_album = album_utils::find_album(album_database, &body.id).ok_or_else(not_found)?;
+
+        if !album_utils::can_manage_album(&edited_album, &user) {
+            return Err(status::Custom(Status::Unauthorized, Json(Error {
+                error: String::from("You are unauthorized to access & edit this album")
+            })))
+        }
+
+        if let Some(name) = &body.name {
+            edited_album.name = name.clone();
+        }
+
+        if body.edit_description == Some(true) {
+            edited_album.description = body.description.clone();
+        }
+
+        album_utils::check_album_details(&config, &edited_album.name, edited_album.description.as_deref())?;
+
+        if let Some(media_ids) = &body.media_ids {
+            edited_album.media_ids = album_utils::check_album_media(&config, &database, &user, media_ids)?;
+
+            // A cover taken out of the album goes along with it
+            if !matches!(&edited_album.cover_id, Some(cover_id) if edited_album.media_ids.contains(cover_id)) {
+                edited_album.cover_id = None;
+            }
+        }
+
+        if body.edit_cover == Some(true) {
+            check_cover(body.cover_id.as_deref(), &edited_album.media_ids)?;
+            edited_album.cover_id = body.cover_id.clone();
+        }
+
+        if let Some(unlisted) = body.unlisted {
+            edited_album.unlisted = unlisted;
+        }
+
+        if let Some(private) = body.private {
+            edited_album.private = private;
+        }
+
+        let edited = album_database.update_and_fetch(&edited_album.id, |album_vec| {
+            // Not brought back if it was deleted in the meantime
+            album_vec?;
+
+            Some(IVec::from(match serde_json::to_vec(&edited_album) {
+                Ok(result) => result,
+                Err(_) => return None
+            }))
+        });
+
+        match edited {
+            Ok(Some(_)) => Ok(Status::Ok),
+            Ok(None) => Err(not_found()),
+            Err(_) => Err(media_utils::internal_error())
+        }
+    }
+
+    /// Permanently deletes an album, its media are left as they are
+    #[utoipa::path(
+        delete,
+        context_path = "/api/album",
+        request_body = DeleteAlbum,
+        responses(
+            (status = 200, description = "Successfully deleted album"),
+            (status = 401, description = "Unauthorized deletion", body = Error),
+            (status = 404, description = "Album doesn't exist", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        )
+    )]
+    #[delete("/delete", data = "<body>")]
+    pub async fn delete(
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        body: Json<DeleteAlbum>
+    ) -> Result<Status, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+        let album_database = &database.get_tree("album")?;
+        let user_database = &database.get_tree("user")?;
+
+        let user = media_utils::find_user_by_api_key(user_database, &body.api_key).ok_or_else(invalid_api_key)?;
+        let album = album_utils::find_album(album_database, &body.id).ok_or_else(not_found)?;
+
+        if !album_utils::can_manage_album(&album, &user) {
+            return Err(status::Custom(Status::Unauthorized, Json(Error {
+                error: String::from("Album does not belong to associated api key!")
+            })))
+        }
+
+        if album_database.remove(&album.id).is_err() || album_database.flush().is_err() {
+            return Err(status::Custom(Status::InternalServerError, Json(Error {
+                error: String::from("Failed delete album from database")
+            })))
+        }
+
+        Ok(Status::Ok)
+    }
+}
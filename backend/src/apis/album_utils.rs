@@ -0,0 +1,178 @@
+use rand::distributions::{Alphanumeric, DistString};
+use rand_core::OsRng;
+use rocket::{http::Status, response::status, serde::json::Json};
+use sled::{Db, IVec, Tree};
+
+use crate::{Config, Error};
+use crate::apis::media_utils;
+use crate::database::database::{Album, Media, User};
+
+/// Album stored under the id
+pub fn find_album(album_database: &Tree, id: &str) -> Option<Album> {
+    match album_database.get(id) {
+        Ok(Some(album_vec)) => serde_json::from_str(&String::from_utf8_lossy(&album_vec)).ok(),
+        _ => None
+    }
+}
+
+/// Whether the user may view the album, private albums are only viewable by their author & admins
+pub fn can_view_album(album: &Album, user: Option<&User>) -> bool {
+    if !album.private {
+        return true
+    }
+
+    matches!(user, Some(user) if user.admin || user.username == album.author_username)
+}
+
+/// Whether the user may edit & delete the album
+pub fn can_manage_album(album: &Album, user: &User) -> bool {
+    user.admin || user.username == album.author_username
+}
+
+/// Checks an album's name & description against the instance's limits
+pub fn check_album_details(config: &Config, name: &str, description: Option<&str>) -> Result<(), status::Custom<Json<Error>>> {
+    if name.trim().is_empty() {
+        return Err(status::Custom(Status::BadRequest, Json(Error {
+            error: String::from("Album name can't be empty")
+        })))
+    }
+
+    if config.album_max_name_length > 0 && name.chars().count() as i32 > config.album_max_name_length {
+        return Err(status::Custom(Status::BadRequest, Json(Error {
+            error: format!("Album name is too long! Maximum name length of {} characters", config.album_max_name_length)
+        })))
+    }
+
+    if let Some(description) = description {
+        if config.album_max_description_length > 0 && description.chars().count() as i32 > config.album_max_description_length {
+            return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: format!("Album description is too long! Maximum description length of {} characters", config.album_max_description_length)
+            })))
+        }
+    }
+
+    Ok(())
+}
+
+/// Makes sure the user can put the media in an album, returning it in the given order without duplicates.
+///
+/// Only media the user can view may be added, others are reported the same as missing media
+pub fn check_album_media(config: &Config, database: &Db, user: &User, media_ids: &[String]) -> Result<Vec<String>, status::Custom<Json<Error>>> {
+    let mut checked: Vec<String> = Vec::with_capacity(media_ids.len());
+    for id in media_ids {
+        if !checked.contains(id) {
+            checked.push(id.clone());
+        }
+    }
+
+    if !user.admin && config.album_max_media > 0 && checked.len() as i32 > config.album_max_media {
+        return Err(status::Custom(Status::BadRequest, Json(Error {
+            error: format!("Albums can't hold more than {} media", config.album_max_media)
+        })))
+    }
+
+    let (media_database, grant_database) = match (database.open_tree("media"), database.open_tree("grant")) {
+        (Ok(media_database), Ok(grant_database)) => (media_database, grant_database),
+        _ => return Err(media_utils::internal_error())
+    };
+
+    for id in &checked {
+        let media = match media_database.get(id) {
+            Ok(Some(media_vec)) => serde_json::from_str::<Media>(&String::from_utf8_lossy(&media_vec)).ok(),
+            _ => None
+        };
+
+        if !matches!(media, Some(media) if media_utils::can_view(&grant_database, &media, Some(user))) {
+            return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: format!("Media {} doesn't exist", id)
+            })))
+        }
+    }
+
+    Ok(checked)
+}
+
+/// Stores a new album under an unused id, which it's given
+pub fn insert_album(config: &Config, album_database: &Tree, mut album: Album) -> Result<Album, status::Custom<Json<Error>>> {
+    // Ids are short, so they're retried until one is free
+    for _ in 0..16 {
+        album.id = Alphanumeric.sample_string(&mut OsRng, config.media_dynamic_id_length.max(4) as usize);
+
+        let album_vec = match serde_json::to_vec(&album) {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        match album_database.compare_and_swap(&album.id, None as Option<&[u8]>, Some(album_vec)) {
+            Ok(Ok(())) => {
+                if album_database.flush().is_err() {
+                    return Err(media_utils::internal_error())
+                }
+
+                return Ok(album)
+            },
+            Ok(Err(_)) => continue,
+            Err(_) => return Err(media_utils::internal_error())
+        }
+    }
+
+    Err(media_utils::internal_error())
+}
+
+/// Takes deleted media out of every album holding it, along with the album's cover
+pub fn remove_media_from_albums(database: &Db, media_id: &str) -> sled::Result<()> {
+    let album_database = database.open_tree("album")?;
+
+    let albums: Vec<Album> = album_database.iter()
+        .filter_map(|item| item.ok())
+        .filter_map(|item| serde_json::from_str::<Album>(&String::from_utf8_lossy(&item.1)).ok())
+        .filter(|album| album.media_ids.iter().any(|id| id == media_id))
+        .collect();
+
+    for album in albums {
+        album_database.update_and_fetch(&album.id, |album_vec| {
+            let mut album: Album = serde_json::from_str(&String::from_utf8_lossy(album_vec?)).ok()?;
+            album.media_ids.retain(|id| id != media_id);
+
+            if album.cover_id.as_deref() == Some(media_id) {
+                album.cover_id = None;
+            }
+
+            Some(IVec::from(match serde_json::to_vec(&album) {
+                Ok(result) => result,
+                Err(_) => return None
+            }))
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Moves a user's albums over to their new username, or removes them once the user is gone
+pub fn move_user_albums(database: &Db, username: &str, new_username: Option<&str>) -> sled::Result<()> {
+    let album_database = database.open_tree("album")?;
+
+    let albums: Vec<Album> = album_database.iter()
+        .filter_map(|item| item.ok())
+        .filter_map(|item| serde_json::from_str::<Album>(&String::from_utf8_lossy(&item.1)).ok())
+        .filter(|album| album.author_username == username)
+        .collect();
+
+    for mut album in albums {
+        match new_username {
+            Some(new_username) => {
+                album.author_username = new_username.to_string();
+
+                if let Ok(album_vec) = serde_json::to_vec(&album) {
+                    album_database.insert(&album.id, album_vec)?;
+                }
+            },
+            None => {
+                album_database.remove(&album.id)?;
+            }
+        }
+    }
+
+    album_database.flush()?;
+    Ok(())
+}
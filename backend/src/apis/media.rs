@@ -1,35 +1,40 @@
 #[allow(non_snake_case)]
 pub mod Media {
-    use std::{sync::{Arc, Mutex}, path::Path, fs::{self, File}, io::{Write, Read, Cursor}};
+    use std::{sync::{Arc, Mutex}, fs, io, net::IpAddr, pin::Pin, task::{Context, Poll}};
 
     use crate::{Config, Error};
-    use crate::database::database::{User, Media as DBMedia};
+    use crate::apis::{album_utils, media_utils::{self, UploadDetails}};
+    use crate::database::database::{User, Media as DBMedia, MediaGrant, MediaRevision};
+    use crate::processing::{self, text::Highlighter};
+    use crate::storage::{blob::{self, KnownLength, Layout}, backend::Storage, encryption::Keyring};
 
-    use flate2::{write::{ZlibEncoder, ZlibDecoder}, Compression};
     use itertools::Itertools;
     use rocket::{
-        get, 
-        http::Status,
+        get, head,
+        http::{ContentType as HttpContentType, Status},
         serde::json::Json,
         FromForm, State,
         FromFormField, post,
+        http::RawStr,
         response::{Responder, status}, delete,
-        Response
+        Response,
+        Data,
+        data::Capped,
+        request::{self, FromRequest, Request},
+        tokio::io::{AsyncRead, ReadBuf},
+        form::Form,
+        fs::TempFile
     };
     use serde::{Deserialize, Serialize};
+    use sha2::{Digest, Sha256};
     use utoipa::{IntoParams, ToSchema};
 
-    use rand::distributions::{Alphanumeric, DistString};
-    use rand_core::OsRng;
-
     use base64::decode;
-
-    use infer::{MatcherType, Type};
+    use log::error;
 
     use sled::IVec;
 
-    use chrono::{DateTime, Utc};
-    use crate::database::database_utils;
+    use chrono::{DateTime, NaiveDateTime, Utc};
     use crate::database::database_utils::{DatabaseExtension, DatabaseTreeExtension};
 
     #[derive(Serialize, Deserialize, FromForm, IntoParams, ToSchema, Clone)]
@@ -38,6 +43,14 @@ pub mod Media {
         id: String
     }
 
+    impl From<DBMedia> for Media {
+        fn from(media: DBMedia) -> Self {
+            Media {
+                id: media.id
+            }
+        }
+    }
+
     #[derive(Serialize, Deserialize, IntoParams, ToSchema, Clone)]
     pub struct ContentInfo {
         #[schema(example = "Etho")]
@@ -53,15 +66,86 @@ pub mod Media {
         content_type: ContentType,
         /// Upload's content extension (e.g. .mp4 or .txt)
         content_extension: String,
+        /// Upload's mime type (e.g. video/mp4 or text/plain)
+        #[schema(example = "video/mp4")]
+        content_mime_type: String,
         /// When the media was uploaded in UTC Format
         #[schema(value_type = String)]
         upload_date: DateTime::<Utc>,
         /// Whether the upload is unlisted from /all/ endpoint or not
         unlisted: bool,
+        /// Whether only the author, admins & users granted access can view the upload
+        private: bool,
         /// Tags associated to upload
         tags: Option<Vec<String>>,
         /// Total downloads pertaining to the upload
-        downloads: i64
+        downloads: i64,
+        /// Details read from the upload's contents, such as an image's dimensions
+        content_metadata: Option<ContentMetadata>,
+        /// Language a text upload is highlighted as
+        #[schema(example = "Rust")]
+        content_language: Option<String>,
+        /// When the upload deletes itself in UTC Format, unset when it's kept for good
+        #[schema(value_type = Option<String>)]
+        expiration_date: Option<DateTime::<Utc>>,
+        /// Downloads after which the upload is purged, unset when unlimited
+        max_downloads: Option<u32>,
+        /// Whether the upload is locked with a password
+        password_protected: bool,
+        /// Hosts pages downloading the upload may be on in place of the instance's
+        hotlink_hosts: Option<Vec<String>>,
+        /// Revision of the current content, counted up each time the content is replaced
+        revision: u32,
+        /// When the content was last replaced in UTC Format, unset while it's still the uploaded content
+        #[schema(value_type = Option<String>)]
+        modified_date: Option<DateTime::<Utc>>
+    }
+
+    /// What's shown of password protected media until it's unlocked
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct LockedContentInfo {
+        /// Id pointing to media
+        #[schema(example = "HilrvkpJ")]
+        id: String,
+        #[schema(example = "Funny cat video")]
+        /// Upload's file name
+        content_name: String,
+        /// Always set, as only password protected media is shown this way
+        password_protected: bool
+    }
+
+    /// Media information, limited to its locked view while it's password protected & hasn't been unlocked
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    #[serde(untagged)]
+    pub enum ContentDetails {
+        Unlocked(Box<ContentInfo>),
+        Locked(LockedContentInfo)
+    }
+
+    /// Details read from an upload's contents when it was stored, unset when unknown
+    #[derive(Serialize, Deserialize, ToSchema, Clone, Debug, Default)]
+    pub struct ContentMetadata {
+        /// Image or video width in pixels
+        #[schema(example = 1920)]
+        pub width: Option<u32>,
+        /// Image or video height in pixels
+        #[schema(example = 1080)]
+        pub height: Option<u32>,
+        /// Video or audio length in seconds
+        #[schema(example = 29.5)]
+        pub duration: Option<f64>,
+        /// Video codec or audio codec for audio uploads (e.g. h264, vp9, mp3 or flac)
+        #[schema(example = "h264")]
+        pub codec: Option<String>,
+        /// Audio bitrate in kbps
+        #[schema(example = 320)]
+        pub bitrate: Option<u32>,
+        /// EXIF orientation from 1 (upright) to 8, width & height are as stored before rotating
+        #[schema(example = 1)]
+        pub orientation: Option<u16>,
+        /// When the photo was taken, in the camera's local time
+        #[schema(value_type = Option<String>, example = "2023-04-01T14:30:00")]
+        pub capture_date: Option<NaiveDateTime>
     }
 
     #[derive(Serialize, Deserialize, IntoParams, ToSchema, Clone)]
@@ -76,29 +160,131 @@ pub mod Media {
         tags: Vec<String>
     }
 
+    /// A file or directory within an archive upload
+    #[derive(Serialize, Deserialize, ToSchema, Clone, Debug)]
+    pub struct ArchiveEntry {
+        /// Path within the archive, always separated by forward slashes
+        #[schema(example = "logs/server.log")]
+        pub path: String,
+        /// Uncompressed size in bytes as declared by the archive
+        pub size: u64,
+        /// When the entry was last modified, zip archives don't tell their time zone
+        #[schema(value_type = Option<String>, example = "2023-04-01T14:30:00")]
+        pub modified: Option<NaiveDateTime>,
+        pub directory: bool
+    }
+
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct ArchiveListing {
+        /// Entries in the order they're stored within the archive
+        entries: Vec<ArchiveEntry>,
+        /// Whether the archive holds more entries than the instance lists
+        truncated: bool,
+        /// Entries left out for having paths pointing outside of the archive, such as ../
+        skipped: u32
+    }
+
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct TextPreview {
+        /// Language the text is highlighted as
+        #[schema(example = "Rust")]
+        language: Option<String>,
+        /// First lines of the text, including their line endings
+        content: String,
+        /// Whether the text continues past the preview
+        truncated: bool
+    }
+
     #[derive(Serialize, Deserialize, FromFormField, ToSchema, PartialEq, Eq, Clone, Debug)]
     pub enum ContentType {
         Video,
         Image,
+        Audio,
+        Text,
+        Document,
+        Archive,
+        Font,
         Other
     }
 
+    /// How the browser should present a download
+    #[derive(Serialize, Deserialize, FromFormField, ToSchema, PartialEq, Eq, Clone, Copy, Debug)]
+    pub enum Disposition {
+        /// Display the media in the browser when possible
+        #[field(value = "inline")]
+        #[serde(rename = "inline")]
+        Inline,
+        /// Save the media as a file
+        #[field(value = "attachment")]
+        #[serde(rename = "attachment")]
+        Attachment
+    }
+
+    /// Image format thumbnails & transformed images are encoded in
+    #[derive(Serialize, Deserialize, FromFormField, ToSchema, PartialEq, Eq, Clone, Copy, Debug)]
+    pub enum ImageFormat {
+        /// Lossless, keeps transparency
+        #[field(value = "png")]
+        #[serde(rename = "png")]
+        Png,
+        #[field(value = "jpeg")]
+        #[serde(rename = "jpeg")]
+        Jpeg,
+        /// Lossless, keeps transparency
+        #[field(value = "webp")]
+        #[serde(rename = "webp")]
+        Webp
+    }
+
+    /// How an image is resized into the requested width & height
+    #[derive(Serialize, Deserialize, FromFormField, ToSchema, PartialEq, Eq, Clone, Copy, Debug)]
+    pub enum ImageFit {
+        /// Scale down to fit within the box, keeping the aspect ratio
+        #[field(value = "contain")]
+        #[serde(rename = "contain")]
+        Contain,
+        /// Scale to fill the box, cropping whatever sticks out
+        #[field(value = "cover")]
+        #[serde(rename = "cover")]
+        Cover,
+        /// Stretch to the box's exact size
+        #[field(value = "fill")]
+        #[serde(rename = "fill")]
+        Fill
+    }
+
     #[derive(Serialize, Deserialize, ToSchema, Clone)]
     pub struct SearchQuery {
         /// Only show id's pertaining to a user
         username: Option<String>,
         /// Only return content of certain type such as a video
         content_type: Option<ContentType>,
-        /// Allows search to include the user's unlisted videos in query filtering
+        /// Allows search to include the user's unlisted videos & private videos they can view in query filtering
         api_key: Option<String>,
         /// Only show id's that have specific tags
         tags: Option<Vec<String>>,
         /// Sort in descending order by total downloads
-        downloads: Option<bool>
+        downloads: Option<bool>,
+        /// Only show videos or audio at least this many seconds long
+        #[schema(example = 5.0)]
+        min_duration: Option<f64>,
+        /// Only show videos or audio at most this many seconds long
+        #[schema(example = 30.0)]
+        max_duration: Option<f64>,
+        /// Only show images or videos at least this many pixels wide
+        min_width: Option<u32>,
+        /// Only show images or videos at most this many pixels wide
+        max_width: Option<u32>,
+        /// Only show images or videos at least this many pixels high
+        min_height: Option<u32>,
+        /// Only show images or videos at most this many pixels high
+        max_height: Option<u32>,
+        /// Only show videos or audio using a codec (e.g. h264, vp9 or mp3)
+        codec: Option<String>,
+        /// Only show media within an album, in the album's order unless sorted by downloads
+        album: Option<String>
     }
 
-    // TODO: Replace base64 encoding with either openapi file upload, or direct string byte array instead of using base64?
-
     #[derive(Serialize, Deserialize, ToSchema, Clone)]
     pub struct UploadMedia {
         #[schema(example = "Funny cat video")]
@@ -106,14 +292,103 @@ pub mod Media {
         name: String,
         /// Hide's upload from being listed in /all/ endpoint
         unlisted: Option<bool>,
+        /// Only lets the author, admins & users granted access view the upload
+        private: Option<bool>,
         /// Tags relating to the upload
         tags: Option<Vec<String>>,
+        /// Seconds after uploading at which the upload deletes itself
+        expires_in: Option<u64>,
+        /// Date at which the upload deletes itself in RFC 3339 format, can't be combined with expires_in
+        #[schema(example = "2030-01-01T00:00:00Z")]
+        expires_at: Option<String>,
+        /// Downloads after which the upload is purged, it can then only be downloaded rather than previewed
+        max_downloads: Option<u32>,
+        /// Password the upload is locked with, which has to be given to access it
+        password: Option<String>,
         /// Base64 encoded string containing the file contents
         upload_data: String,
         /// User's api key
         api_key: String,
     }
 
+    #[derive(FromForm, ToSchema)]
+    pub struct UploadMediaForm<'r> {
+        #[schema(example = "Funny cat video")]
+        /// Upload's file name
+        name: String,
+        /// Hide's upload from being listed in /all/ endpoint
+        unlisted: Option<bool>,
+        /// Only lets the author, admins & users granted access view the upload
+        private: Option<bool>,
+        /// Tags relating to the upload
+        tags: Option<Vec<String>>,
+        /// Seconds after uploading at which the upload deletes itself
+        expires_in: Option<u64>,
+        /// Date at which the upload deletes itself in RFC 3339 format, can't be combined with expires_in
+        #[schema(example = "2030-01-01T00:00:00Z")]
+        expires_at: Option<String>,
+        /// Downloads after which the upload is purged, it can then only be downloaded rather than previewed
+        max_downloads: Option<u32>,
+        /// Password the upload is locked with, which has to be given to access it
+        password: Option<String>,
+        /// User's api key
+        api_key: String,
+        /// The file contents
+        #[schema(value_type = String, format = Binary)]
+        file: Capped<TempFile<'r>>
+    }
+
+    #[derive(Serialize, Deserialize, FromForm, IntoParams, Clone)]
+    pub struct UploadMediaQuery {
+        #[param(example = "Funny cat video")]
+        /// Upload's file name
+        name: String,
+        /// Hide's upload from being listed in /all/ endpoint
+        unlisted: Option<bool>,
+        /// Only lets the author, admins & users granted access view the upload
+        private: Option<bool>,
+        /// Tags relating to the upload
+        tags: Option<Vec<String>>,
+        /// Seconds after uploading at which the upload deletes itself
+        expires_in: Option<u64>,
+        /// Date at which the upload deletes itself in RFC 3339 format, can't be combined with expires_in
+        #[param(example = "2030-01-01T00:00:00Z")]
+        expires_at: Option<String>,
+        /// Downloads after which the upload is purged, it can then only be downloaded rather than previewed
+        max_downloads: Option<u32>,
+        /// Password the upload is locked with, which has to be given to access it
+        password: Option<String>,
+        /// User's api key
+        api_key: String
+    }
+
+    #[derive(Serialize, Deserialize, FromForm, IntoParams, Clone)]
+    pub struct UploadTextQuery {
+        #[param(example = "server.log")]
+        /// Upload's file name
+        name: String,
+        /// Language to highlight the text as, by name or file extension (e.g. rust or rs)
+        #[param(example = "rust")]
+        language: Option<String>,
+        /// Hide's upload from being listed in /all/ endpoint
+        unlisted: Option<bool>,
+        /// Only lets the author, admins & users granted access view the upload
+        private: Option<bool>,
+        /// Tags relating to the upload
+        tags: Option<Vec<String>>,
+        /// Seconds after uploading at which the upload deletes itself
+        expires_in: Option<u64>,
+        /// Date at which the upload deletes itself in RFC 3339 format, can't be combined with expires_in
+        #[param(example = "2030-01-01T00:00:00Z")]
+        expires_at: Option<String>,
+        /// Downloads after which the upload is purged, it can then only be downloaded rather than previewed
+        max_downloads: Option<u32>,
+        /// Password the upload is locked with, which has to be given to access it
+        password: Option<String>,
+        /// User's api key
+        api_key: String
+    }
+
     #[derive(Serialize, Deserialize, ToSchema, Clone)]
     pub struct DeleteMedia {
         /// Id pointing to media
@@ -134,204 +409,1894 @@ pub mod Media {
         name: Option<String>,
         /// Media's new unlisted, leave as unset to maintain previous value
         unlisted: Option<bool>,
+        /// Media's new private, leave as unset to maintain previous value
+        private: Option<bool>,
         /// Media's new list of string tags, requires that edit_tags is enabled
         tags: Option<Vec<String>>,
         /// Whether or not to enable tag editing
-        edit_tags: Option<bool>
+        edit_tags: Option<bool>,
+        /// Seconds from now at which the media deletes itself, requires that edit_expiration is enabled
+        expires_in: Option<u64>,
+        /// Date at which the media deletes itself in RFC 3339 format, requires that edit_expiration is enabled
+        #[schema(example = "2030-01-01T00:00:00Z")]
+        expires_at: Option<String>,
+        /// Whether or not to enable expiration editing, the expiration is removed when neither expires_in nor expires_at are set
+        edit_expiration: Option<bool>,
+        /// Password the media is locked with, requires that edit_password is enabled
+        password: Option<String>,
+        /// Whether or not to enable password editing, the media is unlocked when password is unset
+        edit_password: Option<bool>,
+        /// Hosts pages downloading the media may be on in place of the instance's, requires that edit_hotlink_hosts is enabled
+        #[schema(example = json!(["example.com", "*.example.com"]))]
+        hotlink_hosts: Option<Vec<String>>,
+        /// Whether or not to enable hotlink host editing, the instance's hosts apply again when hotlink_hosts is unset
+        edit_hotlink_hosts: Option<bool>
     }
 
-    #[derive(Debug, Serialize)]
-    pub struct FileResponse {
-        data: Vec<u8>,
-        content_disposition: String
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct UnlockMedia {
+        /// Id pointing to media
+        #[schema(example = "HilrvkpJ")]
+        id: String,
+        /// Password the media is locked with
+        password: String
     }
 
-    impl<'r> Responder<'r, 'static> for FileResponse {
-        fn respond_to(self, _: &'r rocket::Request<'_>) -> rocket::response::Result<'static> {
-            Response::build()
-                .sized_body(self.data.len(), Cursor::new(self.data))
-                .raw_header("content-disposition", self.content_disposition)
-                .ok()
-        }
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct UnlockToken {
+        /// Token to send along as the X-Unlock-Token header or unlock_token query parameter
+        #[schema(example = "Q0u4ceTlFvNvjbyNyO0zBkGa")]
+        token: String,
+        /// When the token stops being accepted in UTC Format
+        #[schema(value_type = String)]
+        expiration_date: DateTime::<Utc>
     }
 
-    /// Returns useful media information  
-    #[utoipa::path(
-        get,
-        context_path = "/api/media",
-        responses(
-            (status = 200, description = "Successfully grabbed media information", body = ContentInfo),
-            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
-        ),
-        params(
-            Media
-        )
-    )]
-    #[get("/info?<identification..>")]
-    pub async fn info(
-        _config: &State<Arc<Mutex<Config>>>,
-        database_store: &State<Arc<Mutex<sled::Db>>>,
-        identification: Media
-    ) -> Result<Json<ContentInfo>, status::Custom<Json<Error>>> {
-        let database = database_store.get_database()?;
-        let media_database = &database.get_tree("media")?;
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct GrantMedia {
+        /// Id pointing to media
+        #[schema(example = "HilrvkpJ")]
+        id: String,
+        /// User being granted or revoked access
+        #[schema(example = "Etho")]
+        username: String,
+        /// Api key of the media's author or an admin
+        api_key: String
+    }
 
-        let media_vec: IVec = match media_database.get(&identification.id) {
-            Ok(result) => {
-                match result {
-                    Some(result) => {
-                        result
-                    },
-                    None => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("An internal error on the server's end has occurred")
-                    })))
-                }
-            },
-            Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                error: String::from("Couldn't find media associated with id")
-            })))
-        };
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct ListGrants {
+        /// Id pointing to media
+        #[schema(example = "HilrvkpJ")]
+        id: String,
+        /// Api key of the media's author or an admin
+        api_key: String
+    }
 
-        let media: DBMedia = match serde_json::from_str(&String::from_utf8_lossy(&media_vec)) {
-            Ok(result) => result,
-            Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                error: String::from("An internal error on the server's end has occurred")
-            })))
-        };
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct ContentGrants {
+        /// Usernames the media has been granted to
+        usernames: Vec<String>
+    }
 
-        Ok(Json(ContentInfo {
-            author_username: media.author_username,
-            content_name: media.name,
-            content_size: media.data_size,
-            content_type: media.data_type,
-            content_extension: media.extension,
-            upload_date: media.upload_date,
-            unlisted: media.unlisted,
-            tags: media.tags,
-            downloads: media.downloads
-        }))
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct SignMedia {
+        /// Id pointing to media
+        #[schema(example = "HilrvkpJ")]
+        id: String,
+        /// Seconds the link stays valid
+        #[schema(example = 3600)]
+        expires_in: u64,
+        /// Only lets the link be used from this address
+        #[schema(example = "203.0.113.7")]
+        ip: Option<String>,
+        /// Api key of the media's author or an admin
+        api_key: String
     }
 
-    /// Returns file-disposition based file download
-    #[utoipa::path(
-        get,
-        context_path = "/api/media",
-        responses(
-            (status = 200, description = "Successfully found media"),
-            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
-        ),
-        params(
-            Media
-        )
-    )]
-    #[get("/download?<identification..>")]
-    pub async fn download(
-        _config: &State<Arc<Mutex<Config>>>,
-        database_store: &State<Arc<Mutex<sled::Db>>>,
-        identification: Media
-    ) -> Result<FileResponse, status::Custom<Json<Error>>> {
-        let database = database_store.get_database()?;
-        let media_database = &database.get_tree("media")?;
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct SignedUrl {
+        /// Download path relative to the instance's domain, other download parameters such as disposition can be appended
+        #[schema(example = "/api/media/download?id=HilrvkpJ&expires=1700000000&signature=Vv0y8Xo2cGx4Yd0vHhKk6oZ1lJxZJ3f8m2Vq0oGZk1Q")]
+        url: String,
+        /// When the link stops working in UTC Format
+        #[schema(value_type = String)]
+        expiration_date: DateTime::<Utc>
+    }
 
-        let media: Option<DBMedia> = media_database.iter()
-            .filter_map(|item| item.ok())
-            .filter_map(|item| {
-                let result: DBMedia = match serde_json::from_str(&String::from_utf8_lossy(&item.1)) {
-                    Ok(result) => result,
-                    Err(_) => return None
-                };
-                Some(result)
-            })
-            .find(|media| media.id == identification.id);
+    #[derive(Serialize, Deserialize, FromForm, IntoParams, Clone)]
+    pub struct ReplaceMediaQuery {
+        /// Id pointing to media
+        #[param(example = "HilrvkpJ")]
+        id: String,
+        /// Api key of the media's author or an admin
+        api_key: String
+    }
 
-        if let Some(media) = media {
-            let mut file = match File::open(&media.data_path) {
-                Ok(result) => result,
-                Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                    error: String::from("An internal error on the server's end has occurred")
-                })))
-            };
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct ReplacedMedia {
+        /// Id pointing to media, unchanged by the replacement
+        #[schema(example = "HilrvkpJ")]
+        id: String,
+        /// Revision of the new content
+        #[schema(example = 1)]
+        revision: u32
+    }
 
-            let mut upload_data = Vec::new();
-            if file.read_to_end(&mut upload_data).is_err() {
-                return Err(status::Custom(Status::InternalServerError, Json(Error {
-                    error: String::from("An internal error on the server's end has occurred")
-                })))
-            };
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct ContentRevision {
+        #[schema(example = 0)]
+        revision: u32,
+        #[schema(example = "image/png")]
+        content_mime_type: String,
+        content_extension: String,
+        /// Size of the content as uploaded
+        content_size: u64,
+        /// When the revision's content was stored in UTC Format
+        #[schema(value_type = String)]
+        upload_date: DateTime::<Utc>,
+        /// When the revision was replaced in UTC Format
+        #[schema(value_type = String)]
+        replaced_date: DateTime::<Utc>
+    }
 
-            let data: Vec<u8> = if media.data_compressed {
-                let mut writer = Vec::new();
-                let mut zlibdecoder = ZlibDecoder::new(writer);
-                if zlibdecoder.write_all(&upload_data).is_err() {
-                    return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("An internal error on the server's end has occurred")
-                    })))
-                };
-                writer = match zlibdecoder.finish() {
-                    Ok(result) => result,
-                    Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("An internal error on the server's end has occurred")
-                    })))
-                };
-                writer.to_vec()
-            } else {
-                upload_data
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct ContentRevisions {
+        /// Revision of the current content
+        revision: u32,
+        /// Previous revisions still kept, oldest first
+        revisions: Vec<ContentRevision>
+    }
+
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct DeleteRevision {
+        /// Id pointing to media
+        #[schema(example = "HilrvkpJ")]
+        id: String,
+        /// Previous revision to delete
+        #[schema(example = 0)]
+        revision: u32,
+        /// Api key of the media's author or an admin
+        api_key: String
+    }
+
+    /// Credentials for private & password protected media.
+    ///
+    /// Private media needs the api key of a user it's viewable by, sent as the `X-Api-Key` header or `api_key` query parameter.
+    /// Password protected media needs the `X-Media-Password` header
+    /// or an unlock token through the `X-Unlock-Token` header or `unlock_token` query parameter.
+    /// Links signed through /sign stand in for all of them
+    pub struct MediaAccess {
+        api_key: Option<String>,
+        password: Option<String>,
+        unlock_token: Option<String>,
+        signed_link: Option<SignedLink>,
+        // Where the request came from, wrong passwords are throttled by it
+        address: Option<IpAddr>,
+        // Read along with the request, so checking access doesn't lock the config next to the database
+        config: Config
+    }
+
+    /// The `signature`, `expires` & `ip` query parameters of a signed link
+    struct SignedLink {
+        signature: String,
+        expires: i64,
+        ip: Option<String>,
+        client_ip: Option<IpAddr>,
+        // Read along with the request, so rotating it revokes links straight away
+        secret: Option<String>
+    }
+
+    impl SignedLink {
+        fn check(&self, media_id: &str) -> Result<(), status::Custom<Json<Error>>> {
+            let forbidden = |error: &str| status::Custom(Status::Forbidden, Json(Error {
+                error: String::from(error)
+            }));
+
+            let secret = match &self.secret {
+                Some(result) => result,
+                None => return Err(forbidden("Signed links are disabled on this instance"))
             };
 
-            let mut edited_media = media.clone();
-            edited_media.downloads += 1;
+            if !media_utils::verify_download_signature(secret, media_id, self.expires, self.ip.as_deref(), &self.signature) {
+                return Err(forbidden("Signed link is invalid"))
+            }
 
-            if let Err(_) = media_database.update_and_fetch(&media.id, |_| {
-                Some(IVec::from(match serde_json::to_vec(&edited_media) {
-                    Ok(result) => result,
-                    Err(_) => return None
-                }))
-            }) {
-                return Err(status::Custom(Status::InternalServerError, Json(Error {
-                    error: String::from("An internal error on the server's end has occurred")
-                })))
+            if self.expires <= Utc::now().timestamp() {
+                return Err(forbidden("Signed link has expired"))
             }
 
-            let filename_extension = format!("{}.{}", media.name, media.extension);
-            Ok(
-                FileResponse {
-                    data: data,
-                    content_disposition: format!(r#"attachment; filename={};"#, filename_extension),
+            if let Some(ip) = &self.ip {
+                if self.client_ip.map(|client_ip| client_ip.to_string()).as_ref() != Some(ip) {
+                    return Err(forbidden("Signed link is bound to another address"))
                 }
-            )
-        } else {
-            Err(status::Custom(Status::InternalServerError, Json(Error {
-                error: String::from("An internal error on the server's end has occurred")
-            })))
+            }
+
+            Ok(())
         }
     }
 
-    // TODO: Add maximum return id's
-    // E.g. Limit amount of id's returned. This could be a problem when the database grows exponentially.
+    /// Address the request came from, only taken from the `X-Real-IP` header when the instance trusts its proxy to set it
+    fn client_address(request: &Request<'_>) -> Option<IpAddr> {
+        let trust_proxy_headers = request.rocket().state::<Arc<Mutex<Config>>>()
+            .and_then(|config| config.lock().ok())
+            .map(|config| config.backend_trust_proxy_headers)
+            .unwrap_or(false);
 
-    /// Searches all media id's in the form of a list
-    /// based on optional queries patterns
-    #[utoipa::path(
-        post,
-        context_path = "/api/media",
-        request_body = SearchQuery,
-        responses(
-            (status = 200, description = "Successfully found all media pertaining to the search query", body = ContentFound),
-            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
-        )
-    )]
-    #[post("/search", data = "<search>")]
-    pub async fn search(
-        _config_store: &State<Arc<Mutex<Config>>>,
-        database_store: &State<Arc<Mutex<sled::Db>>>,
-        search: Json<SearchQuery>
-    ) -> Result<Json<ContentFound>, status::Custom<Json<Error>>> {
-        let database = database_store.get_database()?;
-        let media_database = &database.get_tree("media")?;
-        let user_database = &database.get_tree("user")?;
+        match trust_proxy_headers {
+            true => request.client_ip(),
+            false => request.remote().map(|remote| remote.ip())
+        }
+    }
 
-        let user: Option<User> = if search.api_key.is_some() {
-            user_database
+    #[rocket::async_trait]
+    impl<'r> FromRequest<'r> for MediaAccess {
+        type Error = ();
+
+        async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+            let headers = request.headers();
+            // Links can't carry headers, so tokens are also taken from the query
+            let query_token = request.query_value::<String>("unlock_token").and_then(|token| token.ok());
+            let query_key = request.query_value::<String>("api_key").and_then(|key| key.ok());
+            let address = client_address(request);
+
+            let config = match request.rocket().state::<Arc<Mutex<Config>>>().and_then(|config| config.lock().ok()) {
+                Some(config) => config.clone(),
+                None => return request::Outcome::Failure((Status::InternalServerError, ()))
+            };
+
+            let signed_link = request.query_value::<String>("signature").and_then(|signature| signature.ok()).map(|signature| SignedLink {
+                signature,
+                // Links without a readable expiry count as expired
+                expires: request.query_value::<i64>("expires").and_then(|expires| expires.ok()).unwrap_or(0),
+                ip: request.query_value::<String>("ip").and_then(|ip| ip.ok()),
+                client_ip: address,
+                secret: config.backend_signing_secret.clone()
+            });
+
+            request::Outcome::Success(MediaAccess {
+                api_key: headers.get_one("X-Api-Key").map(String::from).or(query_key),
+                password: headers.get_one("X-Media-Password").map(String::from),
+                unlock_token: headers.get_one("X-Unlock-Token").map(String::from).or(query_token),
+                signed_link,
+                address,
+                config
+            })
+        }
+    }
+
+    impl MediaAccess {
+        fn check(&self, database: &sled::Db, media: &DBMedia) -> Result<(), status::Custom<Json<Error>>> {
+            if let Some(signed_link) = &self.signed_link {
+                return signed_link.check(&media.id)
+            }
+
+            media_utils::check_media_visibility(database, media, self.api_key.as_deref())?;
+            media_utils::check_media_access(&self.config, database, media, self.password.as_deref(), self.unlock_token.as_deref(), self.address)
+        }
+
+        /// Whether the media is password protected & nothing was given to unlock it with
+        fn is_locked(&self, media: &DBMedia) -> bool {
+            media.password.is_some() && self.signed_link.is_none() && self.password.is_none() && self.unlock_token.is_none()
+        }
+    }
+
+    /// Request headers that influence how a download is served
+    pub struct DownloadHeaders {
+        range: Option<String>,
+        if_range: Option<String>,
+        if_none_match: Option<String>,
+        if_modified_since: Option<String>,
+        accept_encoding: Option<String>,
+        // Page the request comes from, checked against the hotlink allowlist
+        source: Option<String>
+    }
+
+    #[rocket::async_trait]
+    impl<'r> FromRequest<'r> for DownloadHeaders {
+        type Error = ();
+
+        async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+            let headers = request.headers();
+
+            request::Outcome::Success(DownloadHeaders {
+                range: headers.get_one("Range").map(String::from),
+                if_range: headers.get_one("If-Range").map(String::from),
+                if_none_match: headers.get_one("If-None-Match").map(String::from),
+                if_modified_since: headers.get_one("If-Modified-Since").map(String::from),
+                accept_encoding: headers.get_one("Accept-Encoding").map(String::from),
+                source: headers.get_one("Origin").or_else(|| headers.get_one("Referer")).map(String::from)
+            })
+        }
+    }
+
+    pub struct FileResponse {
+        status: Status,
+        body: Option<Pin<Box<dyn AsyncRead + Send>>>,
+        // Length of the body when known up front
+        length: Option<u64>,
+        content_type: String,
+        content_disposition: String,
+        content_encoding: Option<&'static str>,
+        // Whether the response depends on the request's Accept-Encoding
+        vary_encoding: bool,
+        content_range: Option<String>,
+        accept_ranges: bool,
+        entity_tag: String,
+        last_modified: String
+    }
+
+    /// Body of media's last allowed download, purging the media once it's been sent or the client has gone away
+    struct LastDownload {
+        body: Pin<Box<dyn AsyncRead + Send>>,
+        database_arc: Arc<Mutex<sled::Db>>,
+        storage: Arc<dyn Storage>,
+        // Taken when purging
+        media: Option<DBMedia>
+    }
+
+    impl AsyncRead for LastDownload {
+        fn poll_read(mut self: Pin<&mut Self>, context: &mut Context<'_>, buffer: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+            self.body.as_mut().poll_read(context, buffer)
+        }
+    }
+
+    impl Drop for LastDownload {
+        fn drop(&mut self) {
+            let (database_arc, storage) = (self.database_arc.clone(), self.storage.clone());
+            let media = match self.media.take() {
+                Some(result) => result,
+                None => return
+            };
+
+            let purge = move || {
+                let mut released = Vec::new();
+
+                match database_arc.lock() {
+                    Ok(database) => if let Err(err) = media_utils::remove_media(&database, &media, &mut released) {
+                        error!("Failed to purge media {} after its last download, {}", media.id, err);
+                    },
+                    Err(_) => error!("Failed to lock database, media {} wasn't purged after its last download", media.id)
+                }
+
+                media_utils::delete_released(storage.as_ref(), &released);
+            };
+
+            // Storage may block, which isn't allowed on the runtime's threads
+            match rocket::tokio::runtime::Handle::try_current() {
+                Ok(handle) => drop(handle.spawn_blocking(purge)),
+                Err(_) => purge()
+            }
+        }
+    }
+
+    impl<'r> Responder<'r, 'static> for FileResponse {
+        fn respond_to(self, _: &'r rocket::Request<'_>) -> rocket::response::Result<'static> {
+            let mut response = Response::build();
+            response
+                .status(self.status)
+                .raw_header("accept-ranges", if self.accept_ranges { "bytes" } else { "none" })
+                .raw_header("etag", self.entity_tag)
+                .raw_header("last-modified", self.last_modified);
+
+            // Representation headers are left off of 304 responses
+            if self.status != Status::NotModified {
+                response
+                    .raw_header("content-type", self.content_type)
+                    .raw_header("content-disposition", self.content_disposition);
+
+                if let Some(content_encoding) = self.content_encoding {
+                    response.raw_header("content-encoding", content_encoding);
+                }
+            }
+
+            if self.vary_encoding {
+                response.raw_header("vary", "Accept-Encoding");
+            }
+
+            if let Some(content_range) = self.content_range {
+                response.raw_header("content-range", content_range);
+            }
+
+            match (self.body, self.length) {
+                (Some(body), Some(length)) => {
+                    response.sized_body(length as usize, KnownLength(body));
+                },
+                (Some(body), None) => {
+                    response.streamed_body(body);
+                },
+                _ => {}
+            }
+
+            response.ok()
+        }
+    }
+
+    /// A single byte range requested through the `Range` header
+    enum ByteRange {
+        /// Inclusive start & end of the range
+        Satisfiable(u64, u64),
+        Unsatisfiable
+    }
+
+    /// Parses a `Range` header against content of `length` bytes.
+    /// Returns `None` when the header should be ignored, such as when it's
+    /// malformed or requests multiple ranges
+    fn parse_range(header: &str, length: u64) -> Option<ByteRange> {
+        let range = header.trim().strip_prefix("bytes=")?.trim();
+
+        if range.contains(',') {
+            return None
+        }
+
+        let (start, end) = range.split_once('-')?;
+        let (start, end) = (start.trim(), end.trim());
+
+        if start.is_empty() {
+            let suffix: u64 = end.parse().ok()?;
+
+            if suffix == 0 || length == 0 {
+                return Some(ByteRange::Unsatisfiable)
+            }
+
+            return Some(ByteRange::Satisfiable(length.saturating_sub(suffix), length - 1))
+        }
+
+        let start: u64 = start.parse().ok()?;
+        let end: u64 = if end.is_empty() {
+            u64::MAX
+        } else {
+            end.parse().ok()?
+        };
+
+        if start > end {
+            return None
+        }
+
+        if start >= length {
+            return Some(ByteRange::Unsatisfiable)
+        }
+
+        Some(ByteRange::Satisfiable(start, end.min(length - 1)))
+    }
+
+    /// Length of the media's original contents, unknown for legacy compressed uploads
+    fn content_length(media: &DBMedia) -> Option<u64> {
+        match media_utils::media_layout(media) {
+            // Uploads predating data_length stored plain media with its original size
+            Layout::Plain => Some(media.data_length.unwrap_or(media.data_size as u64)),
+            _ => media.data_length
+        }
+    }
+
+    fn http_date(date: &DateTime::<Utc>) -> String {
+        date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+    }
+
+    /// Answers requests for media from pages it isn't allowed to be embedded on, `None` when the request may go on.
+    ///
+    /// Blocked requests get a 403, along with the instance's placeholder image when it has one
+    fn blocked_hotlink(config_store: &State<Arc<Mutex<Config>>>, media: &DBMedia, headers: &DownloadHeaders) -> Option<Result<FileResponse, status::Custom<Json<Error>>>> {
+        let placeholder = match config_store.lock() {
+            Ok(config) if media_utils::hotlink_allowed(&config, media, headers.source.as_deref()) => return None,
+            Ok(config) => config.media_hotlink_placeholder.clone(),
+            Err(_) => return Some(Err(media_utils::internal_error()))
+        };
+
+        let blocked = status::Custom(Status::Forbidden, Json(Error {
+            error: String::from("Media can't be embedded on this site")
+        }));
+
+        let path = match placeholder {
+            Some(result) => result,
+            None => return Some(Err(blocked))
+        };
+
+        let (data, modified) = match fs::read(&path).and_then(|data| Ok((data, fs::metadata(&path)?.modified()?))) {
+            Ok(result) => result,
+            Err(err) => {
+                error!("Failed to read hotlink placeholder {}, {}", path, err);
+                return Some(Err(blocked))
+            }
+        };
+
+        Some(Ok(FileResponse {
+            status: Status::Forbidden,
+            length: Some(data.len() as u64),
+            content_type: media_utils::infer_type(&data).mime_type().to_string(),
+            content_disposition: content_disposition(Disposition::Inline, "placeholder"),
+            content_encoding: None,
+            vary_encoding: false,
+            content_range: None,
+            accept_ranges: false,
+            entity_tag: format!(r#""{:x}""#, Sha256::digest(&data)),
+            last_modified: http_date(&DateTime::<Utc>::from(modified)),
+            body: Some(Box::pin(std::io::Cursor::new(data)))
+        }))
+    }
+
+    /// Whether an `If-Range` validator still matches the media, meaning the range can be honored
+    fn if_range_matches(if_range: &str, media: &DBMedia) -> bool {
+        let if_range = if_range.trim();
+
+        // Entity tags are compared strongly, weak tags never match
+        if if_range.starts_with('"') || if_range.starts_with("W/") {
+            return if_range == media_utils::entity_tag(media)
+        }
+
+        match DateTime::parse_from_rfc2822(if_range) {
+            Ok(date) => date.timestamp() == media_utils::content_date(media).timestamp(),
+            Err(_) => false
+        }
+    }
+
+    /// Whether the client's cached copy is still current according to
+    /// `If-None-Match`, or `If-Modified-Since` when no entity tags were sent.
+    /// Encoded & identity copies hold the same contents so either tag matches
+    fn is_not_modified(headers: &DownloadHeaders, media: &DBMedia, entity_tag: &str) -> bool {
+        if let Some(if_none_match) = &headers.if_none_match {
+            let identity_tag = media_utils::entity_tag(media);
+
+            return if_none_match.split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == "*" || tag == identity_tag || tag == entity_tag)
+        }
+
+        if let Some(if_modified_since) = &headers.if_modified_since {
+            if let Ok(date) = DateTime::parse_from_rfc2822(if_modified_since.trim()) {
+                return media_utils::content_date(media).timestamp() <= date.timestamp()
+            }
+        }
+
+        false
+    }
+
+    /// Whether an `Accept-Encoding` header allows the given content coding
+    fn accepts_encoding(accept_encoding: Option<&str>, encoding: &str) -> bool {
+        let accept_encoding = match accept_encoding {
+            Some(result) => result,
+            None => return false
+        };
+
+        let mut wildcard = false;
+
+        for coding in accept_encoding.split(',') {
+            let mut parameters = coding.split(';');
+            let name = parameters.next().unwrap_or_default().trim();
+            let quality = parameters
+                .filter_map(|parameter| parameter.trim().strip_prefix("q="))
+                .find_map(|quality| quality.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            if name.eq_ignore_ascii_case(encoding) {
+                return quality > 0.0
+            }
+
+            if name == "*" {
+                wildcard = quality > 0.0;
+            }
+        }
+
+        wildcard
+    }
+
+    /// Entity tag of the media's encoded representation, which has to differ from the identity one
+    fn encoded_entity_tag(entity_tag: &str, encoding: &str) -> String {
+        format!(r#"{}-{}""#, entity_tag.trim_end_matches('"'), encoding)
+    }
+
+    /// Disposition a download is served with, mime types the config marks as risky are never displayed inline
+    fn allowed_disposition(config_store: &State<Arc<Mutex<Config>>>, requested: Option<Disposition>, content_type: &str) -> Result<Disposition, status::Custom<Json<Error>>> {
+        let config = match config_store.lock() {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        // Parameters such as the charset are ignored
+        let essence = content_type.split(';').next().unwrap_or_default().trim();
+        if config.media_attachment_mime_types.iter().any(|mime_type| mime_type.eq_ignore_ascii_case(essence)) {
+            return Ok(Disposition::Attachment)
+        }
+
+        Ok(requested.unwrap_or(Disposition::Attachment))
+    }
+
+    /// Builds a content disposition header value, the plain filename is kept
+    /// as a fallback for clients not supporting the encoded `filename*`
+    fn content_disposition(disposition: Disposition, filename: &str) -> String {
+        let disposition = match disposition {
+            Disposition::Inline => "inline",
+            Disposition::Attachment => "attachment"
+        };
+
+        let fallback: String = filename.chars()
+            .map(|character| match character {
+                '"' | '\\' => '_',
+                character if character.is_ascii() && !character.is_ascii_control() => character,
+                _ => '_'
+            })
+            .collect();
+
+        format!(r#"{}; filename="{}"; filename*=UTF-8''{}"#, disposition, fallback, RawStr::new(filename).percent_encode())
+    }
+
+    /// Returns useful media information  
+    #[utoipa::path(
+        get,
+        context_path = "/api/media",
+        responses(
+            (status = 200, description = "Successfully grabbed media information, only its locked view while it's password protected & no password or unlock token was given", body = ContentDetails),
+            (status = 401, description = "Media is private & no api key was given, or the password or unlock token is wrong", body = Error),
+            (status = 403, description = "Media is private & hasn't been granted to the user, or the signed link is invalid or has expired", body = Error),
+            (status = 429, description = "Too many wrong passwords were given for the media", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            Media,
+            ("X-Api-Key" = Option<String>, Header, description = "Api key of a user able to view private media"),
+            ("api_key" = Option<String>, Query, description = "Api key of a user able to view private media, for links which can't send headers"),
+            ("X-Media-Password" = Option<String>, Header, description = "Password of password protected media"),
+            ("X-Unlock-Token" = Option<String>, Header, description = "Token from /unlock for password protected media"),
+            ("unlock_token" = Option<String>, Query, description = "Token from /unlock for password protected media, for links which can't send headers"),
+            ("expires" = Option<i64>, Query, description = "Expiry of a link signed through /sign"),
+            ("ip" = Option<String>, Query, description = "Address a link signed through /sign is bound to"),
+            ("signature" = Option<String>, Query, description = "Signature of a link signed through /sign, which stands in for the api key & password")
+        )
+    )]
+    #[get("/info?<identification..>")]
+    pub async fn info(
+        _config: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        identification: Media,
+        access: MediaAccess
+    ) -> Result<Json<ContentDetails>, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+        let media_database = &database.get_tree("media")?;
+
+        let media_vec: IVec = match media_database.get(&identification.id) {
+            Ok(result) => {
+                match result {
+                    Some(result) => {
+                        result
+                    },
+                    None => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                        error: String::from("An internal error on the server's end has occurred")
+                    })))
+                }
+            },
+            Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                error: String::from("Couldn't find media associated with id")
+            })))
+        };
+
+        let media: DBMedia = match serde_json::from_str(&String::from_utf8_lossy(&media_vec)) {
+            Ok(result) => result,
+            Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                error: String::from("An internal error on the server's end has occurred")
+            })))
+        };
+
+        // Enough is still shown of locked media to ask for its password
+        if access.is_locked(&media) {
+            media_utils::check_media_visibility(&database, &media, access.api_key.as_deref())?;
+
+            return Ok(Json(ContentDetails::Locked(LockedContentInfo {
+                id: media.id,
+                content_name: media.name,
+                password_protected: true
+            })))
+        }
+
+        access.check(&database, &media)?;
+
+        if media_utils::downloads_exhausted(&media) {
+            return Err(media_utils::downloads_exhausted_error())
+        }
+
+        Ok(Json(ContentDetails::Unlocked(Box::new(ContentInfo {
+            content_mime_type: media_utils::mime_type(&media),
+            author_username: media.author_username,
+            content_name: media.name,
+            content_size: media.data_size,
+            content_type: media.data_type,
+            content_extension: media.extension,
+            upload_date: media.upload_date,
+            unlisted: media.unlisted,
+            private: media.private,
+            tags: media.tags,
+            downloads: media.downloads,
+            content_metadata: media.metadata,
+            content_language: media.language,
+            expiration_date: media.expiration_date,
+            max_downloads: media.max_downloads,
+            password_protected: media.password.is_some(),
+            hotlink_hosts: media.hotlink_hosts,
+            revision: media.revision,
+            modified_date: media.modified_date
+        }))))
+    }
+
+    /// Exchanges the password of protected media for a token unlocking it for a while,
+    /// handy for links & embeds which can't send the password along as a header.
+    ///
+    /// Private media needs the api key of a user it's viewable by, just like /info & /download.
+    /// Wrong passwords are throttled per media & address
+    #[utoipa::path(
+        post,
+        context_path = "/api/media",
+        request_body = UnlockMedia,
+        responses(
+            (status = 200, description = "Successfully unlocked media", body = UnlockToken),
+            (status = 400, description = "Media isn't password protected", body = Error),
+            (status = 401, description = "Wrong password", body = Error),
+            (status = 404, description = "Media doesn't exist or isn't viewable with the given api key", body = Error),
+            (status = 429, description = "Too many wrong passwords were given for the media", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            ("X-Api-Key" = Option<String>, Header, description = "Api key of a user able to view private media"),
+            ("api_key" = Option<String>, Query, description = "Api key of a user able to view private media, for links which can't send headers")
+        )
+    )]
+    #[post("/unlock", data = "<body>")]
+    pub async fn unlock(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        access: MediaAccess,
+        body: Json<UnlockMedia>
+    ) -> Result<Json<UnlockToken>, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+        let media_database = &database.get_tree("media")?;
+
+        let config = match config_store.lock() {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        // Missing & hidden media answer alike, so ids of private media can't be probed
+        let not_found = || status::Custom(Status::NotFound, Json(Error {
+            error: String::from("Couldn't find media associated with id")
+        }));
+
+        let media: DBMedia = match media_database.get(&body.id) {
+            Ok(Some(result)) => match serde_json::from_str(&String::from_utf8_lossy(&result)) {
+                Ok(result) => result,
+                Err(_) => return Err(media_utils::internal_error())
+            },
+            Ok(None) => return Err(not_found()),
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        if media_utils::check_media_visibility(&database, &media, access.api_key.as_deref()).is_err() {
+            return Err(not_found())
+        }
+
+        if media.password.is_none() {
+            return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: String::from("Media isn't password protected")
+            })))
+        }
+
+        if !media_utils::attempt_media_password(&config, &media, &body.password, access.address)? {
+            return Err(status::Custom(Status::Unauthorized, Json(Error {
+                error: String::from("Wrong password")
+            })))
+        }
+
+        let unlock = media_utils::create_unlock_token(&config, &database, &media)?;
+
+        Ok(Json(UnlockToken {
+            token: unlock.token,
+            expiration_date: unlock.expiration_date
+        }))
+    }
+
+    /// Mints a download link that works without an api key or password until it expires,
+    /// optionally only from a single address. Changing the instance's signing secret revokes every link
+    #[utoipa::path(
+        post,
+        context_path = "/api/media",
+        request_body = SignMedia,
+        responses(
+            (status = 200, description = "Successfully signed a download link", body = SignedUrl),
+            (status = 400, description = "The expiry or address isn't valid", body = Error),
+            (status = 401, description = "An authentication issue has occurred", body = Error),
+            (status = 403, description = "Signed links are disabled on this instance", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        )
+    )]
+    #[post("/sign", data = "<body>")]
+    pub async fn sign(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        body: Json<SignMedia>
+    ) -> Result<Json<SignedUrl>, status::Custom<Json<Error>>> {
+        let config = match config_store.lock() {
+            Ok(result) => result.clone(),
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        let secret = match &config.backend_signing_secret {
+            Some(result) => result,
+            None => return Err(status::Custom(Status::Forbidden, Json(Error {
+                error: String::from("Signed links are disabled on this instance")
+            })))
+        };
+
+        let database = database_store.get_database()?;
+        let (media, user) = find_managed_media(&database, &body.id, &body.api_key)?;
+
+        if body.expires_in == 0 {
+            return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: String::from("Signed links have to stay valid for at least a second")
+            })))
+        }
+
+        if !user.admin && config.user_max_signed_url_expiration > 0 && body.expires_in > config.user_max_signed_url_expiration as u64 * 3600 {
+            return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: format!("Signed links can't stay valid for more than {} hours", config.user_max_signed_url_expiration)
+            })))
+        }
+
+        let expiration_date = match chrono::Duration::from_std(std::time::Duration::from_secs(body.expires_in)).ok()
+            .and_then(|duration| Utc::now().checked_add_signed(duration)) {
+            Some(result) => result,
+            None => return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: String::from("Signed link expires too far in the future")
+            })))
+        };
+
+        // Stored the way the client's address is written when it's compared
+        let ip = match &body.ip {
+            Some(ip) => match ip.trim().parse::<IpAddr>() {
+                Ok(result) => Some(result.to_string()),
+                Err(_) => return Err(status::Custom(Status::BadRequest, Json(Error {
+                    error: String::from("Address the link is bound to isn't a valid IP address")
+                })))
+            },
+            None => None
+        };
+
+        let expires = expiration_date.timestamp();
+        let signature = media_utils::sign_download(secret, &media.id, expires, ip.as_deref());
+
+        let mut url = format!("/api/media/download?id={}&expires={}", RawStr::new(&media.id).percent_encode(), expires);
+        if let Some(ip) = &ip {
+            url.push_str(&format!("&ip={}", RawStr::new(ip).percent_encode()));
+        }
+        url.push_str(&format!("&signature={}", signature));
+
+        Ok(Json(SignedUrl {
+            url,
+            expiration_date
+        }))
+    }
+
+    /// Returns file-disposition based file download
+    /// 
+    /// Supports single byte ranges through the `Range` & `If-Range` headers,
+    /// legacy compressed uploads are always returned whole.
+    /// Conditional requests are answered through `ETag` & `Last-Modified` validators.
+    /// Compressed media is sent as stored when the client accepts its `Content-Encoding`.
+    /// Media with a download limit is always sent whole & purged after its last download, HEAD requests aren't counted.
+    /// Links signed through /sign are accepted in place of an api key or password, they only reach the current revision.
+    /// With hotlink protection, requests from pages on hosts that aren't allowed (told by `Origin` or `Referer`) are refused.
+    /// Previous revisions are downloaded through `revision` & don't count as downloads
+    #[utoipa::path(
+        get,
+        context_path = "/api/media",
+        responses(
+            (status = 200, description = "Successfully found media"),
+            (status = 206, description = "Successfully found media, returning the requested range"),
+            (status = 304, description = "Media hasn't changed since the client's cached copy"),
+            (status = 410, description = "Media has reached its download limit", body = Error),
+            (status = 416, description = "Requested range is outside of the media's contents"),
+            (status = 401, description = "Media is private or password protected & no api key, password or unlock token was given", body = Error),
+            (status = 403, description = "Media is private & hasn't been granted to the user, the signed link is invalid, has expired or was used for a previous revision, or the media can't be embedded on the requesting site", body = Error),
+            (status = 404, description = "Revision doesn't exist or has been pruned", body = Error),
+            (status = 429, description = "Too many wrong passwords were given for the media", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            Media,
+            ("revision" = Option<u32>, Query, description = "Previous revision to download in place of the current content"),
+            ("X-Api-Key" = Option<String>, Header, description = "Api key of a user able to view private media"),
+            ("api_key" = Option<String>, Query, description = "Api key of a user able to view private media, for links which can't send headers"),
+            ("X-Media-Password" = Option<String>, Header, description = "Password of password protected media"),
+            ("X-Unlock-Token" = Option<String>, Header, description = "Token from /unlock for password protected media"),
+            ("unlock_token" = Option<String>, Query, description = "Token from /unlock for password protected media, for links which can't send headers"),
+            ("expires" = Option<i64>, Query, description = "Expiry of a link signed through /sign"),
+            ("ip" = Option<String>, Query, description = "Address a link signed through /sign is bound to"),
+            ("signature" = Option<String>, Query, description = "Signature of a link signed through /sign, which stands in for the api key & password"),
+            ("disposition" = Option<Disposition>, Query, description = "Whether to display the media inline or download it as an attachment, defaults to attachment. Mime types able to run scripts (e.g. HTML & SVG) are always attachments"),
+            ("Range" = Option<String>, Header, description = "Single byte range to return, e.g. bytes=0-1023"),
+            ("If-Range" = Option<String>, Header, description = "Only honor Range if the media still matches this entity tag or date"),
+            ("If-None-Match" = Option<String>, Header, description = "Entity tags of the client's cached copies"),
+            ("If-Modified-Since" = Option<String>, Header, description = "Date of the client's cached copy"),
+            ("Accept-Encoding" = Option<String>, Header, description = "Content codings the client accepts, e.g. deflate, zstd or br")
+        )
+    )]
+    #[get("/download?<disposition>&<revision>&<identification..>")]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn download(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        identification: Media,
+        disposition: Option<Disposition>,
+        revision: Option<u32>,
+        headers: DownloadHeaders,
+        access: MediaAccess
+    ) -> Result<FileResponse, status::Custom<Json<Error>>> {
+        send_download(config_store, database_store, storage_store, keyring_store, identification, disposition, revision, headers, access, false).await
+    }
+
+    /// Answers HEAD requests for downloads, which Rocket would otherwise hand to the GET route with the method rewritten.
+    /// They're never counted as downloads, so link previews can't use up media with a download limit
+    #[head("/download?<disposition>&<revision>&<identification..>")]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn download_head(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        identification: Media,
+        disposition: Option<Disposition>,
+        revision: Option<u32>,
+        headers: DownloadHeaders,
+        access: MediaAccess
+    ) -> Result<FileResponse, status::Custom<Json<Error>>> {
+        send_download(config_store, database_store, storage_store, keyring_store, identification, disposition, revision, headers, access, true).await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn send_download(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        identification: Media,
+        disposition: Option<Disposition>,
+        revision: Option<u32>,
+        headers: DownloadHeaders,
+        access: MediaAccess,
+        head: bool
+    ) -> Result<FileResponse, status::Custom<Json<Error>>> {
+        let (media, revision): (DBMedia, Option<MediaRevision>) = {
+            let database = database_store.get_database()?;
+            let media_database = &database.get_tree("media")?;
+
+            let media: DBMedia = match media_database.get(&identification.id) {
+                Ok(Some(result)) => match serde_json::from_str(&String::from_utf8_lossy(&result)) {
+                    Ok(result) => result,
+                    Err(_) => return Err(media_utils::internal_error())
+                },
+                _ => return Err(media_utils::internal_error())
+            };
+
+            access.check(&database, &media)?;
+
+            let revision = match revision {
+                // Signed links are made for sharing what the media currently holds
+                Some(_) if access.signed_link.is_some() => return Err(status::Custom(Status::Forbidden, Json(Error {
+                    error: String::from("Signed links only reach the current revision")
+                }))),
+                Some(revision) => match media_utils::find_revision(&database.get_tree("revision")?, &media.id, revision) {
+                    Some(result) => Some(result),
+                    None => return Err(status::Custom(Status::NotFound, Json(Error {
+                        error: String::from("Revision doesn't exist or has been pruned")
+                    })))
+                },
+                None => None
+            };
+
+            (media, revision)
+        };
+
+        if media_utils::downloads_exhausted(&media) {
+            return Err(media_utils::downloads_exhausted_error())
+        }
+
+        // Checked before the download is counted
+        if let Some(blocked) = blocked_hotlink(config_store, &media, &headers) {
+            return blocked
+        }
+
+        // Previous revisions are served from their own content, access & limits still follow the media
+        let content = match &revision {
+            Some(revision) => &revision.media,
+            None => &media
+        };
+
+        let layout = media_utils::media_layout(content);
+        let length = content_length(content);
+        // Every request for media with a download limit counts, so it's always sent whole
+        let limited = media.max_downloads.is_some() && revision.is_none();
+
+        let mut range = match (&headers.range, length) {
+            (Some(range), Some(length)) if !limited => parse_range(range, length),
+            _ => None
+        };
+
+        if let Some(if_range) = &headers.if_range {
+            if !if_range_matches(if_range, content) {
+                range = None;
+            }
+        }
+
+        // Ranges are served from the original contents, only whole downloads skip decompressing
+        let stored_encoding = blob::content_encoding(&layout);
+        let content_encoding = stored_encoding
+            .filter(|encoding| range.is_none() && accepts_encoding(headers.accept_encoding.as_deref(), encoding));
+
+        let entity_tag = match content_encoding {
+            Some(encoding) => encoded_entity_tag(&media_utils::entity_tag(content), encoding),
+            None => media_utils::entity_tag(content)
+        };
+
+        let filename_extension = format!("{}.{}", media.name, content.extension);
+        let content_type = media_utils::mime_type(content);
+        let disposition = allowed_disposition(config_store, disposition, &content_type)?;
+
+        let mut response = FileResponse {
+            status: Status::Ok,
+            body: None,
+            length,
+            content_type,
+            content_disposition: content_disposition(disposition, &filename_extension),
+            content_encoding,
+            vary_encoding: stored_encoding.is_some(),
+            content_range: None,
+            accept_ranges: length.is_some() && !limited,
+            entity_tag,
+            last_modified: http_date(&media_utils::content_date(content))
+        };
+
+        if is_not_modified(&headers, content, &response.entity_tag) {
+            response.status = Status::NotModified;
+            response.length = None;
+            return Ok(response)
+        }
+
+        let (start, end) = match (range, length) {
+            (Some(ByteRange::Unsatisfiable), Some(length)) => {
+                response.status = Status::RangeNotSatisfiable;
+                response.length = None;
+                response.content_range = Some(format!("bytes */{}", length));
+                return Ok(response)
+            },
+            (Some(ByteRange::Satisfiable(start, end)), Some(length)) => {
+                response.status = Status::PartialContent;
+                response.length = Some(end - start + 1);
+                response.content_range = Some(format!("bytes {}-{}/{}", start, end, length));
+                (start, end)
+            },
+            (_, length) => (0, length.unwrap_or(u64::MAX).saturating_sub(1))
+        };
+
+        let reader = match content_encoding {
+            // Encoded responses are the stored bytes as-is
+            Some(_) => {
+                response.length = Some(media_utils::stored_length(content));
+                media_utils::open_stored_blob(storage_store.inner(), keyring_store, content)
+            },
+            None => media_utils::open_blob(storage_store.inner(), keyring_store, content).map(|mut reader| {
+                reader.set_range(start, end);
+                reader
+            })
+        };
+
+        response.body = match reader {
+            Ok(result) => Some(Box::pin(blob::spawn_reader(result))),
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        // Media players request many ranges while seeking, only count downloads from the start
+        if start == 0 && revision.is_none() && !head {
+            let database = database_store.get_database()?;
+            let media_database = &database.get_tree("media")?;
+
+            // Checked & incremented within a single compare & swap, so concurrent downloads can't both take the last one
+            let previous_media = match media_database.fetch_and_update(&media.id, |media_vec| {
+                let mut edited_media: DBMedia = serde_json::from_str(&String::from_utf8_lossy(media_vec?)).ok()?;
+
+                if !media_utils::downloads_exhausted(&edited_media) {
+                    edited_media.downloads += 1;
+                }
+
+                Some(IVec::from(match serde_json::to_vec(&edited_media) {
+                    Ok(result) => result,
+                    Err(_) => return None
+                }))
+            }) {
+                Ok(Some(result)) => match serde_json::from_str::<DBMedia>(&String::from_utf8_lossy(&result)) {
+                    Ok(result) => result,
+                    Err(_) => return Err(media_utils::internal_error())
+                },
+                // Purged since it was looked up
+                Ok(None) => return Err(media_utils::downloads_exhausted_error()),
+                Err(_) => return Err(media_utils::internal_error())
+            };
+
+            if media_utils::downloads_exhausted(&previous_media) {
+                return Err(media_utils::downloads_exhausted_error())
+            }
+
+            if matches!(previous_media.max_downloads, Some(max_downloads) if previous_media.downloads + 1 >= max_downloads as i64) {
+                response.body = response.body.map(|body| -> Pin<Box<dyn AsyncRead + Send>> {
+                    Box::pin(LastDownload {
+                        body,
+                        database_arc: database_store.inner().clone(),
+                        storage: storage_store.inner().clone(),
+                        media: Some(previous_media)
+                    })
+                });
+            }
+        }
+
+        Ok(response)
+    }
+
+    /// Returns a downscaled copy of an image upload, fitting within a `size` by `size` box
+    ///
+    /// Sizes are rounded up to one of 64, 128, 256 or 512 pixels, defaulting to 256.
+    /// Thumbnails are made on first request and cached until the media is deleted
+    #[utoipa::path(
+        get,
+        context_path = "/api/media",
+        responses(
+            (status = 200, description = "Successfully made or found the thumbnail"),
+            (status = 304, description = "Thumbnail hasn't changed since the client's cached copy"),
+            (status = 400, description = "Media isn't an image", body = Error),
+            (status = 415, description = "Image's format can't be decoded", body = Error),
+            (status = 401, description = "Media is private or password protected & no api key, password or unlock token was given", body = Error),
+            (status = 403, description = "Media is private & hasn't been granted to the user, the signed link is invalid or has expired, or the media can't be embedded on the requesting site", body = Error),
+            (status = 429, description = "Too many wrong passwords were given for the media", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            Media,
+            ("X-Api-Key" = Option<String>, Header, description = "Api key of a user able to view private media"),
+            ("api_key" = Option<String>, Query, description = "Api key of a user able to view private media, for links which can't send headers"),
+            ("X-Media-Password" = Option<String>, Header, description = "Password of password protected media"),
+            ("X-Unlock-Token" = Option<String>, Header, description = "Token from /unlock for password protected media"),
+            ("unlock_token" = Option<String>, Query, description = "Token from /unlock for password protected media, for links which can't send headers"),
+            ("expires" = Option<i64>, Query, description = "Expiry of a link signed through /sign"),
+            ("ip" = Option<String>, Query, description = "Address a link signed through /sign is bound to"),
+            ("signature" = Option<String>, Query, description = "Signature of a link signed through /sign, which stands in for the api key & password"),
+            ("size" = Option<u32>, Query, description = "Largest width or height of the thumbnail in pixels, defaults to 256"),
+            ("format" = Option<ImageFormat>, Query, description = "Image format of the thumbnail, defaults to jpeg"),
+            ("If-None-Match" = Option<String>, Header, description = "Entity tags of the client's cached copies"),
+            ("If-Modified-Since" = Option<String>, Header, description = "Date of the client's cached copy")
+        )
+    )]
+    #[get("/thumbnail?<size>&<format>&<identification..>")]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn thumbnail(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        identification: Media,
+        size: Option<u32>,
+        format: Option<ImageFormat>,
+        headers: DownloadHeaders,
+        access: MediaAccess
+    ) -> Result<FileResponse, status::Custom<Json<Error>>> {
+        let media = find_image(database_store, &identification, &access)?;
+
+        let size = processing::thumbnail::thumbnail_size(size.unwrap_or(256));
+        let format = format.unwrap_or(ImageFormat::Jpeg);
+        let name = processing::thumbnail::variant_name(size, format);
+        let transform = processing::thumbnail::thumbnail_transform(size, format);
+
+        image_variant(config_store, database_store, storage_store, keyring_store, media, &name, transform, &headers).await
+    }
+
+    /// Returns an image upload resized and/or converted to another format
+    ///
+    /// Widths & heights are limited to the instance's `media_transform_sizes`.
+    /// Images are never enlarged when fit is contain, cover & fill only apply when both width & height are given.
+    /// Transformed images are cached until the media is deleted
+    #[utoipa::path(
+        get,
+        context_path = "/api/media",
+        responses(
+            (status = 200, description = "Successfully transformed the image"),
+            (status = 304, description = "Transformed image hasn't changed since the client's cached copy"),
+            (status = 400, description = "Media isn't an image or the parameters aren't allowed", body = Error),
+            (status = 415, description = "Image's format can't be decoded", body = Error),
+            (status = 401, description = "Media is private or password protected & no api key, password or unlock token was given", body = Error),
+            (status = 403, description = "Media is private & hasn't been granted to the user, the signed link is invalid or has expired, or the media can't be embedded on the requesting site", body = Error),
+            (status = 429, description = "Too many wrong passwords were given for the media", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            Media,
+            ("X-Api-Key" = Option<String>, Header, description = "Api key of a user able to view private media"),
+            ("api_key" = Option<String>, Query, description = "Api key of a user able to view private media, for links which can't send headers"),
+            ("X-Media-Password" = Option<String>, Header, description = "Password of password protected media"),
+            ("X-Unlock-Token" = Option<String>, Header, description = "Token from /unlock for password protected media"),
+            ("unlock_token" = Option<String>, Query, description = "Token from /unlock for password protected media, for links which can't send headers"),
+            ("expires" = Option<i64>, Query, description = "Expiry of a link signed through /sign"),
+            ("ip" = Option<String>, Query, description = "Address a link signed through /sign is bound to"),
+            ("signature" = Option<String>, Query, description = "Signature of a link signed through /sign, which stands in for the api key & password"),
+            ("width" = Option<u32>, Query, description = "Width of the box the image is resized into"),
+            ("height" = Option<u32>, Query, description = "Height of the box the image is resized into"),
+            ("fit" = Option<ImageFit>, Query, description = "How the image is resized into the box, defaults to contain"),
+            ("format" = Option<ImageFormat>, Query, description = "Image format to encode in, defaults to the media's own format or png"),
+            ("quality" = Option<u8>, Query, description = "Jpeg quality between 1 and 100, defaults to 80"),
+            ("If-None-Match" = Option<String>, Header, description = "Entity tags of the client's cached copies"),
+            ("If-Modified-Since" = Option<String>, Header, description = "Date of the client's cached copy")
+        )
+    )]
+    #[get("/download/transform?<width>&<height>&<fit>&<format>&<quality>&<identification..>")]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn transform(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        identification: Media,
+        width: Option<u32>,
+        height: Option<u32>,
+        fit: Option<ImageFit>,
+        format: Option<ImageFormat>,
+        quality: Option<u8>,
+        headers: DownloadHeaders,
+        access: MediaAccess
+    ) -> Result<FileResponse, status::Custom<Json<Error>>> {
+        let media = find_image(database_store, &identification, &access)?;
+
+        {
+            let config = match config_store.lock() {
+                Ok(result) => result,
+                Err(_) => return Err(media_utils::internal_error())
+            };
+
+            if [width, height].iter().flatten().any(|size| !config.media_transform_sizes.contains(size)) {
+                return Err(status::Custom(Status::BadRequest, Json(Error {
+                    error: format!("Width & height must be one of {}", config.media_transform_sizes.iter().join(", "))
+                })))
+            }
+        }
+
+        let quality = quality.unwrap_or(processing::transform::DEFAULT_QUALITY);
+        if !(1..=100).contains(&quality) {
+            return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: String::from("Quality must be between 1 and 100")
+            })))
+        }
+
+        let transform = processing::transform::Transform {
+            width,
+            height,
+            fit: fit.unwrap_or(ImageFit::Contain),
+            format: format
+                .or_else(|| processing::transform::media_format(&media))
+                .unwrap_or(ImageFormat::Png),
+            quality
+        };
+
+        let name = format!("transform-{}.{}", transform.hash(), processing::transform::extension(transform.format));
+
+        image_variant(config_store, database_store, storage_store, keyring_store, media, &name, transform, &headers).await
+    }
+
+    /// Finds the identified media, making sure it's an image
+    fn find_image(database_store: &State<Arc<Mutex<sled::Db>>>, identification: &Media, access: &MediaAccess) -> Result<DBMedia, status::Custom<Json<Error>>> {
+        find_typed_media(database_store, identification, access, ContentType::Image, "Only images can be transformed")
+    }
+
+    /// Finds the identified media, making sure it's text
+    fn find_text(database_store: &State<Arc<Mutex<sled::Db>>>, identification: &Media, access: &MediaAccess) -> Result<DBMedia, status::Custom<Json<Error>>> {
+        find_typed_media(database_store, identification, access, ContentType::Text, "Only text uploads can be viewed as text")
+    }
+
+    /// Finds the identified media the request may access, failing with `error` when it isn't of the given content type
+    fn find_typed_media(
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        identification: &Media,
+        access: &MediaAccess,
+        content_type: ContentType,
+        error: &str
+    ) -> Result<DBMedia, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+        let media_database = &database.get_tree("media")?;
+
+        let media: DBMedia = match media_database.get(&identification.id) {
+            Ok(Some(result)) => match serde_json::from_str(&String::from_utf8_lossy(&result)) {
+                Ok(result) => result,
+                Err(_) => return Err(media_utils::internal_error())
+            },
+            _ => return Err(media_utils::internal_error())
+        };
+
+        access.check(&database, &media)?;
+
+        if media.data_type != content_type {
+            return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: String::from(error)
+            })))
+        }
+
+        // Previews would get around the download limit
+        if media.max_downloads.is_some() {
+            return Err(status::Custom(Status::Forbidden, Json(Error {
+                error: String::from("Media with a download limit can only be downloaded")
+            })))
+        }
+
+        Ok(media)
+    }
+
+    /// Serves the image variant `name`, transforming & caching it first when it isn't cached yet
+    #[allow(clippy::too_many_arguments)]
+    async fn image_variant(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        media: DBMedia,
+        name: &str,
+        transform: processing::transform::Transform,
+        headers: &DownloadHeaders
+    ) -> Result<FileResponse, status::Custom<Json<Error>>> {
+        if let Some(blocked) = blocked_hotlink(config_store, &media, headers) {
+            return blocked
+        }
+
+        let extension = processing::transform::extension(transform.format);
+
+        let mut response = FileResponse {
+            status: Status::Ok,
+            body: None,
+            length: None,
+            content_type: processing::transform::mime_type(transform.format).to_string(),
+            content_disposition: content_disposition(Disposition::Inline, &format!("{}.{}", media.name, extension)),
+            content_encoding: None,
+            vary_encoding: false,
+            content_range: None,
+            accept_ranges: false,
+            entity_tag: encoded_entity_tag(&media_utils::entity_tag(&media), name.trim_end_matches(&format!(".{}", extension))),
+            last_modified: http_date(&media_utils::content_date(&media))
+        };
+
+        if is_not_modified(headers, &media, &response.entity_tag) {
+            response.status = Status::NotModified;
+            return Ok(response)
+        }
+
+        let staged_path = {
+            let config = match config_store.lock() {
+                Ok(result) => result,
+                Err(_) => return Err(media_utils::internal_error())
+            };
+
+            media_utils::staging_path(&config)?
+        };
+
+        let variant_database = {
+            let database = database_store.get_database()?;
+            database.get_tree("variant")?
+        };
+
+        let storage = storage_store.inner().clone();
+        let keyring = keyring_store.inner().clone();
+        let name = name.to_string();
+
+        let image = match rocket::tokio::task::spawn_blocking(move || {
+            processing::transform::cached_transform(&variant_database, &storage, &keyring, &media, &name, &transform, &staged_path)
+        }).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(error)) if error.kind() == std::io::ErrorKind::Unsupported => return Err(status::Custom(Status::UnsupportedMediaType, Json(Error {
+                error: String::from("Image's format isn't supported for transforming")
+            }))),
+            _ => return Err(media_utils::internal_error())
+        };
+
+        response.length = Some(image.len() as u64);
+        response.body = Some(Box::pin(std::io::Cursor::new(image)));
+
+        Ok(response)
+    }
+
+    /// Returns a text upload as plain UTF-8 text, displayed inline whatever it contains
+    #[utoipa::path(
+        get,
+        context_path = "/api/media",
+        responses(
+            (status = 200, description = "Successfully found the text"),
+            (status = 304, description = "Text hasn't changed since the client's cached copy"),
+            (status = 400, description = "Media isn't a text upload", body = Error),
+            (status = 401, description = "Media is private or password protected & no api key, password or unlock token was given", body = Error),
+            (status = 403, description = "Media is private & hasn't been granted to the user, the signed link is invalid or has expired, or the media can't be embedded on the requesting site", body = Error),
+            (status = 429, description = "Too many wrong passwords were given for the media", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            Media,
+            ("X-Api-Key" = Option<String>, Header, description = "Api key of a user able to view private media"),
+            ("api_key" = Option<String>, Query, description = "Api key of a user able to view private media, for links which can't send headers"),
+            ("X-Media-Password" = Option<String>, Header, description = "Password of password protected media"),
+            ("X-Unlock-Token" = Option<String>, Header, description = "Token from /unlock for password protected media"),
+            ("unlock_token" = Option<String>, Query, description = "Token from /unlock for password protected media, for links which can't send headers"),
+            ("expires" = Option<i64>, Query, description = "Expiry of a link signed through /sign"),
+            ("ip" = Option<String>, Query, description = "Address a link signed through /sign is bound to"),
+            ("signature" = Option<String>, Query, description = "Signature of a link signed through /sign, which stands in for the api key & password"),
+            ("If-None-Match" = Option<String>, Header, description = "Entity tags of the client's cached copies"),
+            ("If-Modified-Since" = Option<String>, Header, description = "Date of the client's cached copy")
+        )
+    )]
+    #[get("/text/raw?<identification..>")]
+    pub async fn text_raw(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        identification: Media,
+        headers: DownloadHeaders,
+        access: MediaAccess
+    ) -> Result<FileResponse, status::Custom<Json<Error>>> {
+        let media = find_text(database_store, &identification, &access)?;
+
+        if let Some(blocked) = blocked_hotlink(config_store, &media, &headers) {
+            return blocked
+        }
+
+        let mut response = FileResponse {
+            status: Status::Ok,
+            body: None,
+            length: content_length(&media),
+            content_type: String::from("text/plain; charset=utf-8"),
+            content_disposition: content_disposition(Disposition::Inline, &format!("{}.{}", media.name, media.extension)),
+            content_encoding: None,
+            vary_encoding: false,
+            content_range: None,
+            accept_ranges: false,
+            entity_tag: media_utils::entity_tag(&media),
+            last_modified: http_date(&media_utils::content_date(&media))
+        };
+
+        if is_not_modified(&headers, &media, &response.entity_tag) {
+            response.status = Status::NotModified;
+            response.length = None;
+            return Ok(response)
+        }
+
+        response.body = match media_utils::open_blob(storage_store.inner(), keyring_store, &media) {
+            Ok(result) => Some(Box::pin(blob::spawn_reader(result))),
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        Ok(response)
+    }
+
+    /// Returns the first lines of a text upload
+    ///
+    /// Lines default to 10 and are limited to the instance's `media_text_preview_lines`,
+    /// at most 64 kilobytes of text are looked at
+    #[utoipa::path(
+        get,
+        context_path = "/api/media",
+        responses(
+            (status = 200, description = "Successfully previewed the text", body = TextPreview),
+            (status = 400, description = "Media isn't a text upload or too many lines were requested", body = Error),
+            (status = 401, description = "Media is private or password protected & no api key, password or unlock token was given", body = Error),
+            (status = 403, description = "Media is private & hasn't been granted to the user, or the signed link is invalid or has expired", body = Error),
+            (status = 429, description = "Too many wrong passwords were given for the media", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            Media,
+            ("X-Api-Key" = Option<String>, Header, description = "Api key of a user able to view private media"),
+            ("api_key" = Option<String>, Query, description = "Api key of a user able to view private media, for links which can't send headers"),
+            ("X-Media-Password" = Option<String>, Header, description = "Password of password protected media"),
+            ("X-Unlock-Token" = Option<String>, Header, description = "Token from /unlock for password protected media"),
+            ("unlock_token" = Option<String>, Query, description = "Token from /unlock for password protected media, for links which can't send headers"),
+            ("expires" = Option<i64>, Query, description = "Expiry of a link signed through /sign"),
+            ("ip" = Option<String>, Query, description = "Address a link signed through /sign is bound to"),
+            ("signature" = Option<String>, Query, description = "Signature of a link signed through /sign, which stands in for the api key & password"),
+            ("lines" = Option<u32>, Query, description = "Amount of lines to return, defaults to 10")
+        )
+    )]
+    #[get("/text/preview?<lines>&<identification..>")]
+    pub async fn text_preview(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        identification: Media,
+        lines: Option<u32>,
+        access: MediaAccess
+    ) -> Result<Json<TextPreview>, status::Custom<Json<Error>>> {
+        let media = find_text(database_store, &identification, &access)?;
+
+        let lines = lines.unwrap_or(10);
+        {
+            let config = match config_store.lock() {
+                Ok(result) => result,
+                Err(_) => return Err(media_utils::internal_error())
+            };
+
+            if lines == 0 || lines > config.media_text_preview_lines {
+                return Err(status::Custom(Status::BadRequest, Json(Error {
+                    error: format!("Lines must be between 1 and {}", config.media_text_preview_lines)
+                })))
+            }
+        }
+
+        let reader = match media_utils::open_blob(storage_store.inner(), keyring_store, &media) {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        let (content, truncated) = match rocket::tokio::task::spawn_blocking(move || {
+            processing::text::preview(reader, lines as usize)
+        }).await {
+            Ok(Ok(result)) => result,
+            _ => return Err(media_utils::internal_error())
+        };
+
+        Ok(Json(TextPreview {
+            language: media.language,
+            content,
+            truncated
+        }))
+    }
+
+    /// Returns a text upload rendered as a syntax highlighted HTML page
+    ///
+    /// Text is highlighted according to its language, or shown as plain text when it has none.
+    /// Only text up to the instance's `media_text_highlight_size` can be highlighted.
+    /// Pages are rendered on first request and cached until the media is deleted
+    #[utoipa::path(
+        get,
+        context_path = "/api/media",
+        responses(
+            (status = 200, description = "Successfully highlighted the text"),
+            (status = 304, description = "Highlighted text hasn't changed since the client's cached copy"),
+            (status = 400, description = "Media isn't a text upload or is too large to highlight", body = Error),
+            (status = 401, description = "Media is private or password protected & no api key, password or unlock token was given", body = Error),
+            (status = 403, description = "Media is private & hasn't been granted to the user, the signed link is invalid or has expired, or the media can't be embedded on the requesting site", body = Error),
+            (status = 429, description = "Too many wrong passwords were given for the media", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            Media,
+            ("X-Api-Key" = Option<String>, Header, description = "Api key of a user able to view private media"),
+            ("api_key" = Option<String>, Query, description = "Api key of a user able to view private media, for links which can't send headers"),
+            ("X-Media-Password" = Option<String>, Header, description = "Password of password protected media"),
+            ("X-Unlock-Token" = Option<String>, Header, description = "Token from /unlock for password protected media"),
+            ("unlock_token" = Option<String>, Query, description = "Token from /unlock for password protected media, for links which can't send headers"),
+            ("expires" = Option<i64>, Query, description = "Expiry of a link signed through /sign"),
+            ("ip" = Option<String>, Query, description = "Address a link signed through /sign is bound to"),
+            ("signature" = Option<String>, Query, description = "Signature of a link signed through /sign, which stands in for the api key & password"),
+            ("If-None-Match" = Option<String>, Header, description = "Entity tags of the client's cached copies"),
+            ("If-Modified-Since" = Option<String>, Header, description = "Date of the client's cached copy")
+        )
+    )]
+    #[get("/text/highlight?<identification..>")]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn text_highlight(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        highlighter_store: &State<Arc<Highlighter>>,
+        identification: Media,
+        headers: DownloadHeaders,
+        access: MediaAccess
+    ) -> Result<FileResponse, status::Custom<Json<Error>>> {
+        let media = find_text(database_store, &identification, &access)?;
+
+        if let Some(blocked) = blocked_hotlink(config_store, &media, &headers) {
+            return blocked
+        }
+
+        let staged_path = {
+            let config = match config_store.lock() {
+                Ok(result) => result,
+                Err(_) => return Err(media_utils::internal_error())
+            };
+
+            let size_limit = config.media_text_highlight_size as u64 * 1000;
+            if content_length(&media).unwrap_or(u64::MAX) > size_limit {
+                return Err(status::Custom(Status::BadRequest, Json(Error {
+                    error: format!("Text is too large to highlight. Maximum of {} kilobytes", config.media_text_highlight_size)
+                })))
+            }
+
+            media_utils::staging_path(&config)?
+        };
+
+        let title = format!("{}.{}", media.name, media.extension);
+        let name = processing::text::highlight_variant_name(media.language.as_deref(), &title);
+
+        let mut response = FileResponse {
+            status: Status::Ok,
+            body: None,
+            length: None,
+            content_type: String::from("text/html; charset=utf-8"),
+            content_disposition: content_disposition(Disposition::Inline, &format!("{}.html", title)),
+            content_encoding: None,
+            vary_encoding: false,
+            content_range: None,
+            accept_ranges: false,
+            entity_tag: encoded_entity_tag(&media_utils::entity_tag(&media), name.trim_end_matches(".html")),
+            last_modified: http_date(&media_utils::content_date(&media))
+        };
+
+        if is_not_modified(&headers, &media, &response.entity_tag) {
+            response.status = Status::NotModified;
+            return Ok(response)
+        }
+
+        let variant_database = {
+            let database = database_store.get_database()?;
+            database.get_tree("variant")?
+        };
+
+        let storage = storage_store.inner().clone();
+        let keyring = keyring_store.inner().clone();
+        let highlighter = highlighter_store.inner().clone();
+
+        let page = match rocket::tokio::task::spawn_blocking(move || {
+            processing::text::cached_highlight(&variant_database, &storage, &keyring, &highlighter, &media, &staged_path)
+        }).await {
+            Ok(Ok(result)) => result,
+            _ => return Err(media_utils::internal_error())
+        };
+
+        response.length = Some(page.len() as u64);
+        response.body = Some(Box::pin(std::io::Cursor::new(page)));
+
+        Ok(response)
+    }
+
+    /// Lists the files & directories within a zip, tar or tar.gz upload
+    ///
+    /// Only the archive's index is read for zip archives, tar archives are read up to their end.
+    /// At most `media_archive_max_entries` entries are listed
+    #[utoipa::path(
+        get,
+        context_path = "/api/media",
+        responses(
+            (status = 200, description = "Successfully listed the archive", body = ArchiveListing),
+            (status = 400, description = "Media isn't an archive or decompresses past the instance's limit", body = Error),
+            (status = 415, description = "Archive's format isn't supported or it's malformed", body = Error),
+            (status = 401, description = "Media is private or password protected & no api key, password or unlock token was given", body = Error),
+            (status = 403, description = "Media is private & hasn't been granted to the user, or the signed link is invalid or has expired", body = Error),
+            (status = 429, description = "Too many wrong passwords were given for the media", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            Media,
+            ("X-Api-Key" = Option<String>, Header, description = "Api key of a user able to view private media"),
+            ("api_key" = Option<String>, Query, description = "Api key of a user able to view private media, for links which can't send headers"),
+            ("X-Media-Password" = Option<String>, Header, description = "Password of password protected media"),
+            ("X-Unlock-Token" = Option<String>, Header, description = "Token from /unlock for password protected media"),
+            ("unlock_token" = Option<String>, Query, description = "Token from /unlock for password protected media, for links which can't send headers"),
+            ("expires" = Option<i64>, Query, description = "Expiry of a link signed through /sign"),
+            ("ip" = Option<String>, Query, description = "Address a link signed through /sign is bound to"),
+            ("signature" = Option<String>, Query, description = "Signature of a link signed through /sign, which stands in for the api key & password")
+        )
+    )]
+    #[get("/archive/list?<identification..>")]
+    pub async fn archive_list(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        identification: Media,
+        access: MediaAccess
+    ) -> Result<Json<ArchiveListing>, status::Custom<Json<Error>>> {
+        let (media, format) = find_archive(database_store, &identification, &access)?;
+        let limits = archive_limits(config_store)?;
+
+        let reader = match media_utils::open_blob(storage_store.inner(), keyring_store, &media) {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        let listing = match rocket::tokio::task::spawn_blocking(move || {
+            processing::archive::list(reader, format, limits)
+        }).await {
+            Ok(result) => result.map_err(|error| archive_error(error, &limits))?,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        Ok(Json(ArchiveListing {
+            entries: listing.entries,
+            truncated: listing.truncated,
+            skipped: listing.skipped
+        }))
+    }
+
+    /// Returns a single file from within a zip, tar or tar.gz upload
+    ///
+    /// Files are looked up by the path they're listed with.
+    /// Only files up to `media_archive_max_size` megabytes can be extracted and never more than their declared size is returned
+    #[utoipa::path(
+        get,
+        context_path = "/api/media",
+        responses(
+            (status = 200, description = "Successfully found the file within the archive"),
+            (status = 304, description = "File hasn't changed since the client's cached copy"),
+            (status = 400, description = "Media isn't an archive or the file is larger than the instance's limit", body = Error),
+            (status = 404, description = "Archive holds no file at the path", body = Error),
+            (status = 415, description = "Archive's format isn't supported or it's malformed", body = Error),
+            (status = 401, description = "Media is private or password protected & no api key, password or unlock token was given", body = Error),
+            (status = 403, description = "Media is private & hasn't been granted to the user, the signed link is invalid or has expired, or the media can't be embedded on the requesting site", body = Error),
+            (status = 429, description = "Too many wrong passwords were given for the media", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            Media,
+            ("X-Api-Key" = Option<String>, Header, description = "Api key of a user able to view private media"),
+            ("api_key" = Option<String>, Query, description = "Api key of a user able to view private media, for links which can't send headers"),
+            ("X-Media-Password" = Option<String>, Header, description = "Password of password protected media"),
+            ("X-Unlock-Token" = Option<String>, Header, description = "Token from /unlock for password protected media"),
+            ("unlock_token" = Option<String>, Query, description = "Token from /unlock for password protected media, for links which can't send headers"),
+            ("expires" = Option<i64>, Query, description = "Expiry of a link signed through /sign"),
+            ("ip" = Option<String>, Query, description = "Address a link signed through /sign is bound to"),
+            ("signature" = Option<String>, Query, description = "Signature of a link signed through /sign, which stands in for the api key & password"),
+            ("path" = String, Query, description = "Path of the file within the archive, e.g. logs/server.log"),
+            ("disposition" = Option<Disposition>, Query, description = "Whether to display the file inline or download it as an attachment, defaults to attachment. Mime types able to run scripts (e.g. HTML & SVG) are always attachments"),
+            ("If-None-Match" = Option<String>, Header, description = "Entity tags of the client's cached copies"),
+            ("If-Modified-Since" = Option<String>, Header, description = "Date of the client's cached copy")
+        )
+    )]
+    #[get("/archive/extract?<path>&<disposition>&<identification..>")]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn archive_extract(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        identification: Media,
+        path: String,
+        disposition: Option<Disposition>,
+        headers: DownloadHeaders,
+        access: MediaAccess
+    ) -> Result<FileResponse, status::Custom<Json<Error>>> {
+        let (media, format) = find_archive(database_store, &identification, &access)?;
+        let limits = archive_limits(config_store)?;
+
+        if let Some(blocked) = blocked_hotlink(config_store, &media, &headers) {
+            return blocked
+        }
+
+        // The archive is looked through twice, the response can't be failed anymore once streaming
+        let open = || match media_utils::open_blob(storage_store.inner(), keyring_store, &media) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(media_utils::internal_error())
+        };
+
+        let reader = open()?;
+        let find_path = path.clone();
+        let entry = match rocket::tokio::task::spawn_blocking(move || {
+            processing::archive::find(reader, format, &find_path, limits)
+        }).await {
+            Ok(result) => result.map_err(|error| archive_error(error, &limits))?,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        let entry = match entry {
+            Some(result) => result,
+            None => return Err(status::Custom(Status::NotFound, Json(Error {
+                error: format!("Couldn't find a file at {} within the archive", path)
+            })))
+        };
+
+        let filename = entry.path.rsplit('/').next().unwrap_or_default().to_string();
+        let content_type = filename.rsplit_once('.')
+            .and_then(|(_, extension)| HttpContentType::from_extension(extension))
+            .map(|content_type| content_type.to_string())
+            .unwrap_or_else(|| String::from("application/octet-stream"));
+
+        let disposition = allowed_disposition(config_store, disposition, &content_type)?;
+        let entry_hash = format!("{:x}", Sha256::digest(entry.path.as_bytes()));
+
+        let mut response = FileResponse {
+            status: Status::Ok,
+            body: None,
+            length: Some(entry.size),
+            content_type,
+            content_disposition: content_disposition(disposition, &filename),
+            content_encoding: None,
+            vary_encoding: false,
+            content_range: None,
+            accept_ranges: false,
+            entity_tag: encoded_entity_tag(&media_utils::entity_tag(&media), &format!("entry-{}", &entry_hash[..16])),
+            last_modified: http_date(&media_utils::content_date(&media))
+        };
+
+        if is_not_modified(&headers, &media, &response.entity_tag) {
+            response.status = Status::NotModified;
+            response.length = None;
+            return Ok(response)
+        }
+
+        let reader = open()?;
+        response.body = Some(Box::pin(blob::spawn_writer(move |writer| {
+            match processing::archive::extract(reader, format, &entry.path, limits, writer) {
+                Ok(true) => Ok(()),
+                _ => Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Archive entry couldn't be extracted"))
+            }
+        })));
+
+        Ok(response)
+    }
+
+    /// Finds the identified media, making sure it's an archive that can be read
+    fn find_archive(
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        identification: &Media,
+        access: &MediaAccess
+    ) -> Result<(DBMedia, processing::archive::ArchiveFormat), status::Custom<Json<Error>>> {
+        let media = find_typed_media(database_store, identification, access, ContentType::Archive, "Media isn't an archive")?;
+
+        match processing::archive::ArchiveFormat::from_mime_type(&media_utils::mime_type(&media)) {
+            Some(format) => Ok((media, format)),
+            None => Err(status::Custom(Status::UnsupportedMediaType, Json(Error {
+                error: String::from("Only zip, tar & tar.gz archives can be listed")
+            })))
+        }
+    }
+
+    fn archive_limits(config_store: &State<Arc<Mutex<Config>>>) -> Result<processing::archive::ArchiveLimits, status::Custom<Json<Error>>> {
+        let config = match config_store.lock() {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        Ok(processing::archive::ArchiveLimits {
+            max_entries: config.media_archive_max_entries as usize,
+            max_size: config.media_archive_max_size as u64 * 1000000
+        })
+    }
+
+    fn archive_error(error: processing::archive::ArchiveError, limits: &processing::archive::ArchiveLimits) -> status::Custom<Json<Error>> {
+        match error {
+            processing::archive::ArchiveError::Malformed => status::Custom(Status::UnsupportedMediaType, Json(Error {
+                error: String::from("Archive is malformed or uses unsupported features such as encryption")
+            })),
+            processing::archive::ArchiveError::TooLarge => status::Custom(Status::BadRequest, Json(Error {
+                error: format!("Archive exceeds the limit of {} megabytes decompressed", limits.max_size / 1000000)
+            })),
+            processing::archive::ArchiveError::Io(_) => media_utils::internal_error()
+        }
+    }
+
+    // TODO: Add maximum return id's
+    // E.g. Limit amount of id's returned. This could be a problem when the database grows exponentially.
+
+    /// Searches all media id's in the form of a list
+    /// based on optional queries patterns
+    #[utoipa::path(
+        post,
+        context_path = "/api/media",
+        request_body = SearchQuery,
+        responses(
+            (status = 200, description = "Successfully found all media pertaining to the search query", body = ContentFound),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        )
+    )]
+    #[post("/search", data = "<search>")]
+    pub async fn search(
+        _config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        search: Json<SearchQuery>
+    ) -> Result<Json<ContentFound>, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+        let media_database = &database.get_tree("media")?;
+        let user_database = &database.get_tree("user")?;
+        let grant_database = &database.get_tree("grant")?;
+
+        let user: Option<User> = if search.api_key.is_some() {
+            user_database
                 .iter()
                 .filter_map(|item| item.ok())
                 .map(|item| {
@@ -361,7 +2326,18 @@ pub mod Media {
             None
         };
 
-        let medias_filtered: Vec<DBMedia> = media_database
+        // Albums the user can't view match nothing
+        let album = match &search.album {
+            Some(id) => match album_utils::find_album(&database.get_tree("album")?, id) {
+                Some(album) if album_utils::can_view_album(&album, user.as_ref()) => Some(album),
+                _ => return Ok(Json(ContentFound {
+                    ids: Vec::new()
+                }))
+            },
+            None => None
+        };
+
+        let mut medias_filtered: Vec<DBMedia> = media_database
             .iter()
             .filter_map(|item| item.ok())
             .filter_map(|item| {
@@ -400,6 +2376,8 @@ pub mod Media {
 
                 !media.unlisted
             })
+            .filter(|media| media_utils::can_view(grant_database, media, user.as_ref()))
+            .filter(|media| !media_utils::downloads_exhausted(media))
             .filter(|media| {
                 if search.tags.is_none() {
                     return true
@@ -432,7 +2410,13 @@ pub mod Media {
                 }
                 false
             })
+            .filter(|media| metadata_matches(&search, media))
+            .filter(|media| !matches!(&album, Some(album) if !album.media_ids.contains(&media.id)))
             .collect();
+
+        if let Some(album) = &album {
+            medias_filtered.sort_by_key(|media| album.media_ids.iter().position(|id| id == &media.id));
+        }
         
         let mut medias: Option<Vec<String>> = None;
         if let Some(downloads_sort) = &search.downloads {
@@ -457,296 +2441,459 @@ pub mod Media {
         }
     }
 
+    /// Whether the media's metadata is within the search's bounds,
+    /// media missing a searched detail never matches
+    fn metadata_matches(search: &SearchQuery, media: &DBMedia) -> bool {
+        fn within<T: PartialOrd>(value: Option<T>, min: Option<T>, max: Option<T>) -> bool {
+            if min.is_none() && max.is_none() {
+                return true
+            }
+
+            match value {
+                Some(value) => !matches!(&min, Some(min) if &value < min) && !matches!(&max, Some(max) if &value > max),
+                None => false
+            }
+        }
+
+        let metadata = match &media.metadata {
+            Some(result) => result,
+            None => return search.min_duration.is_none() && search.max_duration.is_none()
+                && search.min_width.is_none() && search.max_width.is_none()
+                && search.min_height.is_none() && search.max_height.is_none()
+                && search.codec.is_none()
+        };
+
+        if let Some(codec) = &search.codec {
+            if !matches!(&metadata.codec, Some(media_codec) if media_codec.eq_ignore_ascii_case(codec)) {
+                return false
+            }
+        }
+
+        within(metadata.duration, search.min_duration, search.max_duration)
+            && within(metadata.width, search.min_width, search.max_width)
+            && within(metadata.height, search.min_height, search.max_height)
+    }
+
+    /// Uploads media to a user's account
+    /// 
+    /// Media data should be in the form of base64 string inside the body 
+    #[utoipa::path(
+        post,
+        context_path = "/api/media",
+        request_body = UploadMedia,
+        responses(
+            (status = 200, description = "Successfully uploaded media", body = Media),
+            (status = 400, description = "Server received malformed client request", body = Error),
+            (status = 401, description = "An authentication issue has occurred", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        )
+    )]
+    #[post("/upload", data = "<upload>")]
+    pub async fn upload(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        upload: Json<UploadMedia>
+    ) -> Result<Json<Media>, status::Custom<Json<Error>>> {
+        let (config, user, details, staged_path) = {
+            let database = database_store.get_database()?;
+            let user_database = &database.get_tree("user")?;
+
+            let user = match media_utils::find_user_by_api_key(user_database, &upload.api_key) {
+                Some(result) => result,
+                None => return Err(status::Custom(Status::Unauthorized, Json(Error {
+                    error: String::from("Invalid or wrong credentials provided")
+                })))
+            };
+
+            let config = match config_store.lock() {
+                Ok(result) => result,
+                Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                    error: String::from("An internal error on the server's end has occurred")
+                })))
+            };
+
+            media_utils::check_upload_allowed(&config, &user, &upload.name)?;
+
+            let upload_data = match decode(&upload.upload_data) {
+                Ok(result) => {
+                    result
+                },
+                Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                    error: String::from("An internal error on the server's end has occurred while decoding")
+                })))
+            };
+
+            let staged_path = media_utils::staging_path(&config)?;
+            if fs::write(&staged_path, upload_data).is_err() {
+                return Err(status::Custom(Status::InternalServerError, Json(Error {
+                    error: String::from("An internal error on the server's end has occurred")
+                })))
+            }
+
+            let details = UploadDetails {
+                name: upload.name.clone(),
+                unlisted: upload.unlisted,
+                private: upload.private,
+                tags: upload.tags.clone(),
+                text: false,
+                language: None,
+                expires_in: upload.expires_in,
+                expires_at: upload.expires_at.clone(),
+                max_downloads: upload.max_downloads,
+                password_hash: media_utils::hash_media_password(&config, upload.password.as_deref())?
+            };
+
+            (config.clone(), user, details, staged_path)
+        };
+
+        let media = media_utils::store_upload(config, database_store.inner().clone(), storage_store.inner().clone(), keyring_store.inner().clone(), user, details, staged_path).await?;
+
+        Ok(Json(Media {
+            id: media.id
+        }))
+    }
+
+    /// Uploads media to a user's account
+    /// using a multipart form containing the file and its metadata
+    /// 
+    /// The file is streamed straight to disk, its size is capped by
+    /// the instance's `file` data limit
+    #[utoipa::path(
+        post,
+        context_path = "/api/media",
+        request_body(content = UploadMediaForm, content_type = "multipart/form-data"),
+        responses(
+            (status = 200, description = "Successfully uploaded media", body = Media),
+            (status = 400, description = "Server received malformed client request", body = Error),
+            (status = 401, description = "An authentication issue has occurred", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        )
+    )]
+    #[post("/upload/multipart", format = "multipart/form-data", data = "<upload>")]
+    pub async fn upload_multipart(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        mut upload: Form<UploadMediaForm<'_>>
+    ) -> Result<Json<Media>, status::Custom<Json<Error>>> {
+        if !upload.file.is_complete() {
+            return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: String::from("File size too big! Upload exceeded the instance's size limit")
+            })))
+        }
+
+        let staged_path = {
+            let config = match config_store.lock() {
+                Ok(result) => result,
+                Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                    error: String::from("An internal error on the server's end has occurred")
+                })))
+            };
+
+            media_utils::staging_path(&config)?
+        };
+
+        if upload.file.move_copy_to(&staged_path).await.is_err() {
+            let _ = fs::remove_file(&staged_path);
+            return Err(status::Custom(Status::InternalServerError, Json(Error {
+                error: String::from("An internal error on the server's end has occurred")
+            })))
+        }
+
+        // The staged file is removed however this fails, as nothing else would clean it up
+        let prepared = (|| {
+            let database = database_store.get_database()?;
+            let user_database = &database.get_tree("user")?;
+
+            let user = match media_utils::find_user_by_api_key(user_database, &upload.api_key) {
+                Some(result) => result,
+                None => return Err(status::Custom(Status::Unauthorized, Json(Error {
+                    error: String::from("Invalid or wrong credentials provided")
+                })))
+            };
+
+            let config = match config_store.lock() {
+                Ok(result) => result,
+                Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                    error: String::from("An internal error on the server's end has occurred")
+                })))
+            };
+
+            let details = UploadDetails {
+                name: upload.name.clone(),
+                unlisted: upload.unlisted,
+                private: upload.private,
+                tags: upload.tags.clone(),
+                text: false,
+                language: None,
+                expires_in: upload.expires_in,
+                expires_at: upload.expires_at.clone(),
+                max_downloads: upload.max_downloads,
+                password_hash: media_utils::hash_media_password(&config, upload.password.as_deref())?
+            };
+
+            Ok((config.clone(), user, details))
+        })();
+
+        let (config, user, details) = match prepared {
+            Ok(result) => result,
+            Err(err) => {
+                let _ = fs::remove_file(&staged_path);
+                return Err(err)
+            }
+        };
+
+        let media = media_utils::store_upload(config, database_store.inner().clone(), storage_store.inner().clone(), keyring_store.inner().clone(), user, details, staged_path).await?;
+
+        Ok(Json(Media {
+            id: media.id
+        }))
+    }
+
     /// Uploads media to a user's account
+    /// using the raw request body as the file contents
     /// 
-    /// Media data should be in the form of base64 string inside the body 
+    /// Metadata is passed through the query string and the body is streamed
+    /// straight to disk, capped by the user's upload size limit
     #[utoipa::path(
         post,
         context_path = "/api/media",
-        request_body = UploadMedia,
+        request_body(content = Vec<u8>, content_type = "application/octet-stream"),
         responses(
             (status = 200, description = "Successfully uploaded media", body = Media),
             (status = 400, description = "Server received malformed client request", body = Error),
             (status = 401, description = "An authentication issue has occurred", body = Error),
             (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            UploadMediaQuery
         )
     )]
-    #[post("/upload", data = "<upload>")]
-    pub async fn upload(
+    #[post("/upload/raw?<upload..>", format = "application/octet-stream", data = "<data>")]
+    pub async fn upload_raw(
         config_store: &State<Arc<Mutex<Config>>>,
         database_store: &State<Arc<Mutex<sled::Db>>>,
-        upload: Json<UploadMedia>
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        upload: UploadMediaQuery,
+        data: Data<'_>
     ) -> Result<Json<Media>, status::Custom<Json<Error>>> {
-        let database = database_store.get_database()?;
-        let user_database = &database.get_tree("user")?;
+        let (size_limit, staged_path) = {
+            let database = database_store.get_database()?;
+            let user_database = &database.get_tree("user")?;
 
-        let user = user_database.iter()
-            .filter_map(|item| item.ok())
-            .map(|item| {
-                let result: User = match serde_json::from_str(&String::from_utf8_lossy(&item.1)) {
-                    Ok(result) => result,
-                    Err(_) => return None
-                };
-                Some(result)
-            }).find_map(|user| {
-                match user {
-                    Some(result) => {
-                        if result.api_key == upload.api_key {
-                            return Some(result)
-                        }
-                        None
-                    },
-                    None => None
-                }
-            });
+            let user = match media_utils::find_user_by_api_key(user_database, &upload.api_key) {
+                Some(result) => result,
+                None => return Err(status::Custom(Status::Unauthorized, Json(Error {
+                    error: String::from("Invalid or wrong credentials provided")
+                })))
+            };
 
-        return match user {
-            Some(mut user) => {
-                let config = match config_store.lock() {
-                    Ok(result) => result,
-                    Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("An internal error on the server's end has occurred")
-                    })))
-                };
+            let config = match config_store.lock() {
+                Ok(result) => result,
+                Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                    error: String::from("An internal error on the server's end has occurred")
+                })))
+            };
 
-                if config.media_max_name_length > 0 {
-                    if upload.name.len() as i32 > config.media_max_name_length {
-                        return Err(status::Custom(Status::BadRequest, Json(Error {
-                            error: format!("Name length too long. Maximum of {} characters", config.media_max_name_length)
-                        })))
-                    }
-                }
+            media_utils::check_upload_allowed(&config, &user, &upload.name)?;
 
-                if !user.admin {
-                    if config.user_upload_limit > 0 {
-                        if user.uploads.len() as i32 >= config.user_upload_limit {
-                            return Err(status::Custom(Status::BadRequest, Json(Error {
-                                error: format!("Maximum file uploads reached. Maximum of {} uploads per account", config.user_upload_limit)
-                            })))
-                        }
-                    }
-                }
+            (media_utils::upload_size_limit(&config, &user), media_utils::staging_path(&config)?)
+        };
 
-                let upload_data = match decode(&upload.upload_data) {
-                    Ok(result) => {
-                        result
-                    },
-                    Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("An internal error on the server's end has occurred while decoding")
-                    })))
-                };
+        let written = match data.open(size_limit).into_file(&staged_path).await {
+            Ok(result) => result,
+            Err(_) => {
+                let _ = fs::remove_file(&staged_path);
+                return Err(status::Custom(Status::InternalServerError, Json(Error {
+                    error: String::from("An internal error on the server's end has occurred")
+                })))
+            }
+        };
 
-                if !user.admin { 
-                    if config.user_upload_size_limit > 0 {
-                        let mb_size = upload_data.len() as i32 / 1000000;
-                        if mb_size > config.user_upload_size_limit {
-                            return Err(status::Custom(Status::BadRequest, Json(Error {
-                                error: format!("File size too big! Maximum of {} megabytes", config.user_upload_size_limit)
-                            })))
-                        }
-                    }
-                }
+        if !written.is_complete() {
+            let _ = fs::remove_file(&staged_path);
+            return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: format!("File size too big! Maximum of {} bytes", size_limit.as_u64())
+            })))
+        }
 
-                let media_database = &database.get_tree("media")?;
-
-                if !user.admin { 
-                    if config.user_total_upload_size_limit > 0 {
-                        let mb_size = upload_data.len() as i32 / 1000000;
-    
-                        let media_total_size: Option<i32> = media_database.iter()
-                            .filter_map(|item| item.ok())
-                            .filter_map(|item| {
-                                let result: DBMedia = match serde_json::from_str(&String::from_utf8_lossy(&item.1)) {
-                                    Ok(result) => result,
-                                    Err(_) => return None
-                                };
-                                Some(result)
-                            })
-                            .map(|media| media.data_size)
-                            .sum1();
-                        if let Some(media_total_size) = media_total_size {
-                            let mb_total_size = media_total_size / 1000000;
-                            let potential_overflow = mb_total_size + mb_size;
-                            if potential_overflow > config.user_total_upload_size_limit {
-                                return Err(status::Custom(Status::BadRequest, Json(Error {
-                                    error: format!("User has reached maximum amount of file storage. Maximum file uploads {} megabytes", config.user_total_upload_size_limit)
-                                })))
-                            }
-                        }
-                    }
-                }
+        // The staged file is removed however this fails, as nothing else would clean it up
+        let prepared = (|| {
+            let database = database_store.get_database()?;
+            let user_database = &database.get_tree("user")?;
 
-                let data_type = match infer::get(&upload_data) {
-                    Some(result) => {
-                        result
-                    }
-                    None => Type::new(MatcherType::Text, "text/plain", "txt", ignore)
-                    // Hacky solution since plain text files are not supported.
-                };
+            // Re-fetched as the account may have changed while the body was streaming
+            let user = match media_utils::find_user_by_api_key(user_database, &upload.api_key) {
+                Some(result) => result,
+                None => return Err(status::Custom(Status::Unauthorized, Json(Error {
+                    error: String::from("Invalid or wrong credentials provided")
+                })))
+            };
 
-                let data: (Vec<u8>, bool) = if config.backend_store_compressed {
-                    let zlib_encoder = ZlibEncoder::new(upload_data.clone(), Compression::best());
-                    match zlib_encoder.finish() {
-                        Ok(result) => { 
-                            if result.len() > upload_data.len() {
-                                (upload_data, false)
-                            } else {
-                                (result, true)
-                            } 
-                        },
-                        Err(_) => (upload_data, false)
-                    }
-                } else {
-                    (upload_data, false)
-                };
+            let config = match config_store.lock() {
+                Ok(result) => result,
+                Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                    error: String::from("An internal error on the server's end has occurred")
+                })))
+            };
 
-                let content_path = match &config.backend_media_directory {
-                    Some(result) => {
-                        Path::new(result)
-                    },
-                    None => { 
-                        Path::new(".\\")
-                    }
-                }.join("content");
+            let details = UploadDetails {
+                name: upload.name.clone(),
+                unlisted: upload.unlisted,
+                private: upload.private,
+                tags: upload.tags.clone(),
+                text: false,
+                language: None,
+                expires_in: upload.expires_in,
+                expires_at: upload.expires_at.clone(),
+                max_downloads: upload.max_downloads,
+                password_hash: media_utils::hash_media_password(&config, upload.password.as_deref())?
+            };
 
-                let mut content_type = ContentType::Other;
-                let mut content_directory = match data_type.matcher_type() {
-                    MatcherType::Video => {
-                        content_type = ContentType::Video;
-                        content_path.join("Video") 
-                    }
-                    MatcherType::Image => {
-                        content_type = ContentType::Image;
-                        content_path.join("Image")
-                    }
-                    _ => {
-                        content_path.join("Other") 
-                    }
-                };
+            Ok((config.clone(), user, details))
+        })();
 
-                if !content_directory.exists() && fs::create_dir_all(&content_directory).is_err() {
-                    return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("An internal error on the server's end has occurred")
-                    })))
-                }
+        let (config, user, details) = match prepared {
+            Ok(result) => result,
+            Err(err) => {
+                let _ = fs::remove_file(&staged_path);
+                return Err(err)
+            }
+        };
 
-                content_directory = content_directory.join(Alphanumeric.sample_string(&mut OsRng, 24));
-                let mut content_file = match File::create(&content_directory) {
-                    Ok(result) => {
-                        result
-                    }
-                    Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("An internal error on the server's end has occurred")
-                    })))
-                };
+        let media = media_utils::store_upload(config, database_store.inner().clone(), storage_store.inner().clone(), keyring_store.inner().clone(), user, details, staged_path).await?;
 
-                if content_file.write_all(&data.0).is_err() {
-                    return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("An internal error on the server's end has occurred")
-                    })))
-                }
+        Ok(Json(Media {
+            id: media.id
+        }))
+    }
 
-                let mut safe_tags: Option<Vec<String>> = None;
-
-                if let Some(tags) = &upload.tags {
-                    let sorted_tags: Vec<String> = tags
-                    .into_iter()
-                    .filter(|tag| {
-                        let contains = config.tags_default.contains(&tag.to_lowercase());
-                        if !contains {
-                            if config.tags_allow_custom || user.admin {
-                                if user.admin {
-                                    return true
-                                }
-                                
-                                if tag.chars().count() as i32 > config.tags_max_name_length {
-                                    return false
-                                }
-                                return true
-                            }
-                            return false;
-                        }
-                        return contains;
-                    }
-                    ).map(|tag| tag.to_lowercase())
-                    .collect();
+    /// Uploads text such as logs or code snippets to a user's account
+    /// using the raw request body as the text
+    ///
+    /// Text must be valid UTF-8 and is always stored & served as plain text, whatever it looks like.
+    /// The language the text is highlighted as can be given by name or file extension
+    #[utoipa::path(
+        post,
+        context_path = "/api/media",
+        request_body(content = String, content_type = "text/plain"),
+        responses(
+            (status = 200, description = "Successfully uploaded text", body = Media),
+            (status = 400, description = "Text isn't valid UTF-8, its language is unknown or the request is malformed", body = Error),
+            (status = 401, description = "An authentication issue has occurred", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            UploadTextQuery
+        )
+    )]
+    #[post("/upload/text?<upload..>", format = "text/plain", data = "<data>")]
+    pub async fn upload_text(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        highlighter_store: &State<Arc<Highlighter>>,
+        upload: UploadTextQuery,
+        data: Data<'_>
+    ) -> Result<Json<Media>, status::Custom<Json<Error>>> {
+        let language = match &upload.language {
+            Some(language) => match highlighter_store.language(language) {
+                Some(result) => Some(result),
+                None => return Err(status::Custom(Status::BadRequest, Json(Error {
+                    error: format!("Unknown language {}", language)
+                })))
+            },
+            None => None
+        };
 
-                    if !sorted_tags.is_empty() {
-                        safe_tags = Some(sorted_tags);
-                    }
-                }
+        let (size_limit, staged_path) = {
+            let database = database_store.get_database()?;
+            let user_database = &database.get_tree("user")?;
 
-                let media = DBMedia {
-                    id: Alphanumeric.sample_string(&mut OsRng, config.media_dynamic_id_length as usize),
-                    name: upload.name.clone(),
-                    extension: data_type.extension().to_string(),
-                    data_type: content_type,
-                    data_path: content_directory,
-                    data_size: data.0.len() as i32,
-                    upload_date: chrono::offset::Utc::now(),
-                    data_compressed: data.1,
-                    author_username: user.username.clone(),
-                    unlisted: upload.unlisted.unwrap_or(false),
-                    tags: safe_tags,
-                    downloads: 0
-                };
+            let user = match media_utils::find_user_by_api_key(user_database, &upload.api_key) {
+                Some(result) => result,
+                None => return Err(status::Custom(Status::Unauthorized, Json(Error {
+                    error: String::from("Invalid or wrong credentials provided")
+                })))
+            };
 
-                println!("Media: {:#?}", media);
+            let config = match config_store.lock() {
+                Ok(result) => result,
+                Err(_) => return Err(media_utils::internal_error())
+            };
 
-                let media_vec = match serde_json::to_vec(&media) {
-                    Ok(result) => result,
-                    Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("An internal error on the server's end has occurred")
-                    })))
-                };
+            media_utils::check_upload_allowed(&config, &user, &upload.name)?;
 
-                if media_database.insert(&media.id, media_vec).is_err() {
-                    return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("An internal error on the server's end has occurred")
-                    })))
-                }
+            (media_utils::upload_size_limit(&config, &user), media_utils::staging_path(&config)?)
+        };
 
-                match media_database.flush() {
-                    Ok(_) => {
-                        let media_id = media.id;
-                        user.uploads.push(media_id.clone());
-
-                        if user_database.update_and_fetch(&user.username, |_| {
-                            Some(IVec::from(match serde_json::to_vec(&user) {
-                                Ok(result) => result,
-                                Err(_) => return None
-                            }))
-                        }).is_err() {
-                            return Err(status::Custom(Status::InternalServerError, Json(Error {
-                                error: String::from("An internal error on the server's end has occurred")
-                            })))
-                        }
+        let written = match data.open(size_limit).into_file(&staged_path).await {
+            Ok(result) => result,
+            Err(_) => {
+                let _ = fs::remove_file(&staged_path);
+                return Err(media_utils::internal_error())
+            }
+        };
 
-                        println!("User: {:#?}", user);
+        if !written.is_complete() {
+            let _ = fs::remove_file(&staged_path);
+            return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: format!("File size too big! Maximum of {} bytes", size_limit.as_u64())
+            })))
+        }
 
-                        if user_database.flush().is_err() {
-                            return Err(status::Custom(Status::InternalServerError, Json(Error {
-                                error: String::from("An internal error on the server's end has occurred")
-                            })))
-                        }
+        // The staged file is removed however this fails, as nothing else would clean it up
+        let prepared = (|| {
+            let database = database_store.get_database()?;
+            let user_database = &database.get_tree("user")?;
 
-                        let content_id = Media {
-                            id: media_id
-                        };
-                        
-                        Ok(Json(content_id))
-                    },
-                    Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("An internal error on the server's end has occurred")
-                    })))
-                }
-            }
-            None => {
-                Err(status::Custom(Status::Unauthorized, Json(Error {
+            // Re-fetched as the account may have changed while the body was streaming
+            let user = match media_utils::find_user_by_api_key(user_database, &upload.api_key) {
+                Some(result) => result,
+                None => return Err(status::Custom(Status::Unauthorized, Json(Error {
                     error: String::from("Invalid or wrong credentials provided")
                 })))
+            };
+
+            let config = match config_store.lock() {
+                Ok(result) => result,
+                Err(_) => return Err(media_utils::internal_error())
+            };
+
+            let details = UploadDetails {
+                name: upload.name.clone(),
+                unlisted: upload.unlisted,
+                private: upload.private,
+                tags: upload.tags.clone(),
+                text: true,
+                language,
+                expires_in: upload.expires_in,
+                expires_at: upload.expires_at.clone(),
+                max_downloads: upload.max_downloads,
+                password_hash: media_utils::hash_media_password(&config, upload.password.as_deref())?
+            };
+
+            Ok((config.clone(), user, details))
+        })();
+
+        let (config, user, details) = match prepared {
+            Ok(result) => result,
+            Err(err) => {
+                let _ = fs::remove_file(&staged_path);
+                return Err(err)
             }
         };
+
+        let media = media_utils::store_upload(config, database_store.inner().clone(), storage_store.inner().clone(), keyring_store.inner().clone(), user, details, staged_path).await?;
+
+        Ok(Json(Media {
+            id: media.id
+        }))
     }
 
     pub fn ignore(_buf: &[u8]) -> bool {
@@ -768,93 +2915,88 @@ pub mod Media {
     pub async fn delete(
         _config_store: &State<Arc<Mutex<Config>>>,
         database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
         body: Json<DeleteMedia>
     ) -> Result<Status, status::Custom<Json<Error>>> {
-        let database = database_store.get_database()?;
-        let user_database = &database.get_tree("user")?;
-
-        let user = user_database.iter()
-            .filter_map(|item| item.ok())
-            .map(|item| {
-                let result: User = match serde_json::from_str(&String::from_utf8_lossy(&item.1)) {
-                    Ok(result) => result,
-                    Err(_) => return None
-                };
-                Some(result)
-            }).find_map(|user| {
-                match user {
-                    Some(result) => {
-                        if result.api_key == body.api_key {
-                            return Some(result)
-                        }
-                        None
-                    },
-                    None => None
-                }
-            });
-
-        return match user {
-            Some(mut user) => {
-                let media_database = match database.open_tree("media") {
-                    Ok(result) => result,
-                    Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("An internal error on the server's end has occurred")
-                    })))
-                };
+        // Stored blobs are only deleted once the database is unlocked
+        let released = {
+            let database = database_store.get_database()?;
+            let user_database = &database.get_tree("user")?;
 
-                let media_vec = match media_database.get(&body.id) {
-                    Ok(result) => {
-                        match result {
-                            Some(result) => result,
-                            None => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                                error: String::from("An internal error on the server's end has occurred")
-                            })))
-                        }
-                    },
-                    Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("Couldn't find media associated with id")
-                    })))
-                };
+            let user = user_database.iter()
+                .filter_map(|item| item.ok())
+                .map(|item| {
+                    let result: User = match serde_json::from_str(&String::from_utf8_lossy(&item.1)) {
+                        Ok(result) => result,
+                        Err(_) => return None
+                    };
+                    Some(result)
+                }).find_map(|user| {
+                    match user {
+                        Some(result) => {
+                            if result.api_key == body.api_key {
+                                return Some(result)
+                            }
+                            None
+                        },
+                        None => None
+                    }
+                });
 
-                let media: DBMedia = match serde_json::from_str(&String::from_utf8_lossy(&media_vec)) {
-                    Ok(result) => result,
-                    Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("An internal error on the server's end has occurred")
-                    })))
-                };
+            match user {
+                Some(user) => {
+                    let media_database = match database.open_tree("media") {
+                        Ok(result) => result,
+                        Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                            error: String::from("An internal error on the server's end has occurred")
+                        })))
+                    };
 
-                if media.author_username == user.username {
-                    match media_database.remove(&body.id) {
-                        Ok(_) => {
-                            user.uploads.retain(|upload| upload == &body.id);
-                            match user_database.update_and_fetch(&user.username, |_| {
-                                Some(IVec::from(match serde_json::to_vec(&user) {
-                                    Ok(result) => result,
-                                    Err(_) => return None
-                                }))
-                            }) {
-                                Ok(_) => {
-                                    Ok(Status::Ok)
-                                },
-                                Err(_) => Err(status::Custom(Status::InternalServerError, Json(Error {
+                    let media_vec = match media_database.get(&body.id) {
+                        Ok(result) => {
+                            match result {
+                                Some(result) => result,
+                                None => return Err(status::Custom(Status::InternalServerError, Json(Error {
                                     error: String::from("An internal error on the server's end has occurred")
                                 })))
                             }
                         },
-                        Err(_) => Err(status::Custom(Status::InternalServerError, Json(Error {
-                            error: String::from("Failed delete media from database")
+                        Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                            error: String::from("Couldn't find media associated with id")
                         })))
-                    }
-                } else {
-                    Err(status::Custom(Status::Unauthorized, Json(Error {
-                        error: String::from("Media does not belong to associated api key!")
-                    })))
-                }
-            },
-            None => Err(status::Custom(Status::Unauthorized, Json(Error {
-                error: String::from("Api key not valid and or does not exist!")
-            })))
-        }
+                    };
+
+                    let media: DBMedia = match serde_json::from_str(&String::from_utf8_lossy(&media_vec)) {
+                        Ok(result) => result,
+                        Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                            error: String::from("An internal error on the server's end has occurred")
+                        })))
+                    };
+
+                    if media.author_username == user.username {
+                        let mut released = Vec::new();
+
+                        match media_utils::remove_media(&database, &media, &mut released) {
+                            Ok(_) => released,
+                            Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                                error: String::from("Failed delete media from database")
+                            })))
+                        }
+                    } else {
+                        return Err(status::Custom(Status::Unauthorized, Json(Error {
+                            error: String::from("Media does not belong to associated api key!")
+                        })))
+                    }
+                },
+                None => return Err(status::Custom(Status::Unauthorized, Json(Error {
+                    error: String::from("Api key not valid and or does not exist!")
+                })))
+            }
+        };
+
+        media_utils::delete_released_blocking(storage_store.inner().clone(), released).await;
+
+        Ok(Status::Ok)
     }
 
     /// Edit media information
@@ -955,40 +3097,32 @@ pub mod Media {
                         edited_media.unlisted = unlisted;
                     }
 
+                    if let Some(private) = body.private {
+                        edited_media.private = private;
+                    }
+
                     if let Some(edit_tags) = body.edit_tags {
                         if edit_tags == true {
-                            let mut safe_tags: Option<Vec<String>> = None;
-
-                            if let Some(tags) = body.tags.clone() {
-                                let sorted_tags: Vec<String> = tags
-                                .into_iter()
-                                .filter(|tag| {
-                                    let contains = config.tags_default.contains(&tag.to_lowercase());
-                                    if !contains {
-                                        if config.tags_allow_custom || user.admin {
-                                            if user.admin {
-                                                return true
-                                            }
-                                            
-                                            if tag.chars().count() as i32 > config.tags_max_name_length {
-                                                return false
-                                            }
-                                            return true
-                                        }
-                                        return false;
-                                    }
-                                    return contains;
-                                }
-                                ).map(|tag| tag.to_lowercase())
-                                .collect();
-
-                                if !sorted_tags.is_empty() {
-                                    safe_tags = Some(sorted_tags);
-                                }
-                            }
+                            edited_media.tags = media_utils::filter_tags(&config, &user, &body.tags);
+                        }
+                    }
 
-                            edited_media.tags = safe_tags;
+                    if body.edit_hotlink_hosts == Some(true) {
+                        if let Some(host) = body.hotlink_hosts.iter().flatten().find(|host| media_utils::url_host(host.trim_start_matches("*.")).is_none()) {
+                            return Err(status::Custom(Status::BadRequest, Json(Error {
+                                error: format!("Hotlink host {} isn't valid", host)
+                            })))
                         }
+
+                        edited_media.hotlink_hosts = body.hotlink_hosts.clone();
+                    }
+
+                    if body.edit_password == Some(true) {
+                        edited_media.password = media_utils::hash_media_password(&config, body.password.as_deref())?;
+                    }
+
+                    if body.edit_expiration == Some(true) {
+                        edited_media.expiration_date = media_utils::expiration_date(&config, &user, body.expires_in, body.expires_at.as_deref(), edited_media.upload_date)?;
                     }
                     
                     match media_database.update_and_fetch(&edited_media.id, |_| {
@@ -1016,6 +3150,362 @@ pub mod Media {
         }
     }
 
+    /// Finds media along with the owner of the api key, who has to be its author or an admin to manage its grants
+    fn find_managed_media(database: &sled::Db, id: &str, api_key: &str) -> Result<(DBMedia, User), status::Custom<Json<Error>>> {
+        let (user_database, media_database) = match (database.open_tree("user"), database.open_tree("media")) {
+            (Ok(user_database), Ok(media_database)) => (user_database, media_database),
+            _ => return Err(media_utils::internal_error())
+        };
+
+        let user = match media_utils::find_user_by_api_key(&user_database, api_key) {
+            Some(result) => result,
+            None => return Err(status::Custom(Status::Unauthorized, Json(Error {
+                error: String::from("Api key not valid and or does not exist!")
+            })))
+        };
+
+        let media: DBMedia = match media_database.get(id) {
+            Ok(Some(result)) => match serde_json::from_str(&String::from_utf8_lossy(&result)) {
+                Ok(result) => result,
+                Err(_) => return Err(media_utils::internal_error())
+            },
+            _ => return Err(media_utils::internal_error())
+        };
+
+        if !user.admin && media.author_username != user.username {
+            return Err(status::Custom(Status::Unauthorized, Json(Error {
+                error: String::from("You are unauthorized to access & edit this content")
+            })))
+        }
+
+        Ok((media, user))
+    }
+
+    /// Grants a user access to private media
+    #[utoipa::path(
+        post,
+        context_path = "/api/media",
+        request_body = GrantMedia,
+        responses(
+            (status = 200, description = "Successfully granted access"),
+            (status = 400, description = "User doesn't exist", body = Error),
+            (status = 401, description = "An authentication issue has occurred", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        )
+    )]
+    #[post("/grant", data = "<body>")]
+    pub async fn grant(
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        body: Json<GrantMedia>
+    ) -> Result<Status, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+        let user_database = &database.get_tree("user")?;
+        let grant_database = &database.get_tree("grant")?;
+
+        let (media, granter) = find_managed_media(&database, &body.id, &body.api_key)?;
+
+        if !matches!(user_database.contains_key(&body.username), Ok(true)) {
+            return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: String::from("User doesn't exist")
+            })))
+        }
+
+        let grant = MediaGrant {
+            media_id: media.id,
+            username: body.username.clone(),
+            grant_date: Utc::now(),
+            granter_username: granter.username
+        };
+
+        let grant_vec = match serde_json::to_vec(&grant) {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        if grant_database.insert(media_utils::grant_key(&grant.media_id, &grant.username), grant_vec).is_err() || grant_database.flush().is_err() {
+            return Err(media_utils::internal_error())
+        }
+
+        Ok(Status::Ok)
+    }
+
+    /// Revokes a user's access to private media
+    #[utoipa::path(
+        delete,
+        context_path = "/api/media",
+        request_body = GrantMedia,
+        responses(
+            (status = 200, description = "Successfully revoked access"),
+            (status = 400, description = "Media hasn't been granted to the user", body = Error),
+            (status = 401, description = "An authentication issue has occurred", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        )
+    )]
+    #[delete("/grant", data = "<body>")]
+    pub async fn revoke(
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        body: Json<GrantMedia>
+    ) -> Result<Status, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+        let grant_database = &database.get_tree("grant")?;
+
+        let (media, _) = find_managed_media(&database, &body.id, &body.api_key)?;
+
+        match grant_database.remove(media_utils::grant_key(&media.id, &body.username)) {
+            Ok(Some(_)) => {},
+            Ok(None) => return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: String::from("Media hasn't been granted to the user")
+            }))),
+            Err(_) => return Err(media_utils::internal_error())
+        }
+
+        if grant_database.flush().is_err() {
+            return Err(media_utils::internal_error())
+        }
+
+        Ok(Status::Ok)
+    }
+
+    /// Lists the users private media has been granted to
+    #[utoipa::path(
+        post,
+        context_path = "/api/media",
+        request_body = ListGrants,
+        responses(
+            (status = 200, description = "Successfully grabbed the media's grants", body = ContentGrants),
+            (status = 401, description = "An authentication issue has occurred", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        )
+    )]
+    #[post("/grants", data = "<body>")]
+    pub async fn grants(
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        body: Json<ListGrants>
+    ) -> Result<Json<ContentGrants>, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+        let grant_database = &database.get_tree("grant")?;
+
+        let (media, _) = find_managed_media(&database, &body.id, &body.api_key)?;
+
+        Ok(Json(ContentGrants {
+            usernames: media_utils::media_grants(grant_database, &media.id)
+                .into_iter()
+                .map(|grant| grant.username)
+                .collect()
+        }))
+    }
+
+    /// Replaces the content of media with the raw request body, keeping its id & details
+    /// so existing links point at the new content
+    ///
+    /// The previous content is kept as a numbered revision, the oldest revisions are pruned past the instance's limit.
+    /// Text media can only be replaced by valid UTF-8 text
+    #[utoipa::path(
+        post,
+        context_path = "/api/media",
+        request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+        responses(
+            (status = 200, description = "Successfully replaced media", body = ReplacedMedia),
+            (status = 400, description = "Server received malformed client request", body = Error),
+            (status = 401, description = "An authentication issue has occurred", body = Error),
+            (status = 403, description = "Editing is disabled on this instance", body = Error),
+            (status = 410, description = "Media has reached its download limit", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            ReplaceMediaQuery
+        )
+    )]
+    #[post("/replace?<replace..>", format = "application/octet-stream", data = "<data>")]
+    pub async fn replace(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        replace: ReplaceMediaQuery,
+        data: Data<'_>
+    ) -> Result<Json<ReplacedMedia>, status::Custom<Json<Error>>> {
+        let (size_limit, staged_path) = {
+            let database = database_store.get_database()?;
+            let (media, user) = find_managed_media(&database, &replace.id, &replace.api_key)?;
+
+            let config = match config_store.lock() {
+                Ok(result) => result,
+                Err(_) => return Err(media_utils::internal_error())
+            };
+
+            if !user.admin && !config.media_allow_editing {
+                return Err(status::Custom(Status::Forbidden, Json(Error {
+                    error: String::from("Editing is disabled on this instance")
+                })))
+            }
+
+            if media_utils::downloads_exhausted(&media) {
+                return Err(media_utils::downloads_exhausted_error())
+            }
+
+            (media_utils::upload_size_limit(&config, &user), media_utils::staging_path(&config)?)
+        };
+
+        let written = match data.open(size_limit).into_file(&staged_path).await {
+            Ok(result) => result,
+            Err(_) => {
+                let _ = fs::remove_file(&staged_path);
+                return Err(media_utils::internal_error())
+            }
+        };
+
+        if !written.is_complete() {
+            let _ = fs::remove_file(&staged_path);
+            return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: format!("File size too big! Maximum of {} bytes", size_limit.as_u64())
+            })))
+        }
+
+        let (config, user, media) = {
+            let database = match database_store.get_database() {
+                Ok(result) => result,
+                Err(error) => {
+                    let _ = fs::remove_file(&staged_path);
+                    return Err(error)
+                }
+            };
+
+            // Re-fetched as the media or account may have changed while the body was streaming
+            let (media, user) = match find_managed_media(&database, &replace.id, &replace.api_key) {
+                Ok(result) => result,
+                Err(error) => {
+                    let _ = fs::remove_file(&staged_path);
+                    return Err(error)
+                }
+            };
+
+            let config = match config_store.lock() {
+                Ok(result) => result,
+                Err(_) => {
+                    let _ = fs::remove_file(&staged_path);
+                    return Err(media_utils::internal_error())
+                }
+            };
+
+            (config.clone(), user, media)
+        };
+
+        let media = media_utils::replace_content(config, database_store.inner().clone(), storage_store.inner().clone(), keyring_store.inner().clone(), user, media, staged_path).await?;
+
+        Ok(Json(ReplacedMedia {
+            id: media.id,
+            revision: media.revision
+        }))
+    }
+
+    /// Lists the previous revisions kept of media
+    #[utoipa::path(
+        get,
+        context_path = "/api/media",
+        responses(
+            (status = 200, description = "Successfully grabbed the media's revisions", body = ContentRevisions),
+            (status = 401, description = "Media is private or password protected & no api key, password or unlock token was given", body = Error),
+            (status = 403, description = "Media is private & hasn't been granted to the user, or the signed link is invalid or has expired", body = Error),
+            (status = 429, description = "Too many wrong passwords were given for the media", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            Media,
+            ("X-Api-Key" = Option<String>, Header, description = "Api key of a user able to view private media"),
+            ("api_key" = Option<String>, Query, description = "Api key of a user able to view private media, for links which can't send headers"),
+            ("X-Media-Password" = Option<String>, Header, description = "Password of password protected media"),
+            ("X-Unlock-Token" = Option<String>, Header, description = "Token from /unlock for password protected media"),
+            ("unlock_token" = Option<String>, Query, description = "Token from /unlock for password protected media, for links which can't send headers")
+        )
+    )]
+    #[get("/revisions?<identification..>")]
+    pub async fn revisions(
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        identification: Media,
+        access: MediaAccess
+    ) -> Result<Json<ContentRevisions>, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+        let media_database = &database.get_tree("media")?;
+        let revision_database = &database.get_tree("revision")?;
+
+        let media: DBMedia = match media_database.get(&identification.id) {
+            Ok(Some(result)) => match serde_json::from_str(&String::from_utf8_lossy(&result)) {
+                Ok(result) => result,
+                Err(_) => return Err(media_utils::internal_error())
+            },
+            _ => return Err(media_utils::internal_error())
+        };
+
+        access.check(&database, &media)?;
+
+        let revisions = media_utils::media_revisions(revision_database, &media.id)
+            .into_iter()
+            .map(|revision| ContentRevision {
+                revision: revision.revision,
+                content_mime_type: media_utils::mime_type(&revision.media),
+                content_extension: revision.media.extension.clone(),
+                content_size: media_utils::logical_size(&revision.media),
+                upload_date: media_utils::content_date(&revision.media),
+                replaced_date: revision.replaced_date
+            })
+            .collect();
+
+        Ok(Json(ContentRevisions {
+            revision: media.revision,
+            revisions
+        }))
+    }
+
+    /// Permanently deletes a previous revision of media
+    #[utoipa::path(
+        delete,
+        context_path = "/api/media",
+        request_body = DeleteRevision,
+        responses(
+            (status = 200, description = "Successfully deleted the revision"),
+            (status = 401, description = "An authentication issue has occurred", body = Error),
+            (status = 404, description = "Revision doesn't exist or has been pruned", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        )
+    )]
+    #[delete("/revision", data = "<body>")]
+    pub async fn delete_revision(
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        body: Json<DeleteRevision>
+    ) -> Result<Status, status::Custom<Json<Error>>> {
+        let not_found = || status::Custom(Status::NotFound, Json(Error {
+            error: String::from("Revision doesn't exist or has been pruned")
+        }));
+
+        // Stored blobs are only deleted once the database is unlocked
+        let released = {
+            let database = database_store.get_database()?;
+            let revision_database = &database.get_tree("revision")?;
+
+            let (media, _) = find_managed_media(&database, &body.id, &body.api_key)?;
+            let revision = media_utils::find_revision(revision_database, &media.id, body.revision).ok_or_else(not_found)?;
+            let mut released = Vec::new();
+
+            match media_utils::remove_revision(&database, &revision, &mut released) {
+                Ok(true) => {},
+                Ok(false) => return Err(not_found()),
+                Err(_) => return Err(media_utils::internal_error())
+            }
+
+            if database.flush().is_err() {
+                return Err(media_utils::internal_error())
+            }
+
+            released
+        };
+
+        media_utils::delete_released_blocking(storage_store.inner().clone(), released).await;
+
+        Ok(Status::Ok)
+    }
+
     /// Grabs all media related tags in use on the instance
     #[utoipa::path(
         get,
@@ -1054,4 +3544,245 @@ pub mod Media {
         
         Ok(Json(content_tags))
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use rand::distributions::{Alphanumeric, DistString};
+        use rand_core::OsRng;
+        use std::path::PathBuf;
+
+        use rocket::{http::Header, local::blocking::Client, routes};
+
+        use crate::storage::backend::open_storage;
+
+        fn stored_media(database_arc: &Arc<Mutex<sled::Db>>, id: &str) -> Option<DBMedia> {
+            let database = database_arc.lock().unwrap();
+            let media_vec = database.open_tree("media").unwrap().get(id).unwrap()?;
+            serde_json::from_slice(&media_vec).ok()
+        }
+
+        #[test]
+        fn parse_range_ignores_multiple_ranges() {
+            assert!(parse_range("bytes=0-1,4-5", 10).is_none());
+            assert!(parse_range("bytes=-2, 0-1", 10).is_none());
+        }
+
+        #[test]
+        fn parse_range_reads_suffix_ranges() {
+            assert!(matches!(parse_range("bytes=-3", 10), Some(ByteRange::Satisfiable(7, 9))));
+            // Suffixes longer than the content cover all of it
+            assert!(matches!(parse_range("bytes=-20", 10), Some(ByteRange::Satisfiable(0, 9))));
+            assert!(matches!(parse_range("bytes=-0", 10), Some(ByteRange::Unsatisfiable)));
+            assert!(matches!(parse_range("bytes=-3", 0), Some(ByteRange::Unsatisfiable)));
+        }
+
+        #[test]
+        fn parse_range_clamps_open_ranges() {
+            assert!(matches!(parse_range("bytes=4-", 10), Some(ByteRange::Satisfiable(4, 9))));
+            assert!(matches!(parse_range("bytes=4-100", 10), Some(ByteRange::Satisfiable(4, 9))));
+            assert!(matches!(parse_range("bytes=10-", 10), Some(ByteRange::Unsatisfiable)));
+            assert!(parse_range("bytes=5-4", 10).is_none());
+            assert!(parse_range("items=0-1", 10).is_none());
+        }
+
+        #[test]
+        fn accepts_encoding_refuses_zero_quality() {
+            assert!(!accepts_encoding(Some("gzip;q=0"), "gzip"));
+            assert!(!accepts_encoding(Some("br;q=0.0, gzip"), "br"));
+            assert!(!accepts_encoding(Some("*;q=0"), "gzip"));
+            // Naming the coding outweighs the wildcard
+            assert!(!accepts_encoding(Some("*, gzip;q=0"), "gzip"));
+            assert!(accepts_encoding(Some("gzip;q=0, *"), "br"));
+        }
+
+        #[test]
+        fn accepts_encoding_reads_qualities() {
+            assert!(accepts_encoding(Some("gzip"), "gzip"));
+            assert!(accepts_encoding(Some("deflate, GZIP;q=0.5"), "gzip"));
+            assert!(accepts_encoding(Some("*"), "br"));
+            assert!(!accepts_encoding(Some("deflate"), "gzip"));
+            assert!(!accepts_encoding(None, "gzip"));
+        }
+
+        fn signed_link(media_id: &str, expires: i64, ip: Option<&str>, client_ip: &str) -> SignedLink {
+            SignedLink {
+                signature: media_utils::sign_download("secret", media_id, expires, ip),
+                expires,
+                ip: ip.map(String::from),
+                client_ip: client_ip.parse().ok(),
+                secret: Some(String::from("secret"))
+            }
+        }
+
+        #[test]
+        fn signed_links_expire() {
+            let expires = Utc::now().timestamp() + 60;
+            assert!(signed_link("HilrvkpJ", expires, None, "127.0.0.1").check("HilrvkpJ").is_ok());
+
+            let expired = signed_link("HilrvkpJ", Utc::now().timestamp() - 1, None, "127.0.0.1").check("HilrvkpJ");
+            assert_eq!(expired.err().map(|error| error.1.0.error).as_deref(), Some("Signed link has expired"));
+
+            // Signed for other media
+            assert!(signed_link("Q0u4ceTl", expires, None, "127.0.0.1").check("HilrvkpJ").is_err());
+
+            let mut extended = signed_link("HilrvkpJ", expires, None, "127.0.0.1");
+            extended.expires += 3600;
+            assert_eq!(extended.check("HilrvkpJ").err().map(|error| error.1.0.error).as_deref(), Some("Signed link is invalid"));
+        }
+
+        #[test]
+        fn signed_links_stay_bound_to_their_address() {
+            let expires = Utc::now().timestamp() + 60;
+            assert!(signed_link("HilrvkpJ", expires, Some("127.0.0.1"), "127.0.0.1").check("HilrvkpJ").is_ok());
+
+            let elsewhere = signed_link("HilrvkpJ", expires, Some("127.0.0.1"), "10.0.0.1").check("HilrvkpJ");
+            assert_eq!(elsewhere.err().map(|error| error.1.0.error).as_deref(), Some("Signed link is bound to another address"));
+
+            let mut disabled = signed_link("HilrvkpJ", expires, None, "127.0.0.1");
+            disabled.secret = None;
+            assert!(disabled.check("HilrvkpJ").is_err());
+        }
+
+        /// Instance storing its media in a temporary directory, along with a text upload stored on it
+        struct TestInstance {
+            directory: PathBuf,
+            config: Config,
+            database_arc: Arc<Mutex<sled::Db>>,
+            storage: Arc<dyn Storage>,
+            keyring: Arc<Keyring>,
+            media: DBMedia
+        }
+
+        impl TestInstance {
+            fn new(content: &str, max_downloads: Option<u32>, password: Option<&str>) -> TestInstance {
+                let directory = std::env::temp_dir().join(format!("centix-test-{}", Alphanumeric.sample_string(&mut OsRng, 12)));
+                let config = Config {
+                    backend_media_directory: Some(directory.to_string_lossy().to_string()),
+                    ..Config::default()
+                };
+
+                let database_arc = Arc::new(Mutex::new(sled::Config::new().temporary(true).open().unwrap()));
+                let storage = open_storage(&config).unwrap();
+                let keyring = Arc::new(Keyring::default());
+
+                let user = User {
+                    username: String::from("reader"),
+                    creation_date: Utc::now(),
+                    uploads: Vec::new(),
+                    api_key: String::from("key"),
+                    password: String::new(),
+                    admin: false,
+                    invite_key: None
+                };
+                database_arc.lock().unwrap().open_tree("user").unwrap().insert(&user.username, serde_json::to_vec(&user).unwrap()).unwrap();
+
+                let staged_path = media_utils::staging_path(&config).unwrap();
+                fs::write(&staged_path, content).unwrap();
+
+                let details = UploadDetails {
+                    name: String::from("note"),
+                    unlisted: None,
+                    private: None,
+                    tags: None,
+                    text: true,
+                    language: None,
+                    expires_in: None,
+                    expires_at: None,
+                    max_downloads,
+                    password_hash: media_utils::hash_media_password(&config, password).unwrap()
+                };
+                let media = rocket::execute(media_utils::store_upload(config.clone(), database_arc.clone(), storage.clone(), keyring.clone(), user, details, staged_path)).unwrap();
+
+                TestInstance {
+                    directory,
+                    config,
+                    database_arc,
+                    storage,
+                    keyring,
+                    media
+                }
+            }
+
+            fn client(&self, routes: Vec<rocket::Route>) -> Client {
+                let rocket = rocket::build()
+                    .manage(Arc::new(Mutex::new(self.config.clone())))
+                    .manage(self.database_arc.clone())
+                    .manage(self.storage.clone())
+                    .manage(self.keyring.clone())
+                    .mount("/api/media", routes);
+
+                Client::tracked(rocket).unwrap()
+            }
+        }
+
+        impl Drop for TestInstance {
+            fn drop(&mut self) {
+                let _ = fs::remove_dir_all(&self.directory);
+            }
+        }
+
+        #[test]
+        fn header_passwords_are_throttled() {
+            let instance = TestInstance::new("locked away", None, Some("secret"));
+            let client = instance.client(routes![info]);
+            let url = format!("/api/media/info?id={}", instance.media.id);
+            let attempt = |password: &str| client.get(&url).header(Header::new("X-Media-Password", password.to_string())).dispatch().status();
+
+            assert_eq!(attempt("secret"), Status::Ok);
+
+            for _ in 0..instance.config.media_unlock_attempts {
+                assert_eq!(attempt("guess"), Status::Unauthorized);
+            }
+
+            // Refused even with the right password until the lockout is over
+            assert_eq!(attempt("guess"), Status::TooManyRequests);
+            assert_eq!(attempt("secret"), Status::TooManyRequests);
+        }
+
+        #[test]
+        fn locked_media_info_is_limited() {
+            let instance = TestInstance::new("locked away", None, Some("secret"));
+            let client = instance.client(routes![info]);
+            let url = format!("/api/media/info?id={}", instance.media.id);
+
+            let info: serde_json::Value = client.get(&url).dispatch().into_json().unwrap();
+            assert_eq!(info, serde_json::json!({ "id": instance.media.id, "content_name": instance.media.name, "password_protected": true }));
+
+            let info: serde_json::Value = client.get(&url).header(Header::new("X-Media-Password", "secret")).dispatch().into_json().unwrap();
+            assert_eq!(info["content_size"], serde_json::json!(instance.media.data_size));
+
+            assert_eq!(client.get(&url).header(Header::new("X-Media-Password", "guess")).dispatch().status(), Status::Unauthorized);
+        }
+
+        #[test]
+        fn head_requests_dont_take_downloads() {
+            let instance = TestInstance::new("burn after reading", Some(1), None);
+            let (database_arc, media) = (instance.database_arc.clone(), instance.media.clone());
+            let client = instance.client(routes![download, download_head]);
+            let url = format!("/api/media/download?id={}", media.id);
+
+            for _ in 0..2 {
+                assert_eq!(client.head(&url).dispatch().status(), Status::Ok);
+            }
+
+            assert_eq!(stored_media(&database_arc, &media.id).map(|media| media.downloads), Some(0));
+
+            let response = client.get(&url).dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            assert_eq!(response.into_string().as_deref(), Some("burn after reading"));
+
+            // Purged off of the runtime once the last download's body is dropped
+            let mut purged = false;
+            for _ in 0..50 {
+                if stored_media(&database_arc, &media.id).is_none() {
+                    purged = true;
+                    break
+                }
+
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+            assert!(purged);
+        }
+    }
 }
@@ -0,0 +1,1710 @@
+use std::{fs::{self, File}, io::{self, Read, Write}, net::IpAddr, path::{Path, PathBuf}, sync::{Arc, Mutex, MutexGuard}, thread};
+
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use infer::{MatcherType, Type};
+use log::{error, info};
+use pbkdf2::{password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString}, Pbkdf2};
+use rand::distributions::{Alphanumeric, DistString};
+use rand_core::OsRng;
+use rocket::{data::ByteUnit, http::{ContentType as HttpContentType, Status}, response::status, serde::json::Json};
+use sha2::{Digest, Sha256};
+use sled::{Db, IVec, Tree};
+
+use crate::{Config, Error};
+use crate::apis::album_utils;
+use crate::apis::media::Media::{self as MediaApi, ContentType};
+use crate::database::database::{Blob, Media, MediaGrant, MediaRevision, MediaUnlock, User};
+use crate::database::database_utils::DatabaseTreeExtension;
+use crate::processing::{metadata, sanitize, strip, text};
+use crate::storage::{blob::{self, BlobReader, Codec, Layout}, backend::Storage, encryption::{self, Keyring}, variant};
+
+/// Amount of bytes handed to `infer` when sniffing an upload's file type
+pub const INFER_HEADER_LENGTH: usize = 8192;
+
+/// Describes an upload that has been streamed into the staging directory
+/// and is waiting to be turned into a media record
+pub struct UploadDetails {
+    pub name: String,
+    pub unlisted: Option<bool>,
+    pub tags: Option<Vec<String>>,
+    // Uploaded as text, which is stored as such whatever it looks like
+    pub text: bool,
+    pub language: Option<String>,
+    // Seconds after being stored or RFC 3339 date at which the media deletes itself
+    pub expires_in: Option<u64>,
+    pub expires_at: Option<String>,
+    pub max_downloads: Option<u32>,
+    // Already hashed through `hash_media_password`
+    pub password_hash: Option<String>,
+    pub private: Option<bool>
+}
+
+pub fn internal_error() -> status::Custom<Json<Error>> {
+    status::Custom(Status::InternalServerError, Json(Error {
+        error: String::from("An internal error on the server's end has occurred")
+    }))
+}
+
+/// Root directory that all uploaded content is stored under
+pub fn content_directory(config: &Config) -> PathBuf {
+    match &config.backend_media_directory {
+        Some(result) => {
+            Path::new(result)
+        },
+        None => {
+            Path::new(".\\")
+        }
+    }.join("content")
+}
+
+/// Directory holding uploads that haven't been fully received or processed yet
+pub fn staging_directory(config: &Config) -> Result<PathBuf, status::Custom<Json<Error>>> {
+    let staging_directory = content_directory(config).join("Staging");
+
+    if !staging_directory.exists() && fs::create_dir_all(&staging_directory).is_err() {
+        return Err(internal_error())
+    }
+
+    Ok(staging_directory)
+}
+
+/// Creates a unique file path inside of the upload staging directory
+pub fn staging_path(config: &Config) -> Result<PathBuf, status::Custom<Json<Error>>> {
+    Ok(staging_directory(config)?.join(Alphanumeric.sample_string(&mut OsRng, 24)))
+}
+
+pub fn find_user_by_api_key(user_database: &Tree, api_key: &str) -> Option<User> {
+    user_database.iter()
+        .filter_map(|item| item.ok())
+        .filter_map(|item| {
+            let result: User = match serde_json::from_str(&String::from_utf8_lossy(&item.1)) {
+                Ok(result) => result,
+                Err(_) => return None
+            };
+            Some(result)
+        })
+        .find(|user| user.api_key == api_key)
+}
+
+/// Largest body a user is allowed to stream to the server for a single upload
+pub fn upload_size_limit(config: &Config, user: &User) -> ByteUnit {
+    let largest_size = largest_upload_size(config);
+
+    if !user.admin && largest_size > 0 {
+        ByteUnit::Megabyte(largest_size as u64)
+    } else {
+        ByteUnit::max_value()
+    }
+}
+
+/// Megabytes of the largest upload of any type, 0 when uploads aren't limited.
+/// Used before an upload's type is known, it's held against the limit of its type once sniffed
+pub fn largest_upload_size(config: &Config) -> i32 {
+    if config.user_upload_size_limit <= 0 {
+        return 0
+    }
+
+    config.user_type_limits.values()
+        .map(|limit| limit.max_size)
+        .fold(config.user_upload_size_limit, i32::max)
+}
+
+/// Removes any tags the user isn't allowed to apply and lowercases the rest
+pub fn filter_tags(config: &Config, user: &User, tags: &Option<Vec<String>>) -> Option<Vec<String>> {
+    let tags = match tags {
+        Some(result) => result,
+        None => return None
+    };
+
+    let sorted_tags: Vec<String> = tags
+        .iter()
+        .filter(|tag| {
+            if config.tags_default.contains(&tag.to_lowercase()) {
+                return true
+            }
+
+            if user.admin {
+                return true
+            }
+
+            config.tags_allow_custom && tag.chars().count() as i32 <= config.tags_max_name_length
+        })
+        .map(|tag| tag.to_lowercase())
+        .collect();
+
+    if sorted_tags.is_empty() {
+        None
+    } else {
+        Some(sorted_tags)
+    }
+}
+
+/// Validates everything about an upload that is known before any data is received
+pub fn check_upload_allowed(config: &Config, user: &User, name: &str) -> Result<(), status::Custom<Json<Error>>> {
+    if config.media_max_name_length > 0 && name.len() as i32 > config.media_max_name_length {
+        return Err(status::Custom(Status::BadRequest, Json(Error {
+            error: format!("Name length too long. Maximum of {} characters", config.media_max_name_length)
+        })))
+    }
+
+    if !user.admin && config.user_upload_limit > 0 && user.uploads.len() as i32 >= config.user_upload_limit {
+        return Err(status::Custom(Status::BadRequest, Json(Error {
+            error: format!("Maximum file uploads reached. Maximum of {} uploads per account", config.user_upload_limit)
+        })))
+    }
+
+    Ok(())
+}
+
+/// Validates an upload's size against the largest upload of any type & the per-account limit.
+///
+/// The limit for the upload's own type is checked by `check_upload_type` once it's sniffed
+pub fn check_upload_size(config: &Config, user: &User, media_database: &Tree, revision_database: &Tree, size: u64) -> Result<(), status::Custom<Json<Error>>> {
+    if user.admin {
+        return Ok(())
+    }
+
+    let mb_size = (size / 1000000) as i32;
+    let largest_size = largest_upload_size(config);
+
+    if largest_size > 0 && mb_size > largest_size {
+        return Err(status::Custom(Status::BadRequest, Json(Error {
+            error: format!("File size too big! Maximum of {} megabytes", largest_size)
+        })))
+    }
+
+    if config.user_total_upload_size_limit > 0 {
+        let medias: Vec<Media> = media_database.iter()
+            .filter_map(|item| item.ok())
+            .filter_map(|item| {
+                let result: Media = match serde_json::from_str(&String::from_utf8_lossy(&item.1)) {
+                    Ok(result) => result,
+                    Err(_) => return None
+                };
+                Some(result)
+            })
+            .filter(|media| media.author_username == user.username)
+            .collect();
+
+        // Kept revisions still hold their content, so they count towards the author's storage
+        let media_total_size: i64 = medias.iter()
+            .map(|media| logical_size(media) as i64 + media_revisions(revision_database, &media.id)
+                .iter()
+                .map(|revision| logical_size(&revision.media) as i64)
+                .sum::<i64>())
+            .sum();
+
+        let mb_total_size = (media_total_size / 1000000) as i32;
+        if mb_total_size + mb_size > config.user_total_upload_size_limit {
+            return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: format!("User has reached maximum amount of file storage. Maximum file uploads {} megabytes", config.user_total_upload_size_limit)
+            })))
+        }
+    }
+
+    Ok(())
+}
+
+/// How specifically a type rule (a mime type, mime wildcard or content type) matches an upload's type, `None` when it doesn't
+fn type_rule_specificity(rule: &str, content_type: &ContentType, mime_type: &str) -> Option<u8> {
+    let rule = rule.trim();
+
+    if rule.eq_ignore_ascii_case(mime_type) {
+        return Some(2)
+    }
+
+    if let Some(prefix) = rule.strip_suffix("/*") {
+        if mime_type.split('/').next().unwrap_or_default().eq_ignore_ascii_case(prefix) {
+            return Some(1)
+        }
+    }
+
+    if rule.eq_ignore_ascii_case(&format!("{:?}", content_type)) {
+        return Some(0)
+    }
+
+    None
+}
+
+/// Validates an upload's sniffed type against the allow & deny lists, as well as the limits for its type
+pub fn check_upload_type(
+    config: &Config,
+    user: &User,
+    media_database: &Tree,
+    content_type: &ContentType,
+    mime_type: &str,
+    size: u64
+) -> Result<(), status::Custom<Json<Error>>> {
+    let matches = |rule: &String| type_rule_specificity(rule, content_type, mime_type).is_some();
+
+    if config.media_denied_types.iter().any(matches) || (!config.media_allowed_types.is_empty() && !config.media_allowed_types.iter().any(matches)) {
+        return Err(status::Custom(Status::UnsupportedMediaType, Json(Error {
+            error: format!("Uploads of type {} aren't allowed", mime_type)
+        })))
+    }
+
+    if user.admin {
+        return Ok(())
+    }
+
+    let (rule, limit) = match config.user_type_limits.iter()
+        .filter_map(|(rule, limit)| type_rule_specificity(rule, content_type, mime_type).map(|specificity| (specificity, rule, limit)))
+        .max_by_key(|(specificity, _, _)| *specificity) {
+        Some((_, rule, limit)) => (rule, limit),
+        // The body was only capped by the largest limit of any type, types without a rule are held to the instance's
+        None => {
+            if config.user_upload_size_limit > 0 && (size / 1000000) as i32 > config.user_upload_size_limit {
+                return Err(status::Custom(Status::BadRequest, Json(Error {
+                    error: format!("File size too big! Maximum of {} megabytes", config.user_upload_size_limit)
+                })))
+            }
+
+            return Ok(())
+        }
+    };
+
+    let max_size = if limit.max_size > 0 { limit.max_size } else { config.user_upload_size_limit };
+    if max_size > 0 && (size / 1000000) as i32 > max_size {
+        return Err(status::Custom(Status::BadRequest, Json(Error {
+            error: format!("File size too big! Maximum of {} megabytes for {}", max_size, rule)
+        })))
+    }
+
+    if limit.max_count > 0 {
+        // Counted against the rule that applies to this upload, so other uploads matching it under a more specific rule count too
+        let count = user.uploads.iter()
+            .filter_map(|id| media_database.get(id).ok().flatten())
+            .filter_map(|media_vec| serde_json::from_str::<Media>(&String::from_utf8_lossy(&media_vec)).ok())
+            .filter(|media| type_rule_specificity(rule, &media.data_type, &self::mime_type(media)).is_some())
+            .count();
+
+        if count as i32 >= limit.max_count {
+            return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: format!("Maximum {} uploads reached. Maximum of {} uploads per account", rule, limit.max_count)
+            })))
+        }
+    }
+
+    Ok(())
+}
+
+/// Works out when media deletes itself from either a number of seconds from now or a date,
+/// keeping it within the instance's maximum expiration counted from `upload_date`.
+///
+/// Media without an expiration is kept for good, unless the user is held to the maximum
+pub fn expiration_date(
+    config: &Config,
+    user: &User,
+    expires_in: Option<u64>,
+    expires_at: Option<&str>,
+    upload_date: DateTime<Utc>
+) -> Result<Option<DateTime<Utc>>, status::Custom<Json<Error>>> {
+    let now = Utc::now();
+
+    let expiration_date = match (expires_in, expires_at) {
+        (Some(_), Some(_)) => return Err(status::Custom(Status::BadRequest, Json(Error {
+            error: String::from("Only one of expires_in or expires_at can be set")
+        }))),
+        (Some(expires_in), None) => match Duration::from_std(std::time::Duration::from_secs(expires_in)).ok().and_then(|duration| now.checked_add_signed(duration)) {
+            Some(result) => Some(result),
+            None => return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: String::from("Expiration is too far in the future")
+            })))
+        },
+        (None, Some(expires_at)) => match DateTime::parse_from_rfc3339(expires_at) {
+            Ok(result) => Some(result.with_timezone(&Utc)),
+            Err(_) => return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: String::from("Expiration date must be in RFC 3339 format (e.g. 2030-01-01T00:00:00Z)")
+            })))
+        },
+        (None, None) => None
+    };
+
+    if matches!(expiration_date, Some(expiration_date) if expiration_date <= now) {
+        return Err(status::Custom(Status::BadRequest, Json(Error {
+            error: String::from("Expiration date must be in the future")
+        })))
+    }
+
+    if user.admin || config.user_max_expiration <= 0 {
+        return Ok(expiration_date)
+    }
+
+    let max_expiration_date = upload_date + Duration::hours(config.user_max_expiration as i64);
+
+    match expiration_date {
+        Some(expiration_date) if expiration_date > max_expiration_date => Err(status::Custom(Status::BadRequest, Json(Error {
+            error: format!("Expiration too far in the future. Maximum of {} hours after uploading", config.user_max_expiration)
+        }))),
+        Some(expiration_date) => Ok(Some(expiration_date)),
+        None => Ok(Some(max_expiration_date))
+    }
+}
+
+pub fn check_max_downloads(max_downloads: Option<u32>) -> Result<(), status::Custom<Json<Error>>> {
+    if max_downloads == Some(0) {
+        return Err(status::Custom(Status::BadRequest, Json(Error {
+            error: String::from("max_downloads must be at least 1")
+        })))
+    }
+
+    Ok(())
+}
+
+pub fn downloads_exhausted_error() -> status::Custom<Json<Error>> {
+    status::Custom(Status::Gone, Json(Error {
+        error: String::from("Media has reached its download limit")
+    }))
+}
+
+/// Hashes the password media is locked with the same way account passwords are
+pub fn hash_media_password(config: &Config, password: Option<&str>) -> Result<Option<String>, status::Custom<Json<Error>>> {
+    let password = match password {
+        Some(result) => result,
+        None => return Ok(None)
+    };
+
+    if password.is_empty() {
+        return Err(status::Custom(Status::BadRequest, Json(Error {
+            error: String::from("Password can't be empty")
+        })))
+    }
+
+    if config.user_password_limit > 0 && password.len() as i32 > config.user_password_limit {
+        return Err(status::Custom(Status::BadRequest, Json(Error {
+            error: format!("Password too long! Maximum length of {} characters", config.user_password_limit)
+        })))
+    }
+
+    let salt = SaltString::generate(&mut OsRng);
+    match Pbkdf2.hash_password(password.as_bytes(), &salt) {
+        Ok(result) => Ok(Some(result.to_string())),
+        Err(_) => Err(internal_error())
+    }
+}
+
+/// Whether a password unlocks media, media without a password never matches
+pub fn verify_media_password(media: &Media, password: &str) -> bool {
+    let password_hash = match media.password.as_deref().map(PasswordHash::new) {
+        Some(Ok(result)) => result,
+        _ => return false
+    };
+
+    Pbkdf2.verify_password(password.as_bytes(), &password_hash).is_ok()
+}
+
+/// Makes sure the request may access password protected media, through either its password or an unlock token.
+///
+/// Passwords are throttled per media & address the same way as through /unlock
+pub fn check_media_access(
+    config: &Config,
+    database: &Db,
+    media: &Media,
+    password: Option<&str>,
+    unlock_token: Option<&str>,
+    address: Option<IpAddr>
+) -> Result<(), status::Custom<Json<Error>>> {
+    if media.password.is_none() {
+        return Ok(())
+    }
+
+    if let Some(token) = unlock_token {
+        let unlock_database = match database.open_tree("unlock") {
+            Ok(result) => result,
+            Err(_) => return Err(internal_error())
+        };
+
+        let unlock = match unlock_database.get(token) {
+            Ok(Some(unlock_vec)) => serde_json::from_str::<MediaUnlock>(&String::from_utf8_lossy(&unlock_vec)).ok(),
+            _ => None
+        };
+
+        if matches!(unlock, Some(unlock) if unlock.media_id == media.id && unlock.expiration_date > Utc::now()) {
+            return Ok(())
+        }
+    }
+
+    if let Some(password) = password {
+        if attempt_media_password(config, media, password, address)? {
+            return Ok(())
+        }
+    }
+
+    Err(status::Custom(Status::Unauthorized, Json(Error {
+        error: match (password, unlock_token) {
+            (None, None) => String::from("Media is password protected"),
+            _ => String::from("Wrong password or expired unlock token")
+        }
+    })))
+}
+
+/// Wrong passwords given for media from an address, forgotten `media_unlock_lockout` minutes after the first
+struct UnlockFailures {
+    media_id: String,
+    address: Option<IpAddr>,
+    count: i32,
+    since: DateTime<Utc>
+}
+
+static UNLOCK_FAILURES: Mutex<Vec<UnlockFailures>> = Mutex::new(Vec::new());
+
+/// Whether a password unlocks media, refusing to check it once `media_unlock_attempts` wrong passwords were given for it from the address
+pub fn attempt_media_password(config: &Config, media: &Media, password: &str, address: Option<IpAddr>) -> Result<bool, status::Custom<Json<Error>>> {
+    check_unlock_attempts(config, &media.id, address)?;
+
+    let unlocked = verify_media_password(media, password);
+    record_unlock_attempt(&media.id, address, unlocked);
+
+    Ok(unlocked)
+}
+
+fn check_unlock_attempts(config: &Config, media_id: &str, address: Option<IpAddr>) -> Result<(), status::Custom<Json<Error>>> {
+    if config.media_unlock_attempts <= 0 {
+        return Ok(())
+    }
+
+    let mut failures = UNLOCK_FAILURES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let lockout_start = Utc::now() - Duration::minutes(config.media_unlock_lockout.max(1) as i64);
+    failures.retain(|failure| failure.since > lockout_start);
+
+    if failures.iter().any(|failure| failure.media_id == media_id && failure.address == address && failure.count >= config.media_unlock_attempts) {
+        return Err(status::Custom(Status::TooManyRequests, Json(Error {
+            error: String::from("Too many wrong passwords, try again later")
+        })))
+    }
+
+    Ok(())
+}
+
+/// Counts a wrong password towards `media_unlock_attempts`, or forgets the address' failures once it gets the password right
+fn record_unlock_attempt(media_id: &str, address: Option<IpAddr>, unlocked: bool) {
+    let mut failures = UNLOCK_FAILURES.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if unlocked {
+        failures.retain(|failure| failure.media_id != media_id || failure.address != address);
+        return
+    }
+
+    match failures.iter_mut().find(|failure| failure.media_id == media_id && failure.address == address) {
+        Some(failure) => failure.count += 1,
+        None => failures.push(UnlockFailures {
+            media_id: media_id.to_string(),
+            address,
+            count: 1,
+            since: Utc::now()
+        })
+    }
+}
+
+/// Stores a token unlocking the media for `media_unlock_expiration` minutes
+pub fn create_unlock_token(config: &Config, database: &Db, media: &Media) -> Result<MediaUnlock, status::Custom<Json<Error>>> {
+    let unlock_database = match database.open_tree("unlock") {
+        Ok(result) => result,
+        Err(_) => return Err(internal_error())
+    };
+
+    let unlock = MediaUnlock {
+        token: Alphanumeric.sample_string(&mut OsRng, 32),
+        media_id: media.id.clone(),
+        expiration_date: Utc::now() + Duration::minutes(config.media_unlock_expiration.max(1) as i64)
+    };
+
+    let unlock_vec = match serde_json::to_vec(&unlock) {
+        Ok(result) => result,
+        Err(_) => return Err(internal_error())
+    };
+
+    if unlock_database.insert(&unlock.token, unlock_vec).is_err() || unlock_database.flush().is_err() {
+        return Err(internal_error())
+    }
+
+    Ok(unlock)
+}
+
+/// Removes unlock tokens past their expiration date
+pub fn expire_unlock_tokens(database: &Db) {
+    let unlock_database = match database.open_tree("unlock") {
+        Ok(result) => result,
+        Err(err) => {
+            error!("Failed to open database tree (unlock), {}", err);
+            return
+        }
+    };
+
+    let now = Utc::now();
+    let expired: Vec<MediaUnlock> = unlock_database.iter()
+        .filter_map(|item| item.ok())
+        .filter_map(|item| serde_json::from_str::<MediaUnlock>(&String::from_utf8_lossy(&item.1)).ok())
+        .filter(|unlock| unlock.expiration_date <= now)
+        .collect();
+
+    for unlock in expired {
+        let _ = unlock_database.remove(&unlock.token);
+    }
+
+    if unlock_database.flush().is_err() {
+        error!("Failed to flush database tree (unlock)");
+    }
+}
+
+/// Key a grant of the media to a user is stored under, grants of a media share its id as prefix
+pub fn grant_key(media_id: &str, username: &str) -> String {
+    format!("{}/{}", media_id, username)
+}
+
+/// Whether the user may view the media, private media is only viewable by its author, admins & users it's been granted to
+pub fn can_view(grant_database: &Tree, media: &Media, user: Option<&User>) -> bool {
+    if !media.private {
+        return true
+    }
+
+    match user {
+        Some(user) => user.admin
+            || user.username == media.author_username
+            || matches!(grant_database.contains_key(grant_key(&media.id, &user.username)), Ok(true)),
+        None => false
+    }
+}
+
+/// Makes sure the owner of the api key may view the media
+pub fn check_media_visibility(database: &Db, media: &Media, api_key: Option<&str>) -> Result<(), status::Custom<Json<Error>>> {
+    if !media.private {
+        return Ok(())
+    }
+
+    let (user_database, grant_database) = match (database.open_tree("user"), database.open_tree("grant")) {
+        (Ok(user_database), Ok(grant_database)) => (user_database, grant_database),
+        _ => return Err(internal_error())
+    };
+
+    let user = match api_key {
+        Some(api_key) => find_user_by_api_key(&user_database, api_key),
+        None => return Err(status::Custom(Status::Unauthorized, Json(Error {
+            error: String::from("Media is private")
+        })))
+    };
+
+    if !can_view(&grant_database, media, user.as_ref()) {
+        return Err(status::Custom(Status::Forbidden, Json(Error {
+            error: String::from("You haven't been granted access to this media")
+        })))
+    }
+
+    Ok(())
+}
+
+/// Grants of the media, in order of username
+pub fn media_grants(grant_database: &Tree, media_id: &str) -> Vec<MediaGrant> {
+    grant_database.scan_prefix(grant_key(media_id, ""))
+        .filter_map(|item| item.ok())
+        .filter_map(|item| serde_json::from_str::<MediaGrant>(&String::from_utf8_lossy(&item.1)).ok())
+        .collect()
+}
+
+/// Moves a user's grants over to their new username, or removes them once the user is gone
+/// so they don't pass on to whoever takes the username next
+pub fn move_user_grants(database: &Db, username: &str, new_username: Option<&str>) -> sled::Result<()> {
+    let grant_database = database.open_tree("grant")?;
+
+    let grants: Vec<MediaGrant> = grant_database.iter()
+        .filter_map(|item| item.ok())
+        .filter_map(|item| serde_json::from_str::<MediaGrant>(&String::from_utf8_lossy(&item.1)).ok())
+        .filter(|grant| grant.username == username)
+        .collect();
+
+    for mut grant in grants {
+        grant_database.remove(grant_key(&grant.media_id, &grant.username))?;
+
+        if let Some(new_username) = new_username {
+            grant.username = new_username.to_string();
+
+            if let Ok(grant_vec) = serde_json::to_vec(&grant) {
+                grant_database.insert(grant_key(&grant.media_id, &grant.username), grant_vec)?;
+            }
+        }
+    }
+
+    grant_database.flush()?;
+    Ok(())
+}
+
+/// Signature of a download link, tying the media, the link's expiry (as a unix timestamp) & the address it's bound to to the signing secret
+pub fn sign_download(secret: &str, media_id: &str, expires: i64, ip: Option<&str>) -> String {
+    base64::encode_config(download_mac(secret, media_id, expires, ip).finalize().into_bytes(), base64::URL_SAFE_NO_PAD)
+}
+
+/// Whether a download link's signature was made with the signing secret, compared in constant time
+pub fn verify_download_signature(secret: &str, media_id: &str, expires: i64, ip: Option<&str>, signature: &str) -> bool {
+    let signature = match base64::decode_config(signature, base64::URL_SAFE_NO_PAD) {
+        Ok(result) => result,
+        Err(_) => return false
+    };
+
+    download_mac(secret, media_id, expires, ip).verify_slice(&signature).is_ok()
+}
+
+fn download_mac(secret: &str, media_id: &str, expires: i64, ip: Option<&str>) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}\n{}\n{}", media_id, expires, ip.unwrap_or_default()).as_bytes());
+    mac
+}
+
+/// Host of a Referer or Origin header, or of a bare host such as those in backend_domains
+pub fn url_host(url: &str) -> Option<String> {
+    let url = url.trim();
+    let rest = match url.find("://") {
+        Some(index) => &url[index + 3..],
+        None => url
+    };
+
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+
+    // IPv6 addresses are bracketed so their colons aren't taken for a port
+    let host = match host.strip_prefix('[') {
+        Some(address) => address.split(']').next()?,
+        None => host.split(':').next()?
+    };
+
+    let host = host.trim_end_matches('.').to_lowercase();
+    if host.is_empty() {
+        return None
+    }
+
+    Some(host)
+}
+
+/// Whether a host is matched by an allowlist entry, entries starting with "*." also match subdomains
+fn host_allowed(host: &str, entry: &str) -> bool {
+    match entry.trim().strip_prefix("*.") {
+        Some(domain) => matches!(url_host(domain), Some(domain) if host == domain || host.ends_with(&format!(".{}", domain))),
+        None => url_host(entry).as_deref() == Some(host)
+    }
+}
+
+/// Whether media may be downloaded by a page at `source`, being the request's Origin or Referer.
+///
+/// Requests telling neither aren't from a page or chose not to say, they're let through as there's no telling them apart
+pub fn hotlink_allowed(config: &Config, media: &Media, source: Option<&str>) -> bool {
+    let allowed_hosts = match &media.hotlink_hosts {
+        Some(hosts) => hosts,
+        None if !config.media_hotlink_protection => return true,
+        None if config.media_hotlink_allowed_hosts.is_empty() => &config.backend_domains,
+        None => &config.media_hotlink_allowed_hosts
+    };
+
+    let source = match source {
+        Some(result) => result,
+        None => return true
+    };
+
+    // Such as the "null" origin of sandboxed pages
+    let host = match url_host(source) {
+        Some(result) if source.contains("://") => result,
+        _ => return false
+    };
+
+    allowed_hosts.iter().any(|entry| host_allowed(&host, entry))
+}
+
+/// Whether media has been downloaded as many times as it may be, it's unavailable from then on until it's purged
+pub fn downloads_exhausted(media: &Media) -> bool {
+    matches!(media.max_downloads, Some(max_downloads) if media.downloads >= max_downloads as i64)
+}
+
+/// Removes a media record along with its grants & revisions, releasing its blobs & taking it off of its author's uploads & any albums.
+///
+/// Storage keys nothing references anymore are added to `released`, to be deleted through `delete_released` once the database is unlocked.
+/// Returns whether the media still existed
+pub fn remove_media(database: &Db, media: &Media, released: &mut Vec<String>) -> sled::Result<bool> {
+    let media_database = database.open_tree("media")?;
+    let user_database = database.open_tree("user")?;
+
+    if media_database.remove(&media.id)?.is_none() {
+        return Ok(false)
+    }
+
+    release_blob(database, &storage_key(media), released);
+
+    let grant_database = database.open_tree("grant")?;
+    for grant in media_grants(&grant_database, &media.id) {
+        grant_database.remove(grant_key(&grant.media_id, &grant.username))?;
+    }
+
+    let revision_database = database.open_tree("revision")?;
+    for revision in media_revisions(&revision_database, &media.id) {
+        remove_revision(database, &revision, released)?;
+    }
+
+    album_utils::remove_media_from_albums(database, &media.id)?;
+
+    user_database.update_and_fetch(&media.author_username, |user_vec| {
+        let mut user: User = serde_json::from_str(&String::from_utf8_lossy(user_vec?)).ok()?;
+        user.uploads.retain(|upload| upload != &media.id);
+
+        Some(IVec::from(match serde_json::to_vec(&user) {
+            Ok(result) => result,
+            Err(_) => return None
+        }))
+    })?;
+
+    Ok(true)
+}
+
+/// Removes every media whose expiration date has passed.
+///
+/// Media out of downloads is purged once its last download has been sent,
+/// `exhausted` also removes what was left behind (e.g. by a restart mid download) and is only safe while nothing is being downloaded
+pub fn expire_media(database_arc: &Mutex<Db>, storage: &dyn Storage, exhausted: bool) {
+    let mut released = Vec::new();
+
+    {
+        let database = match database_arc.lock() {
+            Ok(result) => result,
+            Err(_) => {
+                error!("Failed to lock database, expired media wasn't removed");
+                return
+            }
+        };
+
+        expire_media_records(&database, exhausted, &mut released);
+    }
+
+    delete_released(storage, &released);
+}
+
+fn expire_media_records(database: &Db, exhausted: bool, released: &mut Vec<String>) {
+    let media_database = match database.open_tree("media") {
+        Ok(result) => result,
+        Err(err) => {
+            error!("Failed to open database tree (media), {}", err);
+            return
+        }
+    };
+
+    let now = Utc::now();
+    let expired: Vec<Media> = media_database.iter()
+        .filter_map(|item| item.ok())
+        .filter_map(|item| serde_json::from_str::<Media>(&String::from_utf8_lossy(&item.1)).ok())
+        .filter(|media| matches!(media.expiration_date, Some(expiration_date) if expiration_date <= now) || (exhausted && downloads_exhausted(media)))
+        .collect();
+
+    for media in expired {
+        match remove_media(database, &media, released) {
+            Ok(_) => info!("[Cleanup] Removed expired media {}", media.id),
+            Err(err) => error!("Failed to remove expired media {}, {}", media.id, err)
+        }
+    }
+
+    if database.flush().is_err() {
+        error!("Failed to flush database after expiring media");
+    }
+}
+
+/// Size of the media as uploaded, regardless of compression or it sharing a blob with other media
+pub fn logical_size(media: &Media) -> u64 {
+    media.data_length.unwrap_or(media.data_size as u64)
+}
+
+/// Sniffs the file type of a file on disk
+pub fn infer_file_type(path: &Path) -> io::Result<Type> {
+    let mut header = Vec::with_capacity(INFER_HEADER_LENGTH);
+    File::open(path)?
+        .take(INFER_HEADER_LENGTH as u64)
+        .read_to_end(&mut header)?;
+
+    Ok(infer_type(&header))
+}
+
+/// Sniffs the file type from the first `INFER_HEADER_LENGTH` bytes of a file,
+/// anything unrecognized that isn't text is treated as arbitrary binary data
+pub fn infer_type(header: &[u8]) -> Type {
+    match infer::get(header) {
+        // SVGs are images, sniffed apart so they're sanitized & not stored as plain text
+        Some(result) if result.mime_type() == "text/xml" && sanitize::is_svg(header) => svg_type(),
+        Some(result) => result,
+        None if sanitize::is_svg(header) => svg_type(),
+        None if text::is_text(header) => Type::new(MatcherType::Text, "text/plain", "txt", MediaApi::ignore),
+        None => Type::new(MatcherType::Custom, "application/octet-stream", "bin", MediaApi::ignore)
+    }
+}
+
+fn svg_type() -> Type {
+    Type::new(MatcherType::Image, "image/svg+xml", "svg", MediaApi::ignore)
+}
+
+/// Category a sniffed file type is listed under
+pub fn content_type(data_type: &Type) -> ContentType {
+    match (data_type.matcher_type(), data_type.mime_type()) {
+        (MatcherType::Video, _) => ContentType::Video,
+        (MatcherType::Image, _) => ContentType::Image,
+        (MatcherType::Audio, _) => ContentType::Audio,
+        (MatcherType::Text, _) => ContentType::Text,
+        (MatcherType::Doc | MatcherType::Book, _) => ContentType::Document,
+        (MatcherType::Font, _) => ContentType::Font,
+        // `infer` lists a few documents & fonts among its archives
+        (MatcherType::Archive, "application/pdf" | "application/rtf" | "application/postscript" | "application/epub+zip") => ContentType::Document,
+        (MatcherType::Archive, _) if data_type.extension() == "eot" => ContentType::Font,
+        (MatcherType::Archive, "application/x-shockwave-flash" | "application/vnd.sqlite3" | "application/x-nintendo-nes-rom" | "application/dicom") => ContentType::Other,
+        (MatcherType::Archive, _) => ContentType::Archive,
+        _ => ContentType::Other
+    }
+}
+
+/// Directory blobs of a content type are stored under
+pub fn type_directory(content_type: &ContentType) -> &'static str {
+    match content_type {
+        ContentType::Video => "Video",
+        ContentType::Image => "Image",
+        ContentType::Audio => "Audio",
+        ContentType::Text => "Text",
+        ContentType::Document => "Document",
+        ContentType::Archive => "Archive",
+        ContentType::Font => "Font",
+        ContentType::Other => "Other"
+    }
+}
+
+/// Hex encoded SHA-256 of a file's contents
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Mime type the media is served as, falling back on the extension for uploads predating mime detection
+pub fn mime_type(media: &Media) -> String {
+    if let Some(mime_type) = &media.mime_type {
+        return mime_type.clone()
+    }
+
+    match HttpContentType::from_extension(&media.extension) {
+        Some(result) => result.to_string(),
+        None => String::from("application/octet-stream")
+    }
+}
+
+/// Strong entity tag identifying the media's contents
+pub fn entity_tag(media: &Media) -> String {
+    match &media.data_hash {
+        Some(hash) => format!(r#""{}""#, hash),
+        None => format!(r#""{}-{}-{}""#, media.id, content_date(media).timestamp(), media.data_size)
+    }
+}
+
+/// When the media's current content was stored, either uploaded or last replaced
+pub fn content_date(media: &Media) -> DateTime<Utc> {
+    media.modified_date.unwrap_or(media.upload_date)
+}
+
+/// How the media's blob is laid out on disk
+pub fn media_layout(media: &Media) -> Layout {
+    match (&media.data_blocks, media.data_compressed) {
+        (Some(offsets), true) => Layout::Blocks(media.data_codec, offsets.clone()),
+        (None, true) => Layout::Stream,
+        _ => Layout::Plain
+    }
+}
+
+/// Key of the media's blob within storage
+pub fn storage_key(media: &Media) -> String {
+    media.data_path.to_string_lossy().to_string()
+}
+
+/// Amount of bytes stored for the media before encryption
+pub fn stored_length(media: &Media) -> u64 {
+    match media.data_nonce {
+        Some(_) => encryption::decrypted_length(media.data_size as u64),
+        None => media.data_size as u64
+    }
+}
+
+/// Opens the media's blob for reading its original contents
+pub fn open_blob(storage: &Arc<dyn Storage>, keyring: &Keyring, media: &Media) -> io::Result<BlobReader> {
+    let cipher = keyring.blob_cipher(&media.data_key_id, &media.data_nonce, u64::try_from(media.data_size).ok())?;
+    BlobReader::open(storage.clone(), &storage_key(media), cipher, &media_layout(media), media.data_length)
+}
+
+/// Opens the media's blob for reading its stored bytes, as they are before decryption is taken into account
+pub fn open_stored_blob(storage: &Arc<dyn Storage>, keyring: &Keyring, media: &Media) -> io::Result<BlobReader> {
+    let cipher = keyring.blob_cipher(&media.data_key_id, &media.data_nonce, u64::try_from(media.data_size).ok())?;
+    BlobReader::open(storage.clone(), &storage_key(media), cipher, &Layout::Plain, Some(stored_length(media)))
+}
+
+/// Compresses `source` into `destination` using the seekable layout,
+/// returning the block offsets if the compressed copy ended up smaller than the original
+fn compress_file(source: &Path, destination: &Path, codec: Codec) -> io::Result<Option<Vec<u64>>> {
+    let mut source_file = File::open(source)?;
+    let mut destination_file = io::BufWriter::new(File::create(destination)?);
+
+    let (written, offsets) = blob::compress_seekable(&mut source_file, &mut destination_file, codec)?;
+    destination_file.flush()?;
+
+    if written <= fs::metadata(source)?.len() {
+        Ok(Some(offsets))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Encrypts `source` into `destination` with the given nonce
+fn encrypt_file(key: &encryption::EncryptionKey, nonce: &str, source: &Path, destination: &Path) -> io::Result<()> {
+    let mut source_file = io::BufReader::new(File::open(source)?);
+    let mut destination_file = io::BufWriter::new(File::create(destination)?);
+
+    encryption::encrypt(key, nonce, &mut source_file, &mut destination_file)?;
+    destination_file.flush()
+}
+
+/// Keys of blobs being stored while the database is unlocked, identical uploads wait on the first instead of overwriting it
+static PENDING_BLOBS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Claim on storing a blob, given up when dropped
+struct PendingBlob(String);
+
+impl PendingBlob {
+    fn claim(data_key: &str) -> Option<PendingBlob> {
+        let mut pending = PENDING_BLOBS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if pending.iter().any(|key| key == data_key) {
+            return None
+        }
+
+        pending.push(data_key.to_string());
+        Some(PendingBlob(data_key.to_string()))
+    }
+}
+
+impl Drop for PendingBlob {
+    fn drop(&mut self) {
+        PENDING_BLOBS.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .retain(|key| key != &self.0);
+    }
+}
+
+/// Adds a reference to a stored blob, `None` when there's no blob under the key
+fn reference_blob(blob_database: &Tree, data_key: &str) -> Result<Option<Blob>, status::Custom<Json<Error>>> {
+    let referenced = blob_database.update_and_fetch(data_key, |blob_vec| {
+        let mut blob: Blob = serde_json::from_str(&String::from_utf8_lossy(blob_vec?)).ok()?;
+        blob.references += 1;
+
+        Some(IVec::from(match serde_json::to_vec(&blob) {
+            Ok(result) => result,
+            Err(_) => return None
+        }))
+    });
+
+    match referenced {
+        Ok(Some(blob_vec)) => match serde_json::from_str(&String::from_utf8_lossy(&blob_vec)) {
+            Ok(result) => Ok(Some(result)),
+            Err(_) => Err(internal_error())
+        },
+        Ok(None) => Ok(None),
+        Err(_) => Err(internal_error())
+    }
+}
+
+/// Locks the database for the short writes around processing an upload
+fn lock_database(database_arc: &Mutex<Db>) -> Result<MutexGuard<'_, Db>, status::Custom<Json<Error>>> {
+    match database_arc.lock() {
+        Ok(result) => Ok(result),
+        Err(_) => Err(internal_error())
+    }
+}
+
+/// Stores the staged upload's content, or references the existing blob if the same content was uploaded before.
+///
+/// Blobs are keyed by their content hash, so identical uploads resolve to the same key.
+/// The database is only locked while the blob's record is looked up & written, not while it's compressed, encrypted & stored
+fn store_blob(
+    config: &Config,
+    database_arc: &Mutex<Db>,
+    storage: &dyn Storage,
+    keyring: &Keyring,
+    staged_path: &Path,
+    type_directory: &str,
+    data_hash: String
+) -> Result<Blob, status::Custom<Json<Error>>> {
+    let data_key = format!("{}/{}", type_directory, data_hash);
+
+    // Held until the blob's record has been written
+    let _pending = loop {
+        {
+            let database = lock_database(database_arc)?;
+            if let Some(blob) = reference_blob(&database.get_tree("blob")?, &data_key)? {
+                return Ok(blob)
+            }
+        }
+
+        if let Some(pending) = PendingBlob::claim(&data_key) {
+            break pending
+        }
+
+        thread::sleep(std::time::Duration::from_millis(100));
+    };
+
+    let upload_size = match fs::metadata(staged_path) {
+        Ok(result) => result.len(),
+        Err(_) => return Err(internal_error())
+    };
+
+    let compressed_path = staging_path(config)?;
+
+    let mut data_blocks = None;
+    if config.backend_store_compressed {
+        if let Ok(offsets) = compress_file(staged_path, &compressed_path, config.backend_compression_codec) {
+            data_blocks = offsets;
+        }
+    }
+
+    let mut stored_path = match data_blocks {
+        Some(_) => compressed_path.clone(),
+        None => staged_path.to_path_buf()
+    };
+
+    let encrypted_path = staging_path(config)?;
+
+    let mut data_nonce = None;
+    let mut data_key_id = None;
+    if let Some(key) = keyring.current() {
+        let nonce = encryption::generate_nonce();
+
+        if encrypt_file(key, &nonce, &stored_path, &encrypted_path).is_err() {
+            let _ = fs::remove_file(&compressed_path);
+            let _ = fs::remove_file(&encrypted_path);
+            return Err(internal_error())
+        }
+
+        stored_path = encrypted_path.clone();
+        data_nonce = Some(nonce);
+        data_key_id = Some(key.id.clone());
+    }
+
+    let data_size = fs::metadata(&stored_path).map(|metadata| metadata.len() as i32);
+    let stored = storage.put(&data_key, &stored_path);
+
+    for path in [&compressed_path, &encrypted_path] {
+        if path.exists() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    let data_size = match (data_size, stored) {
+        (Ok(result), Ok(_)) => result,
+        _ => return Err(internal_error())
+    };
+
+    let blob = Blob {
+        data_path: PathBuf::from(&data_key),
+        data_hash,
+        data_size,
+        data_compressed: data_blocks.is_some(),
+        data_codec: config.backend_compression_codec,
+        data_nonce,
+        data_key_id,
+        data_length: Some(upload_size),
+        data_blocks,
+        references: 1
+    };
+
+    let blob_vec = match serde_json::to_vec(&blob) {
+        Ok(result) => result,
+        Err(_) => return Err(internal_error())
+    };
+
+    let inserted = lock_database(database_arc)?.get_tree("blob")?.insert(&data_key, blob_vec).is_ok();
+    if !inserted {
+        let _ = storage.delete(&data_key);
+        return Err(internal_error())
+    }
+
+    Ok(blob)
+}
+
+/// Drops a reference to a stored blob, adding it & its cached variants to `released` once nothing references it.
+///
+/// Keys without a blob record belong to uploads predating deduplication and are released right away
+pub fn release_blob(database: &Db, data_key: &str, released: &mut Vec<String>) {
+    let (blob_database, variant_database) = match (database.open_tree("blob"), database.open_tree("variant")) {
+        (Ok(blob_database), Ok(variant_database)) => (blob_database, variant_database),
+        _ => {
+            error!("Failed to open the blob database, {} wasn't released", data_key);
+            return
+        }
+    };
+
+    let remaining = blob_database.update_and_fetch(data_key, |blob_vec| {
+        let mut blob: Blob = serde_json::from_str(&String::from_utf8_lossy(blob_vec?)).ok()?;
+        blob.references = blob.references.saturating_sub(1);
+
+        if blob.references == 0 {
+            return None
+        }
+
+        Some(IVec::from(match serde_json::to_vec(&blob) {
+            Ok(result) => result,
+            Err(_) => return None
+        }))
+    });
+
+    if let Ok(None) = remaining {
+        released.push(data_key.to_string());
+        released.extend(variant::remove_all(&variant_database, data_key));
+    }
+}
+
+/// Deletes released blobs & variants from storage, which can take a while so the database shouldn't be locked meanwhile
+pub fn delete_released(storage: &dyn Storage, released: &[String]) {
+    for key in released {
+        // Variants are recorded before they're stored, so they may never have made it
+        if storage.delete(key).is_err() && storage.exists(key).unwrap_or(true) {
+            error!("Failed to remove stored blob {}", key);
+        }
+    }
+}
+
+/// Deletes released blobs & variants from storage on a blocking thread, off of the async workers
+pub async fn delete_released_blocking(storage: Arc<dyn Storage>, released: Vec<String>) {
+    if released.is_empty() {
+        return
+    }
+
+    if rocket::tokio::task::spawn_blocking(move || delete_released(storage.as_ref(), &released)).await.is_err() {
+        error!("Failed to remove released blobs");
+    }
+}
+
+/// Runs slow work such as hashing, compressing & storing uploads on a blocking thread, off of the async workers
+pub async fn run_blocking<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, status::Custom<Json<Error>>> + Send + 'static
+) -> Result<T, status::Custom<Json<Error>>> {
+    match rocket::tokio::task::spawn_blocking(work).await {
+        Ok(result) => result,
+        Err(_) => Err(internal_error())
+    }
+}
+
+/// Turns a fully received upload sitting at `staged_path` into a media record.
+///
+/// Every upload route funnels into this, the staged file is always consumed
+/// regardless of whether the upload succeeds.
+/// Neither the database nor the config are locked while the upload is processed, so callers shouldn't hold them either
+pub async fn store_upload(
+    config: Config,
+    database_arc: Arc<Mutex<Db>>,
+    storage: Arc<dyn Storage>,
+    keyring: Arc<Keyring>,
+    user: User,
+    details: UploadDetails,
+    staged_path: PathBuf
+) -> Result<Media, status::Custom<Json<Error>>> {
+    run_blocking(move || {
+        let result = store_staged_upload(&config, &database_arc, storage.as_ref(), &keyring, user, details, &staged_path);
+
+        if staged_path.exists() {
+            let _ = fs::remove_file(&staged_path);
+        }
+
+        result
+    }).await
+}
+
+fn store_staged_upload(
+    config: &Config,
+    database_arc: &Mutex<Db>,
+    storage: &dyn Storage,
+    keyring: &Keyring,
+    user: User,
+    details: UploadDetails,
+    staged_path: &Path
+) -> Result<Media, status::Custom<Json<Error>>> {
+    check_upload_allowed(config, &user, &details.name)?;
+
+    let upload_date = chrono::offset::Utc::now();
+    let expiration_date = expiration_date(config, &user, details.expires_in, details.expires_at.as_deref(), upload_date)?;
+    check_max_downloads(details.max_downloads)?;
+
+    let content = store_content(config, database_arc, storage, keyring, &user, details.text, staged_path)?;
+
+    let media = Media {
+        id: Alphanumeric.sample_string(&mut OsRng, config.media_dynamic_id_length as usize),
+        name: details.name,
+        extension: content.extension,
+        data_type: content.data_type,
+        mime_type: Some(content.mime_type),
+        data_hash: Some(content.blob.data_hash),
+        data_path: content.blob.data_path,
+        data_size: content.blob.data_size,
+        upload_date,
+        data_compressed: content.blob.data_compressed,
+        data_codec: content.blob.data_codec,
+        data_nonce: content.blob.data_nonce,
+        data_key_id: content.blob.data_key_id,
+        data_length: content.blob.data_length,
+        data_blocks: content.blob.data_blocks,
+        author_username: user.username.clone(),
+        unlisted: details.unlisted.unwrap_or(false),
+        private: details.private.unwrap_or(false),
+        hotlink_hosts: None,
+        tags: filter_tags(config, &user, &details.tags),
+        downloads: 0,
+        metadata: content.metadata,
+        language: details.language,
+        expiration_date,
+        max_downloads: details.max_downloads,
+        password: details.password_hash,
+        revision: 0,
+        modified_date: None
+    };
+
+    println!("Media: {:#?}", media);
+
+    let mut released = Vec::new();
+    let saved = save_media(&lock_database(database_arc)?, &media, &user.username, &mut released);
+    delete_released(storage, &released);
+
+    saved.map(|_| media)
+}
+
+/// Records newly stored media & adds it to its author's uploads, releasing its blob into `released` when it can't be
+fn save_media(database: &MutexGuard<Db>, media: &Media, username: &str, released: &mut Vec<String>) -> Result<(), status::Custom<Json<Error>>> {
+    let user_database = &database.get_tree("user")?;
+    let media_database = &database.get_tree("media")?;
+    let data_key = storage_key(media);
+
+    let media_vec = match serde_json::to_vec(media) {
+        Ok(result) => result,
+        Err(_) => {
+            release_blob(database, &data_key, released);
+            return Err(internal_error())
+        }
+    };
+
+    if media_database.insert(&media.id, media_vec).is_err() || media_database.flush().is_err() {
+        release_blob(database, &data_key, released);
+        return Err(internal_error())
+    }
+
+    // Read again, the account may have changed while the upload was processed
+    let user = match user_database.update_and_fetch(username, |user_vec| {
+        let mut user: User = serde_json::from_str(&String::from_utf8_lossy(user_vec?)).ok()?;
+        user.uploads.push(media.id.clone());
+
+        Some(IVec::from(match serde_json::to_vec(&user) {
+            Ok(result) => result,
+            Err(_) => return None
+        }))
+    }) {
+        Ok(Some(user_vec)) => serde_json::from_str::<User>(&String::from_utf8_lossy(&user_vec)).ok(),
+        _ => None
+    };
+
+    let user = match user {
+        Some(result) => result,
+        None => {
+            let _ = remove_media(database, media, released);
+            return Err(status::Custom(Status::Unauthorized, Json(Error {
+                error: String::from("Invalid or wrong credentials provided")
+            })))
+        }
+    };
+
+    println!("User: {:#?}", user);
+
+    if user_database.flush().is_err() {
+        return Err(internal_error())
+    }
+
+    Ok(())
+}
+
+/// Content of an upload once it's been checked, cleaned up & stored
+struct StoredContent {
+    extension: String,
+    data_type: ContentType,
+    mime_type: String,
+    metadata: Option<MediaApi::ContentMetadata>,
+    blob: Blob
+}
+
+/// Checks the staged upload's size & type, strips & sanitizes it, then stores it as a blob
+fn store_content(
+    config: &Config,
+    database_arc: &Mutex<Db>,
+    storage: &dyn Storage,
+    keyring: &Keyring,
+    user: &User,
+    text: bool,
+    staged_path: &Path
+) -> Result<StoredContent, status::Custom<Json<Error>>> {
+    let upload_size = match fs::metadata(staged_path) {
+        Ok(result) => result.len(),
+        Err(_) => return Err(internal_error())
+    };
+
+    {
+        let database = lock_database(database_arc)?;
+
+        // Replaced content is kept as a revision, so replacements are held to the quota with it still counted
+        check_upload_size(config, user, &database.get_tree("media")?, &database.get_tree("revision")?, upload_size)?;
+    }
+
+    if text {
+        match text::is_utf8_file(staged_path) {
+            Ok(true) => {},
+            Ok(false) => return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: String::from("Text must be valid UTF-8")
+            }))),
+            Err(_) => return Err(internal_error())
+        }
+    }
+
+    let data_type = match infer_file_type(staged_path) {
+        // Text such as HTML or a shell script is still served as plain text
+        Ok(_) if text => Type::new(MatcherType::Text, "text/plain", "txt", MediaApi::ignore),
+        Ok(result) => result,
+        Err(_) => return Err(internal_error())
+    };
+
+    let content_type = content_type(&data_type);
+
+    {
+        let database = lock_database(database_arc)?;
+        check_upload_type(config, user, &database.get_tree("media")?, &content_type, data_type.mime_type(), upload_size)?;
+    }
+
+    // Read before stripping, which removes the capture date
+    let metadata = match data_type.matcher_type() {
+        MatcherType::Image => Some(metadata::read_image(staged_path)),
+        MatcherType::Video => Some(metadata::read_video(staged_path, data_type.mime_type())),
+        MatcherType::Audio => Some(metadata::read_audio(staged_path)),
+        _ => None
+    };
+
+    if config.media_strip_metadata {
+        let orientation = metadata.as_ref().and_then(|metadata| metadata.orientation);
+
+        if strip::strip_file(staged_path, data_type.mime_type(), orientation).is_err() {
+            return Err(internal_error())
+        }
+    }
+
+    // Always done, unlike stripping, since scripts within SVGs run on the API's origin
+    match sanitize::sanitize_file(staged_path, data_type.mime_type()) {
+        Ok(_) => {},
+        Err(error) if error.kind() == io::ErrorKind::InvalidData => return Err(status::Custom(Status::BadRequest, Json(Error {
+            error: String::from("SVG is malformed and couldn't be sanitized")
+        }))),
+        Err(_) => return Err(internal_error())
+    }
+
+    let data_hash = match hash_file(staged_path) {
+        Ok(result) => result,
+        Err(_) => return Err(internal_error())
+    };
+
+    let blob = store_blob(config, database_arc, storage, keyring, staged_path, type_directory(&content_type), data_hash)?;
+
+    Ok(StoredContent {
+        extension: data_type.extension().to_string(),
+        data_type: content_type,
+        mime_type: data_type.mime_type().to_string(),
+        metadata,
+        blob
+    })
+}
+
+/// Replaces the content of existing media with a fully received upload sitting at `staged_path`,
+/// keeping the previous content as a revision. Text media can only be replaced by text.
+///
+/// The staged file is always consumed regardless of whether the replacement succeeds,
+/// it's processed the same way as `store_upload` without the database or config locked
+pub async fn replace_content(
+    config: Config,
+    database_arc: Arc<Mutex<Db>>,
+    storage: Arc<dyn Storage>,
+    keyring: Arc<Keyring>,
+    user: User,
+    media: Media,
+    staged_path: PathBuf
+) -> Result<Media, status::Custom<Json<Error>>> {
+    run_blocking(move || {
+        let result = replace_staged_content(&config, &database_arc, storage.as_ref(), &keyring, &user, &media, &staged_path);
+
+        if staged_path.exists() {
+            let _ = fs::remove_file(&staged_path);
+        }
+
+        result
+    }).await
+}
+
+fn replace_staged_content(
+    config: &Config,
+    database_arc: &Mutex<Db>,
+    storage: &dyn Storage,
+    keyring: &Keyring,
+    user: &User,
+    media: &Media,
+    staged_path: &Path
+) -> Result<Media, status::Custom<Json<Error>>> {
+    let content = store_content(config, database_arc, storage, keyring, user, media.data_type == ContentType::Text, staged_path)?;
+
+    let mut released = Vec::new();
+    let replaced = swap_content(config, &lock_database(database_arc)?, media, content, &mut released);
+    delete_released(storage, &released);
+
+    replaced
+}
+
+/// Points the media at its new content & keeps the previous content as a revision,
+/// blobs no longer referenced after pruning its revisions are added to `released`
+fn swap_content(
+    config: &Config,
+    database: &MutexGuard<Db>,
+    media: &Media,
+    content: StoredContent,
+    released: &mut Vec<String>
+) -> Result<Media, status::Custom<Json<Error>>> {
+    let media_database = &database.get_tree("media")?;
+    let revision_database = &database.get_tree("revision")?;
+    let data_key = content.blob.data_path.to_string_lossy().to_string();
+    let replaced_date = Utc::now();
+
+    let replace = |edited_media: &mut Media| {
+        edited_media.extension = content.extension.clone();
+        edited_media.data_type = content.data_type.clone();
+        edited_media.mime_type = Some(content.mime_type.clone());
+        edited_media.data_hash = Some(content.blob.data_hash.clone());
+        edited_media.data_path = content.blob.data_path.clone();
+        edited_media.data_size = content.blob.data_size;
+        edited_media.data_compressed = content.blob.data_compressed;
+        edited_media.data_codec = content.blob.data_codec;
+        edited_media.data_nonce = content.blob.data_nonce.clone();
+        edited_media.data_key_id = content.blob.data_key_id.clone();
+        edited_media.data_length = content.blob.data_length;
+        edited_media.data_blocks = content.blob.data_blocks.clone();
+        edited_media.metadata = content.metadata.clone();
+        // Cached copies of the previous revision are told apart by this date rather than the upload date
+        edited_media.modified_date = Some(replaced_date);
+        edited_media.revision += 1;
+    };
+
+    // The previous content is taken within the same compare & swap, so concurrent replacements each keep what they replaced
+    let previous_media = match media_database.fetch_and_update(&media.id, |media_vec| {
+        let mut edited_media: Media = serde_json::from_str(&String::from_utf8_lossy(media_vec?)).ok()?;
+        replace(&mut edited_media);
+
+        Some(IVec::from(match serde_json::to_vec(&edited_media) {
+            Ok(result) => result,
+            Err(_) => return None
+        }))
+    }) {
+        Ok(Some(media_vec)) => serde_json::from_str::<Media>(&String::from_utf8_lossy(&media_vec)).ok(),
+        _ => None
+    };
+
+    let previous_media = match previous_media {
+        Some(result) => result,
+        None => {
+            release_blob(database, &data_key, released);
+            return Err(internal_error())
+        }
+    };
+
+    let revision = MediaRevision {
+        media_id: previous_media.id.clone(),
+        revision: previous_media.revision,
+        replaced_date,
+        media: previous_media
+    };
+
+    let mut replaced_media = revision.media.clone();
+    replace(&mut replaced_media);
+
+    // The media already points at its new content, so a revision that can't be kept is let go
+    let stored = match serde_json::to_vec(&revision) {
+        Ok(revision_vec) => revision_database.insert(revision_key(&revision.media_id, revision.revision), revision_vec).is_ok(),
+        Err(_) => false
+    };
+
+    if !stored {
+        error!("Failed to keep revision {} of media {}", revision.revision, revision.media_id);
+        release_blob(database, &storage_key(&revision.media), released);
+    }
+
+    prune_revisions(config, database, &media.id, released);
+
+    if database.flush().is_err() {
+        return Err(internal_error())
+    }
+
+    Ok(replaced_media)
+}
+
+/// Key a revision is stored under, zero padded so a media's revisions are listed in order
+pub fn revision_key(media_id: &str, revision: u32) -> String {
+    format!("{}/{:010}", media_id, revision)
+}
+
+/// Previous revisions of the media, oldest first
+pub fn media_revisions(revision_database: &Tree, media_id: &str) -> Vec<MediaRevision> {
+    revision_database.scan_prefix(format!("{}/", media_id))
+        .filter_map(|item| item.ok())
+        .filter_map(|item| serde_json::from_str::<MediaRevision>(&String::from_utf8_lossy(&item.1)).ok())
+        .collect()
+}
+
+pub fn find_revision(revision_database: &Tree, media_id: &str, revision: u32) -> Option<MediaRevision> {
+    match revision_database.get(revision_key(media_id, revision)) {
+        Ok(Some(revision_vec)) => serde_json::from_str(&String::from_utf8_lossy(&revision_vec)).ok(),
+        _ => None
+    }
+}
+
+/// Removes a revision & releases its blob into `released`, returning whether it still existed
+pub fn remove_revision(database: &Db, revision: &MediaRevision, released: &mut Vec<String>) -> sled::Result<bool> {
+    let revision_database = database.open_tree("revision")?;
+
+    if revision_database.remove(revision_key(&revision.media_id, revision.revision))?.is_none() {
+        return Ok(false)
+    }
+
+    release_blob(database, &storage_key(&revision.media), released);
+    Ok(true)
+}
+
+/// Removes the media's oldest revisions past the instance's limit
+fn prune_revisions(config: &Config, database: &Db, media_id: &str, released: &mut Vec<String>) {
+    if config.media_max_revisions <= 0 {
+        return
+    }
+
+    let revision_database = match database.open_tree("revision") {
+        Ok(result) => result,
+        Err(err) => {
+            error!("Failed to open database tree (revision), {}", err);
+            return
+        }
+    };
+
+    let revisions = media_revisions(&revision_database, media_id);
+    let pruned = revisions.len().saturating_sub(config.media_max_revisions as usize);
+
+    for revision in &revisions[..pruned] {
+        if let Err(err) = remove_revision(database, revision, released) {
+            error!("Failed to prune revision {} of media {}, {}", revision.revision, media_id, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn download_signatures_verify() {
+        let signature = sign_download("secret", "HilrvkpJ", 1_900_000_000, None);
+        assert!(verify_download_signature("secret", "HilrvkpJ", 1_900_000_000, None, &signature));
+
+        let signature = sign_download("secret", "HilrvkpJ", 1_900_000_000, Some("127.0.0.1"));
+        assert!(verify_download_signature("secret", "HilrvkpJ", 1_900_000_000, Some("127.0.0.1"), &signature));
+    }
+
+    #[test]
+    fn tampered_download_signatures_fail() {
+        let signature = sign_download("secret", "HilrvkpJ", 1_900_000_000, Some("127.0.0.1"));
+
+        // Pushing the expiry back, reaching other media or dropping the address binding
+        assert!(!verify_download_signature("secret", "HilrvkpJ", 1_900_000_001, Some("127.0.0.1"), &signature));
+        assert!(!verify_download_signature("secret", "Q0u4ceTl", 1_900_000_000, Some("127.0.0.1"), &signature));
+        assert!(!verify_download_signature("secret", "HilrvkpJ", 1_900_000_000, None, &signature));
+        assert!(!verify_download_signature("secret", "HilrvkpJ", 1_900_000_000, Some("127.0.0.2"), &signature));
+        // A rotated secret revokes the link
+        assert!(!verify_download_signature("rotated", "HilrvkpJ", 1_900_000_000, Some("127.0.0.1"), &signature));
+
+        let mut tampered = signature.clone().into_bytes();
+        tampered[0] = if tampered[0] == b'A' { b'B' } else { b'A' };
+        assert!(!verify_download_signature("secret", "HilrvkpJ", 1_900_000_000, Some("127.0.0.1"), &String::from_utf8(tampered).unwrap()));
+        assert!(!verify_download_signature("secret", "HilrvkpJ", 1_900_000_000, Some("127.0.0.1"), &signature[..signature.len() - 2]));
+        assert!(!verify_download_signature("secret", "HilrvkpJ", 1_900_000_000, Some("127.0.0.1"), "not base64!"));
+    }
+
+    fn hotlinked_media(hotlink_hosts: Option<Vec<&str>>) -> Media {
+        Media {
+            id: String::from("HilrvkpJ"),
+            name: String::from("image"),
+            extension: String::from("png"),
+            data_type: ContentType::Image,
+            mime_type: None,
+            data_hash: None,
+            data_size: 0,
+            data_path: PathBuf::new(),
+            data_compressed: false,
+            data_codec: Codec::default(),
+            data_nonce: None,
+            data_key_id: None,
+            data_length: None,
+            data_blocks: None,
+            upload_date: Utc::now(),
+            author_username: String::from("Etho"),
+            unlisted: false,
+            tags: None,
+            downloads: 0,
+            metadata: None,
+            language: None,
+            expiration_date: None,
+            max_downloads: None,
+            password: None,
+            private: false,
+            hotlink_hosts: hotlink_hosts.map(|hosts| hosts.into_iter().map(String::from).collect()),
+            revision: 0,
+            modified_date: None
+        }
+    }
+
+    #[test]
+    fn url_host_strips_everything_but_the_host() {
+        assert_eq!(url_host("https://User@Example.com:8080/page?q=1#top").as_deref(), Some("example.com"));
+        assert_eq!(url_host("http://[::1]:8000/").as_deref(), Some("::1"));
+        assert_eq!(url_host("example.com.").as_deref(), Some("example.com"));
+        assert_eq!(url_host("https://"), None);
+    }
+
+    #[test]
+    fn wildcard_hosts_match_subdomains() {
+        assert!(host_allowed("example.com", "*.example.com"));
+        assert!(host_allowed("cdn.example.com", "*.example.com"));
+        assert!(host_allowed("a.b.example.com", "*.Example.com"));
+        // Lookalikes ending in the same letters aren't subdomains
+        assert!(!host_allowed("badexample.com", "*.example.com"));
+        assert!(!host_allowed("example.com.evil.net", "*.example.com"));
+        // Entries without a wildcard only match their own host
+        assert!(host_allowed("example.com", "https://example.com/"));
+        assert!(!host_allowed("cdn.example.com", "example.com"));
+    }
+
+    #[test]
+    fn hotlinks_follow_the_allowlist() {
+        let config = Config {
+            media_hotlink_protection: true,
+            media_hotlink_allowed_hosts: vec![String::from("*.example.com")],
+            ..Config::default()
+        };
+        let media = hotlinked_media(None);
+
+        assert!(hotlink_allowed(&config, &media, Some("https://cdn.example.com/page")));
+        assert!(!hotlink_allowed(&config, &media, Some("https://badexample.com/page")));
+        // Sandboxed pages send a "null" origin
+        assert!(!hotlink_allowed(&config, &media, Some("null")));
+        assert!(hotlink_allowed(&config, &media, None));
+
+        // The media's own hosts stand in for the instance's
+        let media = hotlinked_media(Some(vec!["blog.example.net"]));
+        assert!(hotlink_allowed(&config, &media, Some("https://blog.example.net/post")));
+        assert!(!hotlink_allowed(&config, &media, Some("https://cdn.example.com/page")));
+    }
+}
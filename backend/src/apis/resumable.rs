@@ -0,0 +1,671 @@
+#[allow(non_snake_case)]
+pub mod Resumable {
+    use std::{sync::{Arc, Mutex}, fs::{self, File, OpenOptions}, io::{self, Seek, SeekFrom}, path::Path};
+
+    use crate::{Config, Error};
+    use crate::apis::media::Media::Media;
+    use crate::apis::media_utils::{self, UploadDetails};
+    use crate::database::database::UploadSession;
+    use crate::storage::{backend::Storage, encryption::Keyring};
+    use crate::database::database_utils::{DatabaseExtension, DatabaseTreeExtension};
+
+    use chrono::{DateTime, Duration, Utc};
+    use log::error;
+    use rocket::{
+        head, patch, post, delete,
+        http::Status,
+        request::{self, FromRequest, Request},
+        response::{self, Responder, Response, status},
+        serde::json::Json,
+        data::ByteUnit,
+        Data, FromForm, State
+    };
+    use serde::{Deserialize, Serialize};
+    use sled::{Db, IVec, Tree};
+    use utoipa::{IntoParams, ToSchema};
+
+    use rand::distributions::{Alphanumeric, DistString};
+    use rand_core::OsRng;
+
+    /// Version of the tus protocol the resumable endpoints are modelled after
+    const TUS_RESUMABLE: &str = "1.0.0";
+
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct CreateUpload {
+        #[schema(example = "Funny cat video")]
+        /// Upload's file name
+        name: String,
+        /// Hide's upload from being listed in /all/ endpoint
+        unlisted: Option<bool>,
+        /// Only lets the author, admins & users granted access view the upload
+        private: Option<bool>,
+        /// Tags relating to the upload
+        tags: Option<Vec<String>>,
+        /// Total size of the file in bytes
+        #[schema(example = "582000")]
+        length: u64,
+        /// Seconds after the upload is finished at which it deletes itself
+        expires_in: Option<u64>,
+        /// Date at which the upload deletes itself in RFC 3339 format, can't be combined with expires_in
+        #[schema(example = "2030-01-01T00:00:00Z")]
+        expires_at: Option<String>,
+        /// Downloads after which the upload is purged, it can then only be downloaded rather than previewed
+        max_downloads: Option<u32>,
+        /// Password the upload is locked with, which has to be given to access it
+        password: Option<String>,
+        /// User's api key
+        api_key: String
+    }
+
+    #[derive(Serialize, Deserialize, ToSchema, Clone)]
+    pub struct UploadInfo {
+        /// Id pointing to the upload session
+        #[schema(example = "Q0u4ceTlFvNvjbyNyO0zBkGa")]
+        id: String,
+        /// Amount of bytes the server has received
+        offset: u64,
+        /// Total size of the file in bytes
+        length: u64,
+        /// When the session expires if no further data is received in UTC Format
+        #[schema(value_type = String)]
+        expiration_date: DateTime::<Utc>
+    }
+
+    #[derive(Serialize, Deserialize, FromForm, IntoParams, Clone)]
+    pub struct UploadKey {
+        /// User's api key
+        api_key: String
+    }
+
+    /// Value of the `Upload-Offset` request header
+    pub struct UploadOffset(u64);
+
+    #[rocket::async_trait]
+    impl<'r> FromRequest<'r> for UploadOffset {
+        type Error = Error;
+
+        async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+            match request.headers().get_one("Upload-Offset").and_then(|offset| offset.parse::<u64>().ok()) {
+                Some(offset) => request::Outcome::Success(UploadOffset(offset)),
+                None => request::Outcome::Failure((Status::BadRequest, Error {
+                    error: String::from("Missing or malformed Upload-Offset header")
+                }))
+            }
+        }
+    }
+
+    /// Reports an upload session's progress through tus headers,
+    /// carrying the finished media once the final chunk has been received
+    pub struct UploadProgress {
+        offset: u64,
+        length: u64,
+        media: Option<Media>
+    }
+
+    impl<'r> Responder<'r, 'static> for UploadProgress {
+        fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+            let mut response = match self.media {
+                Some(media) => Json(media).respond_to(request)?,
+                None => Response::build().status(Status::NoContent).finalize()
+            };
+
+            response.set_raw_header("Tus-Resumable", TUS_RESUMABLE);
+            response.set_raw_header("Upload-Offset", self.offset.to_string());
+            response.set_raw_header("Upload-Length", self.length.to_string());
+            response.set_raw_header("Cache-Control", "no-store");
+
+            Ok(response)
+        }
+    }
+
+    fn expiration_date(config: &Config, session: &UploadSession) -> DateTime::<Utc> {
+        session.last_activity + Duration::hours(config.media_resumable_expiration as i64)
+    }
+
+    fn is_expired(config: &Config, session: &UploadSession) -> bool {
+        config.media_resumable_expiration > 0 && expiration_date(config, session) < Utc::now()
+    }
+
+    fn remove_session(upload_database: &Tree, session: &UploadSession) {
+        if session.data_path.exists() && fs::remove_file(&session.data_path).is_err() {
+            error!("Failed to remove partial upload {:?}", session.data_path);
+        }
+
+        if upload_database.remove(&session.id).is_err() {
+            error!("Failed to remove upload session {}", session.id);
+        }
+    }
+
+    /// Looks up an upload session making sure it belongs to the api key's owner
+    fn find_session(
+        config: &Config,
+        database: &Db,
+        id: &str,
+        api_key: &str
+    ) -> Result<UploadSession, status::Custom<Json<Error>>> {
+        let user_database = match database.open_tree("user") {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+        let upload_database = match database.open_tree("upload") {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        let user = match media_utils::find_user_by_api_key(&user_database, api_key) {
+            Some(result) => result,
+            None => return Err(status::Custom(Status::Unauthorized, Json(Error {
+                error: String::from("Invalid or wrong credentials provided")
+            })))
+        };
+
+        let session: UploadSession = match upload_database.get(id) {
+            Ok(Some(result)) => match serde_json::from_str(&String::from_utf8_lossy(&result)) {
+                Ok(result) => result,
+                Err(_) => return Err(media_utils::internal_error())
+            },
+            Ok(None) => return Err(status::Custom(Status::NotFound, Json(Error {
+                error: String::from("Couldn't find upload associated with id")
+            }))),
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        if session.author_username != user.username {
+            return Err(status::Custom(Status::Unauthorized, Json(Error {
+                error: String::from("Upload does not belong to associated api key!")
+            })))
+        }
+
+        if is_expired(config, &session) {
+            remove_session(&upload_database, &session);
+
+            return Err(status::Custom(Status::Gone, Json(Error {
+                error: String::from("Upload has expired")
+            })))
+        }
+
+        Ok(session)
+    }
+
+    /// Removes every upload session that hasn't received data within
+    /// the configured expiration along with its partial file
+    pub fn expire_sessions(config: &Config, database: &Db) {
+        let upload_database = match database.open_tree("upload") {
+            Ok(result) => result,
+            Err(err) => {
+                error!("Failed to open database tree (upload), {}", err);
+                return
+            }
+        };
+
+        let expired: Vec<UploadSession> = upload_database.iter()
+            .filter_map(|item| item.ok())
+            .filter_map(|item| {
+                let result: UploadSession = match serde_json::from_str(&String::from_utf8_lossy(&item.1)) {
+                    Ok(result) => result,
+                    Err(_) => return None
+                };
+                Some(result)
+            })
+            .filter(|session| is_expired(config, session))
+            .collect();
+
+        for session in expired {
+            remove_session(&upload_database, &session);
+        }
+
+        if upload_database.flush().is_err() {
+            error!("Failed to flush database tree (upload)");
+        }
+    }
+
+    /// Releases every session left reserved by a chunk that was cut short,
+    /// only safe while no chunks are being received
+    pub fn release_sessions(database: &Db) {
+        let upload_database = match database.open_tree("upload") {
+            Ok(result) => result,
+            Err(err) => {
+                error!("Failed to open database tree (upload), {}", err);
+                return
+            }
+        };
+
+        let reserved: Vec<UploadSession> = upload_database.iter()
+            .filter_map(|item| item.ok())
+            .filter_map(|item| serde_json::from_str::<UploadSession>(&String::from_utf8_lossy(&item.1)).ok())
+            .filter(|session| session.appending)
+            .collect();
+
+        for mut session in reserved {
+            session.appending = false;
+
+            if serde_json::to_vec(&session).map(|session_vec| upload_database.insert(&session.id, session_vec)).is_err() {
+                error!("Failed to release upload session {}", session.id);
+            }
+        }
+
+        if upload_database.flush().is_err() {
+            error!("Failed to flush database tree (upload)");
+        }
+    }
+
+    /// Marks a session as receiving a chunk through a compare and swap,
+    /// so only one chunk is ever appended to it at once
+    fn reserve_session(upload_database: &Tree, session: &UploadSession) -> Result<(IVec, UploadSession), status::Custom<Json<Error>>> {
+        let busy = || status::Custom(Status::Conflict, Json(Error {
+            error: String::from("Another chunk of the upload is still being received")
+        }));
+
+        if session.appending {
+            return Err(busy())
+        }
+
+        let current = match upload_database.get(&session.id) {
+            Ok(Some(result)) => result,
+            _ => return Err(media_utils::internal_error())
+        };
+
+        let mut reserved = session.clone();
+        reserved.appending = true;
+        reserved.last_activity = Utc::now();
+
+        let reserved_vec = match serde_json::to_vec(&reserved) {
+            Ok(result) => IVec::from(result),
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        match upload_database.compare_and_swap(&session.id, Some(current), Some(reserved_vec.clone())) {
+            Ok(Ok(_)) => Ok((reserved_vec, reserved)),
+            Ok(Err(_)) => Err(busy()),
+            Err(_) => Err(media_utils::internal_error())
+        }
+    }
+
+    /// Swaps a reserved session for its new state, or removes it when there's none,
+    /// failing when it has been cancelled or expired in the meantime
+    fn commit_session(upload_database: &Tree, id: &str, reserved_vec: &IVec, session: Option<&UploadSession>) -> Result<Option<IVec>, status::Custom<Json<Error>>> {
+        let session_vec = match session.map(serde_json::to_vec).transpose() {
+            Ok(result) => result.map(IVec::from),
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        match upload_database.compare_and_swap(id, Some(reserved_vec), session_vec.clone()) {
+            Ok(Ok(_)) => {},
+            Ok(Err(_)) => return Err(status::Custom(Status::NotFound, Json(Error {
+                error: String::from("Couldn't find upload associated with id")
+            }))),
+            Err(_) => return Err(media_utils::internal_error())
+        }
+
+        if upload_database.flush().is_err() {
+            return Err(media_utils::internal_error())
+        }
+
+        Ok(session_vec)
+    }
+
+    /// Writes `source` into `destination` at `offset`,
+    /// dropping anything a previously interrupted chunk left past it
+    fn append_file(source: &Path, destination: &Path, offset: u64) -> io::Result<u64> {
+        let mut source_file = File::open(source)?;
+        let mut destination_file = OpenOptions::new().write(true).open(destination)?;
+
+        destination_file.set_len(offset)?;
+        destination_file.seek(SeekFrom::Start(offset))?;
+
+        io::copy(&mut source_file, &mut destination_file)
+    }
+
+    /// Streams a chunk into `chunk_path` then appends it to the upload's data,
+    /// returning the amount of bytes received
+    async fn receive_chunk(session: &UploadSession, chunk_path: &Path, data: Data<'_>) -> Result<u64, status::Custom<Json<Error>>> {
+        let remaining = session.upload_length - session.upload_offset;
+        let written = match data.open(ByteUnit::Byte(remaining)).into_file(chunk_path).await {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        if !written.is_complete() {
+            return Err(status::Custom(Status::BadRequest, Json(Error {
+                error: format!("Chunk exceeds the upload's remaining {} bytes", remaining)
+            })))
+        }
+
+        let (chunk_path, data_path, offset) = (chunk_path.to_path_buf(), session.data_path.clone(), session.upload_offset);
+
+        media_utils::run_blocking(move || match append_file(&chunk_path, &data_path, offset) {
+            Ok(result) => Ok(result),
+            Err(_) => Err(media_utils::internal_error())
+        }).await
+    }
+
+    /// Creates a resumable upload session
+    ///
+    /// The file's contents are then sent in one or more chunks
+    /// through the returned session's PATCH endpoint
+    #[utoipa::path(
+        post,
+        context_path = "/api/media",
+        request_body = CreateUpload,
+        responses(
+            (status = 201, description = "Successfully created upload session", body = UploadInfo),
+            (status = 400, description = "Server received malformed client request", body = Error),
+            (status = 401, description = "An authentication issue has occurred", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        )
+    )]
+    #[post("/resumable", data = "<body>")]
+    pub async fn create(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        body: Json<CreateUpload>
+    ) -> Result<status::Created<Json<UploadInfo>>, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+        let user_database = &database.get_tree("user")?;
+        let media_database = &database.get_tree("media")?;
+        let revision_database = &database.get_tree("revision")?;
+        let upload_database = &database.get_tree("upload")?;
+
+        let user = match media_utils::find_user_by_api_key(user_database, &body.api_key) {
+            Some(result) => result,
+            None => return Err(status::Custom(Status::Unauthorized, Json(Error {
+                error: String::from("Invalid or wrong credentials provided")
+            })))
+        };
+
+        let config = match config_store.lock() {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        media_utils::check_upload_allowed(&config, &user, &body.name)?;
+        media_utils::check_upload_size(&config, &user, media_database, revision_database, body.length)?;
+        // Checked up front so the upload isn't refused only once it's finished
+        media_utils::expiration_date(&config, &user, body.expires_in, body.expires_at.as_deref(), Utc::now())?;
+        media_utils::check_max_downloads(body.max_downloads)?;
+        let password_hash = media_utils::hash_media_password(&config, body.password.as_deref())?;
+
+        let id = Alphanumeric.sample_string(&mut OsRng, 24);
+        let data_path = media_utils::staging_directory(&config)?.join(&id);
+
+        if File::create(&data_path).is_err() {
+            return Err(media_utils::internal_error())
+        }
+
+        let session = UploadSession {
+            id: id.clone(),
+            author_username: user.username,
+            name: body.name.clone(),
+            unlisted: body.unlisted,
+            private: body.private,
+            tags: body.tags.clone(),
+            upload_length: body.length,
+            upload_offset: 0,
+            data_path,
+            creation_date: Utc::now(),
+            last_activity: Utc::now(),
+            expires_in: body.expires_in,
+            expires_at: body.expires_at.clone(),
+            max_downloads: body.max_downloads,
+            password: password_hash,
+            appending: false
+        };
+
+        let session_vec = match serde_json::to_vec(&session) {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        if upload_database.insert(&session.id, session_vec).is_err() || upload_database.flush().is_err() {
+            let _ = fs::remove_file(&session.data_path);
+            return Err(media_utils::internal_error())
+        }
+
+        let info = UploadInfo {
+            id: id.clone(),
+            offset: session.upload_offset,
+            length: session.upload_length,
+            expiration_date: expiration_date(&config, &session)
+        };
+
+        Ok(status::Created::new(format!("/api/media/resumable/{}", id)).body(Json(info)))
+    }
+
+    /// Returns the current offset of a resumable upload
+    /// through the `Upload-Offset` header
+    #[utoipa::path(
+        head,
+        context_path = "/api/media",
+        responses(
+            (status = 204, description = "Successfully grabbed upload offset"),
+            (status = 401, description = "An authentication issue has occurred", body = Error),
+            (status = 404, description = "Upload session does not exist", body = Error),
+            (status = 410, description = "Upload session has expired", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            ("id" = String, Path, description = "Id pointing to the upload session"),
+            UploadKey
+        )
+    )]
+    #[head("/resumable/<id>?<key..>")]
+    pub async fn offset(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        id: &str,
+        key: UploadKey
+    ) -> Result<UploadProgress, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+
+        let config = match config_store.lock() {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        let session = find_session(&config, &database, id, &key.api_key)?;
+
+        Ok(UploadProgress {
+            offset: session.upload_offset,
+            length: session.upload_length,
+            media: None
+        })
+    }
+
+    /// Appends a chunk to a resumable upload
+    ///
+    /// The `Upload-Offset` header must match the upload's current offset.
+    /// Once the final chunk is received the upload is turned into media
+    /// and its id is returned, should that fail the final offset can be
+    /// sent again with an empty chunk to retry
+    #[utoipa::path(
+        patch,
+        context_path = "/api/media",
+        request_body(content = Vec<u8>, content_type = "application/offset+octet-stream"),
+        responses(
+            (status = 200, description = "Successfully received the final chunk and uploaded media", body = Media),
+            (status = 204, description = "Successfully received chunk"),
+            (status = 400, description = "Server received malformed client request", body = Error),
+            (status = 401, description = "An authentication issue has occurred", body = Error),
+            (status = 404, description = "Upload session does not exist", body = Error),
+            (status = 409, description = "Upload-Offset does not match the upload's offset", body = Error),
+            (status = 410, description = "Upload session has expired", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            ("id" = String, Path, description = "Id pointing to the upload session"),
+            ("Upload-Offset" = u64, Header, description = "Offset the chunk starts at"),
+            UploadKey
+        )
+    )]
+    #[patch("/resumable/<id>?<key..>", format = "application/offset+octet-stream", data = "<data>")]
+    #[allow(clippy::too_many_arguments)]
+    pub async fn append(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
+        keyring_store: &State<Arc<Keyring>>,
+        id: &str,
+        key: UploadKey,
+        upload_offset: UploadOffset,
+        data: Data<'_>
+    ) -> Result<UploadProgress, status::Custom<Json<Error>>> {
+        // The session is reserved so neither lock is held while the chunk is received
+        let (config, session, reserved_vec, chunk_path) = {
+            let database = database_store.get_database()?;
+            let upload_database = &database.get_tree("upload")?;
+
+            let config = match config_store.lock() {
+                Ok(result) => result,
+                Err(_) => return Err(media_utils::internal_error())
+            };
+
+            let session = find_session(&config, &database, id, &key.api_key)?;
+
+            if session.upload_offset != upload_offset.0 {
+                return Err(status::Custom(Status::Conflict, Json(Error {
+                    error: format!("Upload-Offset does not match the upload's offset of {}", session.upload_offset)
+                })))
+            }
+
+            let chunk_path = media_utils::staging_path(&config)?;
+            let (reserved_vec, session) = reserve_session(upload_database, &session)?;
+
+            (config.clone(), session, reserved_vec, chunk_path)
+        };
+
+        let appended = receive_chunk(&session, &chunk_path, data).await;
+        let _ = fs::remove_file(&chunk_path);
+
+        let mut updated = session.clone();
+        updated.last_activity = Utc::now();
+
+        if let Ok(appended) = &appended {
+            updated.upload_offset += appended;
+        }
+
+        let complete = appended.is_ok() && updated.upload_offset == updated.upload_length;
+        // A finished upload stays reserved while it's stored, so it can't be stored twice
+        updated.appending = complete;
+
+        let (user, reserved_vec) = {
+            let database = database_store.get_database()?;
+            let upload_database = &database.get_tree("upload")?;
+
+            let reserved_vec = commit_session(upload_database, id, &reserved_vec, Some(&updated))?;
+            appended?;
+
+            if !complete {
+                return Ok(UploadProgress {
+                    offset: updated.upload_offset,
+                    length: updated.upload_length,
+                    media: None
+                })
+            }
+
+            let user = database.open_tree("user").ok()
+                .and_then(|user_database| media_utils::find_user_by_api_key(&user_database, &key.api_key));
+
+            (user, reserved_vec.unwrap_or_default())
+        };
+
+        let details = UploadDetails {
+            name: updated.name.clone(),
+            unlisted: updated.unlisted,
+            private: updated.private,
+            tags: updated.tags.clone(),
+            text: false,
+            language: None,
+            expires_in: updated.expires_in,
+            expires_at: updated.expires_at.clone(),
+            max_downloads: updated.max_downloads,
+            password_hash: updated.password.clone()
+        };
+
+        // Stored from a copy, as storing consumes it & the received data has to outlive a failure to be retried
+        let stored = async {
+            let user = user.ok_or_else(media_utils::internal_error)?;
+            let staged_path = media_utils::staging_path(&config)?;
+            let (data_path, copy_path) = (updated.data_path.clone(), staged_path.clone());
+
+            media_utils::run_blocking(move || match fs::copy(&data_path, &copy_path) {
+                Ok(_) => Ok(()),
+                Err(_) => {
+                    let _ = fs::remove_file(&copy_path);
+                    Err(media_utils::internal_error())
+                }
+            }).await?;
+
+            media_utils::store_upload(config, database_store.inner().clone(), storage_store.inner().clone(), keyring_store.inner().clone(), user, details, staged_path).await
+        }.await;
+
+        let database = database_store.get_database()?;
+        let upload_database = &database.get_tree("upload")?;
+
+        let media = match stored {
+            Ok(media) => media,
+            Err(err) => {
+                updated.appending = false;
+
+                if commit_session(upload_database, id, &reserved_vec, Some(&updated)).is_err() {
+                    error!("Failed to release upload session {} after failing to store it", id);
+                }
+
+                return Err(err)
+            }
+        };
+
+        // Only let go of once it's stored, the media exists regardless of whether this succeeds
+        match commit_session(upload_database, id, &reserved_vec, None) {
+            Ok(_) => if fs::remove_file(&updated.data_path).is_err() {
+                error!("Failed to remove finished upload {:?}", updated.data_path);
+            },
+            Err(_) => error!("Failed to remove upload session {} after storing it", id)
+        }
+
+        Ok(UploadProgress {
+            offset: updated.upload_offset,
+            length: updated.upload_length,
+            media: Some(Media::from(media))
+        })
+    }
+
+    /// Cancels a resumable upload discarding any received data
+    #[utoipa::path(
+        delete,
+        context_path = "/api/media",
+        responses(
+            (status = 200, description = "Successfully cancelled upload"),
+            (status = 401, description = "An authentication issue has occurred", body = Error),
+            (status = 404, description = "Upload session does not exist", body = Error),
+            (status = 500, description = "An internal error on the server's end has occurred", body = Error)
+        ),
+        params(
+            ("id" = String, Path, description = "Id pointing to the upload session"),
+            UploadKey
+        )
+    )]
+    #[delete("/resumable/<id>?<key..>")]
+    pub async fn cancel(
+        config_store: &State<Arc<Mutex<Config>>>,
+        database_store: &State<Arc<Mutex<sled::Db>>>,
+        id: &str,
+        key: UploadKey
+    ) -> Result<Status, status::Custom<Json<Error>>> {
+        let database = database_store.get_database()?;
+        let upload_database = &database.get_tree("upload")?;
+
+        let config = match config_store.lock() {
+            Ok(result) => result,
+            Err(_) => return Err(media_utils::internal_error())
+        };
+
+        let session = find_session(&config, &database, id, &key.api_key)?;
+        remove_session(upload_database, &session);
+
+        if upload_database.flush().is_err() {
+            return Err(media_utils::internal_error())
+        }
+
+        Ok(Status::Ok)
+    }
+}
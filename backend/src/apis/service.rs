@@ -1,8 +1,8 @@
 #[allow(non_snake_case)]
 pub mod Service {
-    use std::{sync::{Arc, Mutex}};
+    use std::{collections::BTreeMap, sync::{Arc, Mutex}};
 
-    use crate::{Config, Error, config as cfg};
+    use crate::{Config, Error, config::{self as cfg, TypeLimit}};
     use rocket::{
         get,
         State,
@@ -19,10 +19,42 @@ pub mod Service {
         // Media related
         pub media_allow_editing: bool,
         pub media_max_name_length: i32,
+        // Widths & heights images can be transformed to
+        pub media_transform_sizes: Vec<u32>,
+        // Whether image metadata such as GPS coordinates is stripped from uploads
+        pub media_strip_metadata: bool,
+        // Most lines a text preview can return
+        pub media_text_preview_lines: u32,
+        // Kilobytes of text that can be highlighted
+        pub media_text_highlight_size: u32,
+        // Most entries an archive listing returns
+        pub media_archive_max_entries: u32,
+        // Megabytes of the largest archive entry that can be extracted
+        pub media_archive_max_size: u32,
+        // Mime types always downloaded as attachments
+        pub media_attachment_mime_types: Vec<String>,
+        // Mime types, mime wildcards or content types that can be uploaded (Anything can be if empty)
+        pub media_allowed_types: Vec<String>,
+        // Mime types, mime wildcards or content types that can't be uploaded
+        pub media_denied_types: Vec<String>,
+        // Minutes an unlock token for password protected media stays valid
+        pub media_unlock_expiration: i32,
+        // Only lets media be downloaded by pages on allowed hosts, requests without a Referer or Origin are always let through
+        pub media_hotlink_protection: bool,
+        // Hosts pages downloading media may be on, "*.example.com" also allows subdomains (Uses the hosts of backend_domains if empty)
+        pub media_hotlink_allowed_hosts: Vec<String>,
+        // Previous revisions kept of replaced media, the oldest are pruned past it (Keep all if value = 0)
+        pub media_max_revisions: i32,
         
         // Service related
         pub backend_domains: Vec<String>,
 
+        // Album related
+        pub album_max_name_length: i32,
+        pub album_max_description_length: i32,
+        // Most media within an album (Ignore if admin, or if value = 0)
+        pub album_max_media: i32,
+
         // Media tags
         pub tags_default: Vec<String>,
         pub tags_allow_custom: bool,
@@ -39,9 +71,15 @@ pub mod Service {
         pub user_upload_size_limit: i32, 
         // 120 mb total per account (Ignore if admin. or if value = 0)
         pub user_total_upload_size_limit: i32, 
+        // Limits for uploads of a type, the most specific key applies (Ignore if admin)
+        pub user_type_limits: BTreeMap<String, TypeLimit>,
 
         pub user_username_limit: i32,
-        pub user_password_limit: i32
+        pub user_password_limit: i32,
+        // Most hours until an upload deletes itself, uploads without an expiration get this one (Ignore if admin, or if value = 0)
+        pub user_max_expiration: i32,
+        // Most hours a signed download link stays valid (Ignore if admin, or if value = 0)
+        pub user_max_signed_url_expiration: i32
 
         // TODO: Ignore certain settings if user is an admin
         // DONE Ignore user_upload_limit individual uploads
@@ -11,6 +11,7 @@ pub mod Stats {
     use serde::{Serialize, Deserialize};
     use utoipa::{IntoParams, ToSchema};
 
+    use crate::apis::media::Media::ContentType;
     use crate::database::database::Media;
     use crate::database::database_utils::{DatabaseExtension, DatabaseTreeExtension};
 
@@ -19,6 +20,17 @@ pub mod Stats {
         /// Total uploads on the instance
         media_count: i32,
         /// Total byte size of all uploads
+        media_storage_usage: i32,
+        /// Uploads & their byte size per content type
+        content_types: Vec<ContentTypeStats>
+    }
+
+    #[derive(Serialize, Deserialize, IntoParams, ToSchema, Clone)]
+    pub struct ContentTypeStats {
+        content_type: ContentType,
+        /// Uploads of this content type
+        media_count: i32,
+        /// Byte size of uploads of this content type
         media_storage_usage: i32
     }
 
@@ -46,7 +58,7 @@ pub mod Stats {
         let database = database_store.get_database()?;
         let media_database = &database.get_tree("media")?;
 
-        let media_count: Vec<i32> = media_database.iter()
+        let medias: Vec<(ContentType, i32)> = media_database.iter()
             .filter_map(|item| item.ok())
             .filter_map(|item| {
                 let result: Media = match serde_json::from_str(&String::from_utf8_lossy(&item.1)) {
@@ -55,12 +67,31 @@ pub mod Stats {
                 };
                 Some(result)
             })
-            .map(|media| media.data_size)
+            .map(|media| (media.data_type, media.data_size))
             .collect();
-        
+
+        let content_types = [
+            ContentType::Video, ContentType::Image, ContentType::Audio, ContentType::Text,
+            ContentType::Document, ContentType::Archive, ContentType::Font, ContentType::Other
+        ].into_iter()
+            .map(|content_type| {
+                let sizes: Vec<i32> = medias.iter()
+                    .filter(|media| media.0 == content_type)
+                    .map(|media| media.1)
+                    .collect();
+
+                ContentTypeStats {
+                    content_type,
+                    media_count: sizes.len() as i32,
+                    media_storage_usage: sizes.iter().sum()
+                }
+            })
+            .collect();
+
         Ok(Json(MediaStats {
-            media_count: media_count.len() as i32,
-            media_storage_usage: media_count.iter().sum()
+            media_count: medias.len() as i32,
+            media_storage_usage: medias.iter().map(|media| media.1).sum(),
+            content_types
         }))
     }
 
@@ -4,6 +4,8 @@ pub mod User {
     use std::sync::{Arc, Mutex};
 
     use crate::{Config, database::{database::{User, Invite, Media}}, Error};
+    use crate::apis::{album_utils, media_utils};
+    use crate::storage::backend::Storage;
     
     use rocket::{
         http::Status,
@@ -413,81 +415,98 @@ pub mod User {
     pub async fn delete(
         _config_store: &State<Arc<Mutex<Config>>>,
         database_store: &State<Arc<Mutex<sled::Db>>>,
+        storage_store: &State<Arc<dyn Storage>>,
         credentials: Json<UserCredentials>
     ) -> Result<Status, status::Custom<Json<Error>>> {
-        let database = database_store.get_database()?;
-        let user_database = &database.get_tree("user")?;
+        // Removed media's stored blobs are only deleted once the database is unlocked
+        let mut released = Vec::new();
 
-        let user_vec = match user_database.get(&credentials.username) {
-            Ok(result) => {
-                match result {
-                    Some(result) => result,
-                    None => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("An internal error on the server's end has occurred")
-                    })))
-                }
-            },
-            Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                error: String::from("Couldn't find user associated with username")
-            })))
-        };
+        let result = {
+            let database = database_store.get_database()?;
+            let user_database = &database.get_tree("user")?;
 
-        let user: User = match serde_json::from_str(&String::from_utf8_lossy(&user_vec)) {
-            Ok(result) => result,
-            Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                error: String::from("An internal error on the server's end has occurred")
-            })))
-        };
+            let user_vec = match user_database.get(&credentials.username) {
+                Ok(result) => {
+                    match result {
+                        Some(result) => result,
+                        None => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                            error: String::from("An internal error on the server's end has occurred")
+                        })))
+                    }
+                },
+                Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                    error: String::from("Couldn't find user associated with username")
+                })))
+            };
 
-        let password_hash = match PasswordHash::new(&user.password) {
-            Ok(result) => result,
-            Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                error: String::from("An internal error on the server's end has occurred")
-            })))
-        };
+            let user: User = match serde_json::from_str(&String::from_utf8_lossy(&user_vec)) {
+                Ok(result) => result,
+                Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                    error: String::from("An internal error on the server's end has occurred")
+                })))
+            };
 
-        return match Pbkdf2.verify_password(credentials.password.as_bytes(), &password_hash) {
-            Ok(_) => {
-                let media_database = match database.open_tree("media") {
-                    Ok(result) => result,
-                    Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("An internal error on the server's end has occurred")
-                    })))
-                };
+            let password_hash = match PasswordHash::new(&user.password) {
+                Ok(result) => result,
+                Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                    error: String::from("An internal error on the server's end has occurred")
+                })))
+            };
 
-                let medias: Vec<String> = media_database
-                    .iter()
-                    .filter_map(|item| item.ok())
-                    .filter_map(|item| {
-                        let result: Media = match serde_json::from_str(&String::from_utf8_lossy(&item.1)) {
+            match Pbkdf2.verify_password(credentials.password.as_bytes(), &password_hash) {
+                Ok(_) => {
+                    let media_database = match database.open_tree("media") {
                         Ok(result) => result,
-                            Err(_) => return None
-                        };
-                        Some(result)
-                    })
-                    .filter(|media| media.author_username == credentials.username)
-                    .map(|media| media.id)
-                    .collect::<Vec<_>>();
-                
-                for media_id in medias {
-                    if media_database.remove(media_id).is_err() {
-                        continue;
+                        Err(_) => return Err(status::Custom(Status::InternalServerError, Json(Error {
+                            error: String::from("An internal error on the server's end has occurred")
+                        })))
+                    };
+
+                    let medias: Vec<Media> = media_database
+                        .iter()
+                        .filter_map(|item| item.ok())
+                        .filter_map(|item| {
+                            let result: Media = match serde_json::from_str(&String::from_utf8_lossy(&item.1)) {
+                            Ok(result) => result,
+                                Err(_) => return None
+                            };
+                            Some(result)
+                        })
+                        .filter(|media| media.author_username == credentials.username)
+                        .collect::<Vec<_>>();
+                    
+                    for media in medias {
+                        let _ = media_utils::remove_media(&database, &media, &mut released);
                     }
-                }
 
-                match user_database.remove(credentials.username.clone()) {
-                    Ok(_) => {
-                        Ok(Status::Ok)
-                    },
-                    Err(_) => Err(status::Custom(Status::InternalServerError, Json(Error {
-                        error: String::from("Failed to remove user account from database")
-                    })))
-                }
-            },
-            Err(_) => Err(status::Custom(Status::Forbidden, Json(Error {
-                error: String::from("Invalid or incorrect credentials provided")
-            })))
+                    match user_database.remove(credentials.username.clone()) {
+                        Ok(_) => {
+                            if media_utils::move_user_grants(&database, &credentials.username, None).is_err() {
+                                Err(status::Custom(Status::InternalServerError, Json(Error {
+                                    error: String::from("Failed to remove user account's grants from database")
+                                })))
+                            } else if album_utils::move_user_albums(&database, &credentials.username, None).is_err() {
+                                Err(status::Custom(Status::InternalServerError, Json(Error {
+                                    error: String::from("Failed to remove user account's albums from database")
+                                })))
+                            } else {
+                                Ok(Status::Ok)
+                            }
+                        },
+                        Err(_) => Err(status::Custom(Status::InternalServerError, Json(Error {
+                            error: String::from("Failed to remove user account from database")
+                        })))
+                    }
+                },
+                Err(_) => Err(status::Custom(Status::Forbidden, Json(Error {
+                    error: String::from("Invalid or incorrect credentials provided")
+                })))
+            }
         };
+
+        media_utils::delete_released_blocking(storage_store.inner().clone(), released).await;
+
+        result
     }
 
     /// Change or update a user's associated username
@@ -591,7 +610,8 @@ pub mod User {
 
                         match user_database.insert(body.newname.clone(), user_insert_vec) {
                             Ok(_) => {
-                                if user_database.flush().is_err() {
+                                if user_database.flush().is_err() || media_utils::move_user_grants(&database, &body.user_credentials.username, Some(&body.newname)).is_err()
+                                    || album_utils::move_user_albums(&database, &body.user_credentials.username, Some(&body.newname)).is_err() {
                                     return Err(status::Custom(Status::InternalServerError, Json(Error {
                                         error: String::from("Failed to update backend database")
                                     })))
@@ -1,21 +1,83 @@
 use serde::{Deserialize, Serialize};
-use std::{io::BufReader, path::Path, fs::{File, self}};
+use std::{collections::BTreeMap, io::BufReader, path::Path, fs::{File, self}};
+use utoipa::ToSchema;
+
+use crate::storage::{blob::Codec, backend::StorageBackend};
 
 #[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Config {
     // Media related
     pub media_allow_editing: bool,
     pub media_max_name_length: i32,
     pub media_dynamic_id_length: i32,
+    // Hours an unfinished resumable upload is kept since it last received data
+    pub media_resumable_expiration: i32,
+    // Widths & heights images can be transformed to through /api/media/download/transform
+    pub media_transform_sizes: Vec<u32>,
+    // Strip EXIF, XMP & IPTC metadata (e.g. GPS coordinates) from JPEG, PNG & WebP uploads, only the orientation is kept
+    pub media_strip_metadata: bool,
+    // Most lines a text preview through /api/media/text/preview can return
+    pub media_text_preview_lines: u32,
+    // Kilobytes of text that can be highlighted through /api/media/text/highlight
+    pub media_text_highlight_size: u32,
+    // Most entries listed through /api/media/archive/list
+    pub media_archive_max_entries: u32,
+    // Megabytes of the largest archive entry that can be extracted, as well as the most a tar.gz is decompressed while reading it
+    pub media_archive_max_size: u32,
+    // Mime types always downloaded as attachments whatever disposition is asked for, since browsers run scripts within them
+    pub media_attachment_mime_types: Vec<String>,
+    // Mime types (e.g. image/png), mime wildcards (e.g. image/*) or content types (e.g. Video) that can be uploaded (Anything can be if empty)
+    pub media_allowed_types: Vec<String>,
+    // Refused even when allowed, written the same way as media_allowed_types
+    pub media_denied_types: Vec<String>,
+    // Minutes an unlock token for password protected media stays valid
+    pub media_unlock_expiration: i32,
+    // Wrong passwords allowed per media & address before unlocking is refused for a while (0 to never refuse)
+    pub media_unlock_attempts: i32,
+    // Minutes unlocking stays refused once media_unlock_attempts wrong passwords were given
+    pub media_unlock_lockout: i32,
+    // Only lets media be downloaded by pages on allowed hosts, requests without a Referer or Origin are always let through
+    pub media_hotlink_protection: bool,
+    // Hosts pages downloading media may be on, "*.example.com" also allows subdomains (Uses the hosts of backend_domains if empty)
+    pub media_hotlink_allowed_hosts: Vec<String>,
+    // Image sent along with the 403 for blocked downloads (Leave unset to answer with an error)
+    pub media_hotlink_placeholder: Option<String>,
+    // Previous revisions kept of replaced media, the oldest are pruned past it (Keep all if value = 0)
+    pub media_max_revisions: i32,
     
     // Service related
     pub backend_store_compressed: bool,
+    // Codec used when storing compressed, either zlib, zstd or brotli
+    pub backend_compression_codec: Codec,
     pub backend_domains: Vec<String>,
     pub backend_media_directory: Option<String>,
+    // Hex encoded key file media is encrypted with, generated when missing (Leave unset to store unencrypted)
+    pub backend_encryption_key_path: Option<String>,
+    // Where media is stored, either "local" (backend_media_directory) or "s3"
+    pub backend_storage: StorageBackend,
+    // S3 compatible object store, endpoint including its scheme (e.g. http://localhost:9000)
+    pub backend_s3_endpoint: Option<String>,
+    pub backend_s3_bucket: Option<String>,
+    pub backend_s3_region: String,
+    pub backend_s3_access_key: Option<String>,
+    pub backend_s3_secret_key: Option<String>,
+    // Address the bucket as endpoint/bucket rather than bucket.endpoint (e.g. MinIO)
+    pub backend_s3_path_style: bool,
     pub backend_analytics_key: Option<String>,
+    // Secret download links are signed with, changing it revokes every link handed out (Leave unset to disable signed links)
+    pub backend_signing_secret: Option<String>,
+    // Take the client's address from the X-Real-IP header, only enable behind a proxy which always sets it
+    pub backend_trust_proxy_headers: bool,
     pub backend_tls_cert_path: Option<String>,
     pub backend_tls_key_path: Option<String>,
 
+    // Album related
+    pub album_max_name_length: i32,
+    pub album_max_description_length: i32,
+    // Most media within an album (Ignore if admin, or if value = 0)
+    pub album_max_media: i32,
+
     // Media tags
     pub tags_default: Vec<String>,
     pub tags_allow_custom: bool,
@@ -32,27 +94,76 @@ pub struct Config {
     pub user_upload_size_limit: i32, 
     // 120 mb total per account (Ignore if admin. or if value = 0)
     pub user_total_upload_size_limit: i32, 
+    // Limits for uploads of a type, keyed the same way as media_allowed_types with the most specific key applying (Ignore if admin)
+    // e.g. { "Video": { "max_size": 200, "max_count": 0 }, "image/*": { "max_size": 15, "max_count": 30 } }
+    pub user_type_limits: BTreeMap<String, TypeLimit>,
 
     pub user_username_limit: i32,
     pub user_password_limit: i32,
+    // Most hours until an upload deletes itself, uploads without an expiration get this one (Ignore if admin, or if value = 0)
+    pub user_max_expiration: i32,
+    // Most hours a signed download link stays valid (Ignore if admin, or if value = 0)
+    pub user_max_signed_url_expiration: i32,
     pub user_first_admin: bool
 
     // TODO: Stats -> MediaStats, Allow user to specify whether to show unlisted upload count
 }
 
+/// Limits applying to uploads of a single type
+#[derive(Serialize, Deserialize, ToSchema, Clone, Default)]
+#[serde(default)]
+pub struct TypeLimit {
+    // Megabytes per upload, replaces user_upload_size_limit (Falls back on it if value = 0)
+    pub max_size: i32,
+    // Uploads of the type per account (Ignore if value = 0)
+    pub max_count: i32
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
             media_allow_editing: true,
             media_max_name_length: 32, 
             media_dynamic_id_length: 4, // Maybe go to 6
+            media_resumable_expiration: 24,
+            media_transform_sizes: vec![32, 64, 128, 256, 320, 480, 640, 800, 1024, 1280, 1920],
+            media_strip_metadata: true,
+            media_text_preview_lines: 200,
+            media_text_highlight_size: 1024,
+            media_archive_max_entries: 10000,
+            media_archive_max_size: 256,
+            media_attachment_mime_types: vec![String::from("text/html"), String::from("application/xhtml+xml"), String::from("image/svg+xml"),
+                String::from("text/xml"), String::from("application/xml"), String::from("text/javascript"), String::from("application/javascript")],
+            media_allowed_types: Vec::new(),
+            media_denied_types: Vec::new(),
+            media_unlock_expiration: 60,
+            media_unlock_attempts: 5,
+            media_unlock_lockout: 15,
+            media_hotlink_protection: false,
+            media_hotlink_allowed_hosts: Vec::new(),
+            media_hotlink_placeholder: None,
+            media_max_revisions: 5,
             
             backend_store_compressed: true,
+            backend_compression_codec: Codec::Zlib,
             backend_domains: Vec::new(),
             backend_media_directory: None,
+            backend_encryption_key_path: None,
+            backend_storage: StorageBackend::Local,
+            backend_s3_endpoint: None,
+            backend_s3_bucket: None,
+            backend_s3_region: String::from("us-east-1"),
+            backend_s3_access_key: None,
+            backend_s3_secret_key: None,
+            backend_s3_path_style: true,
             backend_analytics_key: None,
+            backend_signing_secret: None,
+            backend_trust_proxy_headers: false,
             backend_tls_cert_path: None,
             backend_tls_key_path: None,
+            album_max_name_length: 32,
+            album_max_description_length: 512,
+            album_max_media: 500,
             // TODO: Replace this with a "good" default list of tags
             tags_default: vec![String::from("funny"), String::from("meme"), String::from("nsfw"), String::from("clip")],
             tags_allow_custom: false,
@@ -62,8 +173,11 @@ impl Default for Config {
             user_upload_limit: 60,
             user_upload_size_limit: 12,
             user_total_upload_size_limit: 120,
+            user_type_limits: BTreeMap::new(),
             user_username_limit: 24,
             user_password_limit: 128,
+            user_max_expiration: 0,
+            user_max_signed_url_expiration: 168,
             user_first_admin: true
         }
     }
@@ -4,7 +4,8 @@ use std::path::{PathBuf};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::apis::media::Media::ContentType;
+use crate::apis::media::Media::{ContentMetadata, ContentType};
+use crate::storage::blob::Codec;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Media {
@@ -13,14 +14,80 @@ pub struct Media {
     pub name: String,
     pub extension: String,
     pub data_type: ContentType,
+    // Mime type detected when uploaded
+    #[serde(default)]
+    pub mime_type: Option<String>,
+    // Hex encoded SHA-256 of the uncompressed content
+    #[serde(default)]
+    pub data_hash: Option<String>,
     pub data_size: i32,
     pub data_path: PathBuf,
     pub data_compressed: bool,
+    // Codec of compressed blocks, uploads predating codecs are zlib
+    #[serde(default)]
+    pub data_codec: Codec,
+    // Hex encoded nonce & id of the key the blob was encrypted with, unset when stored unencrypted
+    #[serde(default)]
+    pub data_nonce: Option<String>,
+    #[serde(default)]
+    pub data_key_id: Option<String>,
+    // Uncompressed length of the content, unknown for legacy compressed uploads
+    #[serde(default)]
+    pub data_length: Option<u64>,
+    // Compressed offset of each block when stored in the seekable layout
+    #[serde(default)]
+    pub data_blocks: Option<Vec<u64>>,
     pub upload_date: DateTime::<Utc>,
     pub author_username: String,
     pub unlisted: bool,
     pub tags: Option<Vec<String>>,
-    pub downloads: i64
+    pub downloads: i64,
+    // Details read from the content when uploaded, unset for uploads predating them
+    #[serde(default)]
+    pub metadata: Option<ContentMetadata>,
+    // Language text uploads are highlighted as
+    #[serde(default)]
+    pub language: Option<String>,
+    // When the media deletes itself, kept for good when unset
+    #[serde(default)]
+    pub expiration_date: Option<DateTime::<Utc>>,
+    // Downloads after which the media is purged, unlimited when unset
+    #[serde(default)]
+    pub max_downloads: Option<u32>,
+    // PBKDF2 hash of the password the media is locked with, anyone can access it when unset
+    #[serde(default)]
+    pub password: Option<String>,
+    // Only viewable by its author, admins & users it's been granted to
+    #[serde(default)]
+    pub private: bool,
+    // Hosts pages downloading the media may be on, in place of the instance's
+    #[serde(default)]
+    pub hotlink_hosts: Option<Vec<String>>,
+    // Revision of the current content, counted up each time the content is replaced
+    #[serde(default)]
+    pub revision: u32,
+    // When the content was last replaced, unset while it's still the uploaded content
+    #[serde(default)]
+    pub modified_date: Option<DateTime::<Utc>>
+}
+
+// Stored content shared by every media record uploading the same data
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Blob {
+    // Main key, the blob's storage key
+    pub data_path: PathBuf,
+    pub data_hash: String,
+    pub data_size: i32,
+    pub data_compressed: bool,
+    pub data_codec: Codec,
+    #[serde(default)]
+    pub data_nonce: Option<String>,
+    #[serde(default)]
+    pub data_key_id: Option<String>,
+    pub data_length: Option<u64>,
+    pub data_blocks: Option<Vec<u64>>,
+    // Amount of media records referencing the blob
+    pub references: u64
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -36,6 +103,54 @@ pub struct User {
     pub invite_key: Option<String>
 }
 
+// Grants access to password protected media for a short while without sending the password along
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MediaUnlock {
+    // Main key
+    pub token: String,
+    pub media_id: String,
+    pub expiration_date: DateTime::<Utc>
+}
+
+// Ordered group of media, such as the uploads of an event
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Album {
+    // Main key
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    // In the order they're shown, media that's deleted is taken out
+    pub media_ids: Vec<String>,
+    // One of media_ids shown for the album, unset when it's deleted
+    pub cover_id: Option<String>,
+    pub author_username: String,
+    pub creation_date: DateTime::<Utc>,
+    pub unlisted: bool,
+    // Only viewable by its author & admins
+    pub private: bool
+}
+
+// Lets a user view private media
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MediaGrant {
+    // Main key, as media_id/username
+    pub media_id: String,
+    pub username: String,
+    pub grant_date: DateTime::<Utc>,
+    pub granter_username: String
+}
+
+// Content a media held before it was replaced, its blob stays referenced until the revision is removed
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MediaRevision {
+    // Main key, as media_id/revision (zero padded so revisions are kept in order)
+    pub media_id: String,
+    pub revision: u32,
+    pub replaced_date: DateTime::<Utc>,
+    // Media as it was while the revision was current, only its content is served
+    pub media: Media
+}
+
 // TODO: Single use or multi use?
 // TODO: Expiration date?
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -48,4 +163,36 @@ pub struct Invite {
     pub creation_date: DateTime::<Utc>,
     pub creator_username: String,
     pub used: bool
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UploadSession {
+    // Main key
+    pub id: String,
+    pub author_username: String,
+    pub name: String,
+    pub unlisted: Option<bool>,
+    pub tags: Option<Vec<String>>,
+    // Total size the finished upload will have
+    pub upload_length: u64,
+    // Amount of bytes received so far
+    pub upload_offset: u64,
+    pub data_path: PathBuf,
+    pub creation_date: DateTime::<Utc>,
+    pub last_activity: DateTime::<Utc>,
+    // Expiration asked for, applied once the upload is finished
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+    #[serde(default)]
+    pub expires_at: Option<String>,
+    #[serde(default)]
+    pub max_downloads: Option<u32>,
+    // Hashed as soon as the session is created
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub private: Option<bool>,
+    // Set while a chunk is being received, so chunks sent alongside it are refused
+    #[serde(default)]
+    pub appending: bool
 }
\ No newline at end of file
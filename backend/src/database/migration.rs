@@ -0,0 +1,185 @@
+// One-off upgrades of stored records, run when the backend starts.
+//
+// Finished migrations are recorded within the "migration" tree so they only ever run once,
+// a migration that fails part way is run again on the next start.
+use std::{collections::BTreeMap, fs::{self, File}, io::{self, Read}, path::{Path, PathBuf}, sync::Arc};
+
+use log::{info, warn};
+use sled::{Db, IVec, Tree};
+
+use crate::Config;
+use crate::apis::{media::Media::ContentType, media_utils};
+use crate::database::database::{Blob, Media};
+use crate::storage::{backend::Storage, encryption::Keyring, variant};
+
+/// Upgrades records, returning whether it got through all of them
+type Migration = fn(&Config, &Db, &Arc<dyn Storage>, &Keyring) -> Result<bool, String>;
+
+/// Runs every migration that hasn't finished yet
+pub fn migrate(config: &Config, database: &Db, storage: &Arc<dyn Storage>, keyring: &Keyring) -> Result<(), String> {
+    let migration_database = match database.open_tree("migration") {
+        Ok(result) => result,
+        Err(_) => return Err(String::from("Couldn't open the migration database"))
+    };
+
+    let migrations: [(&str, Migration); 1] = [
+        ("content_types", migrate_content_types)
+    ];
+
+    for (name, migration) in migrations {
+        if migration_database.contains_key(name).unwrap_or(false) {
+            continue
+        }
+
+        if !migration(config, database, storage, keyring)? {
+            warn!("[Migration] {} didn't finish, it will be run again on the next start", name);
+            continue
+        }
+
+        let finished = serde_json::to_vec(&chrono::offset::Utc::now()).unwrap_or_default();
+        if migration_database.insert(name, finished).is_err() || migration_database.flush().is_err() {
+            return Err(format!("Failed to record migration {}", name))
+        }
+
+        info!("[Migration] {} finished", name);
+    }
+
+    Ok(())
+}
+
+/// Sorts media stored as `Other` into the content types added since, moving their blobs into the matching directory.
+///
+/// Returns whether every media could be sniffed
+fn migrate_content_types(config: &Config, database: &Db, storage: &Arc<dyn Storage>, keyring: &Keyring) -> Result<bool, String> {
+    let (media_database, blob_database, variant_database) = match (database.open_tree("media"), database.open_tree("blob"), database.open_tree("variant")) {
+        (Ok(media_database), Ok(blob_database), Ok(variant_database)) => (media_database, blob_database, variant_database),
+        _ => return Err(String::from("Couldn't open the media database"))
+    };
+
+    // Deduplicated media share blobs, which only need to be sniffed & moved once
+    let mut blobs: BTreeMap<PathBuf, Vec<Media>> = BTreeMap::new();
+    for media in media_database.iter()
+        .filter_map(|item| item.ok())
+        .filter_map(|item| serde_json::from_str::<Media>(&String::from_utf8_lossy(&item.1)).ok())
+        .filter(|media| media.data_type == ContentType::Other) {
+        blobs.entry(media.data_path.clone()).or_default().push(media);
+    }
+
+    let staging_directory = match media_utils::staging_directory(config) {
+        Ok(result) => result,
+        Err(_) => return Err(String::from("Couldn't create the staging directory"))
+    };
+
+    let mut finished = true;
+
+    for (data_path, medias) in blobs {
+        let mut header = Vec::with_capacity(media_utils::INFER_HEADER_LENGTH);
+        let sniffed = media_utils::open_blob(storage, keyring, &medias[0])
+            .and_then(|reader| reader.take(media_utils::INFER_HEADER_LENGTH as u64).read_to_end(&mut header));
+
+        if let Err(error) = sniffed {
+            warn!("[Migration] Failed to read {:?}: {}", data_path, error);
+            finished = false;
+            continue
+        }
+
+        let data_type = media_utils::infer_type(&header);
+        let content_type = media_utils::content_type(&data_type);
+        if content_type == ContentType::Other {
+            continue
+        }
+
+        let data_key = data_path.to_string_lossy().to_string();
+
+        // Blobs from before deduplication aren't tracked & stay where they are
+        let moved_key = match blob_database.get(&data_key) {
+            Ok(Some(blob_vec)) => match serde_json::from_str::<Blob>(&String::from_utf8_lossy(&blob_vec)) {
+                Ok(blob) => Some(format!("{}/{}", media_utils::type_directory(&content_type), blob.data_hash)),
+                Err(_) => None
+            },
+            _ => None
+        };
+
+        // Only moved into a free spot, blobs are never merged
+        let moved_key = moved_key.filter(|key| !blob_database.contains_key(key).unwrap_or(true));
+
+        if let Some(key) = &moved_key {
+            let staged_path = staging_directory.join("migrate-content-types");
+            let copied = copy_blob(storage.as_ref(), &data_key, key, &staged_path);
+            let _ = fs::remove_file(&staged_path);
+
+            if let Err(error) = copied {
+                warn!("[Migration] Failed to move {:?} to {}: {}", data_path, key, error);
+                finished = false;
+                continue
+            }
+        }
+
+        let updated = update_records(&media_database, &blob_database, &medias, &data_key, moved_key.as_deref(), |media| {
+            media.data_type = content_type.clone();
+            media.mime_type = Some(data_type.mime_type().to_string());
+            media.extension = data_type.extension().to_string();
+        });
+
+        if updated.is_err() || database.flush().is_err() {
+            return Err(format!("Failed to update the media stored at {:?}", data_path))
+        }
+
+        if moved_key.is_some() {
+            if storage.delete(&data_key).is_err() {
+                warn!("[Migration] Failed to remove {:?} after moving it", data_path);
+            }
+
+            media_utils::delete_released(storage.as_ref(), &variant::remove_all(&variant_database, &data_key));
+        }
+
+        info!("[Migration] Moved {:?} to {:?} ({} media)", data_path, content_type, medias.len());
+    }
+
+    Ok(finished)
+}
+
+/// Copies a blob's stored bytes to another key, as they are
+fn copy_blob(storage: &dyn Storage, from: &str, to: &str, staged_path: &Path) -> io::Result<()> {
+    io::copy(&mut storage.stream(from, 0, None)?, &mut File::create(staged_path)?)?;
+    storage.put(to, staged_path)
+}
+
+fn update_records(
+    media_database: &Tree,
+    blob_database: &Tree,
+    medias: &[Media],
+    data_key: &str,
+    moved_key: Option<&str>,
+    update: impl Fn(&mut Media)
+) -> sled::Result<()> {
+    for media in medias {
+        media_database.update_and_fetch(&media.id, |media_vec| {
+            let mut edited_media: Media = serde_json::from_str(&String::from_utf8_lossy(media_vec?)).ok()?;
+            update(&mut edited_media);
+
+            if let Some(key) = moved_key {
+                edited_media.data_path = PathBuf::from(key);
+            }
+
+            Some(IVec::from(match serde_json::to_vec(&edited_media) {
+                Ok(result) => result,
+                Err(_) => return None
+            }))
+        })?;
+    }
+
+    if let Some(key) = moved_key {
+        if let Some(blob_vec) = blob_database.remove(data_key)? {
+            if let Ok(mut blob) = serde_json::from_str::<Blob>(&String::from_utf8_lossy(&blob_vec)) {
+                blob.data_path = PathBuf::from(key);
+
+                if let Ok(blob_vec) = serde_json::to_vec(&blob) {
+                    blob_database.insert(key, blob_vec)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,5 +1,5 @@
 // TODO: Implement backend wide runtime tests (https://doc.rust-lang.org/book/ch11-01-writing-tests.html)
-use std::sync::{Arc, Mutex};
+use std::{sync::{Arc, Mutex}, thread, time::Duration};
 
 use log::warn;
 use rocket::{
@@ -26,6 +26,10 @@ use rocket_analytics::{self, Analytics};
 // TODO: Remove this and use traditional mod.rs files where applicable
 pub mod apis {
     pub mod media;
+    pub mod media_utils;
+    pub mod resumable;
+    pub mod album;
+    pub mod album_utils;
     pub mod user;
     pub mod stats;
     pub mod service;
@@ -35,11 +39,39 @@ pub mod database {
     pub mod database;
 
     pub mod database_utils;
+    pub mod migration;
+}
+
+pub mod storage {
+    pub mod backend;
+    pub mod blob;
+
+    pub mod encryption;
+    pub mod variant;
+
+    pub mod local;
+    pub mod s3;
+}
+
+pub mod processing {
+    pub mod archive;
+    pub mod metadata;
+    pub mod sanitize;
+    pub mod strip;
+    pub mod text;
+    pub mod thumbnail;
+    pub mod transform;
+}
+
+pub mod fairings {
+    pub mod security;
 }
 
 pub mod config;
 
 use crate::apis::media::Media;
+use crate::apis::resumable::Resumable;
+use crate::apis::album::Album;
 use crate::apis::user::User;
 use crate::apis::stats::Stats;
 use crate::apis::service::Service;
@@ -49,12 +81,39 @@ use crate::config::Config;
 #[openapi(
     paths(
         Media::info,
+        Media::unlock,
+        Media::sign,
         Media::download,
+        Media::thumbnail,
+        Media::transform,
+        Media::text_raw,
+        Media::text_preview,
+        Media::text_highlight,
+        Media::archive_list,
+        Media::archive_extract,
         Media::search,
         Media::upload,
+        Media::upload_multipart,
+        Media::upload_raw,
+        Media::upload_text,
         Media::delete,
         Media::edit,
+        Media::grant,
+        Media::revoke,
+        Media::grants,
+        Media::replace,
+        Media::revisions,
+        Media::delete_revision,
         Media::tags,
+        Resumable::create,
+        Resumable::offset,
+        Resumable::append,
+        Resumable::cancel,
+        Album::info,
+        Album::search,
+        Album::create,
+        Album::edit,
+        Album::delete,
         User::register,
         User::login,
         User::delete,
@@ -70,15 +129,20 @@ use crate::config::Config;
         Service::info
     ),
     components(
-        schemas(Media::Media, Media::ContentType, Media::ContentInfo, Media::ContentFound, Media::ContentTags,
-            Media::SearchQuery, Media::UploadMedia, Media::DeleteMedia, Media::EditMedia),
-        schemas(Stats::MediaStats, Stats::UserStats),
+        schemas(Media::Media, Media::ContentType, Media::ContentInfo, Media::LockedContentInfo, Media::ContentDetails, Media::ContentMetadata, Media::ContentFound, Media::ContentTags, Media::TextPreview, Media::ArchiveEntry, Media::ArchiveListing,
+            Media::SearchQuery, Media::UploadMedia, Media::UploadMediaForm, Media::DeleteMedia, Media::EditMedia, Media::UnlockMedia, Media::UnlockToken,
+            Media::GrantMedia, Media::ListGrants, Media::ContentGrants, Media::SignMedia, Media::SignedUrl,
+            Media::ReplacedMedia, Media::ContentRevision, Media::ContentRevisions, Media::DeleteRevision),
+        schemas(Resumable::CreateUpload, Resumable::UploadInfo),
+        schemas(Album::Album, Album::AlbumInfo, Album::CreateAlbum, Album::EditAlbum, Album::DeleteAlbum, Album::AlbumQuery, Album::AlbumsFound),
+        schemas(Stats::MediaStats, Stats::ContentTypeStats, Stats::UserStats),
         schemas(User::InviteInfo, User::UserInvite, User::UserApiKey, User::UserList, User::UserInfo, User::UserCredentials, User::UserRegistration,
             User::UserUpdateUsername, User::UserUpdatePassword, User::InviteInfoRequest),
-        schemas(Error, Service::ApiConfig, Service::Information)
+        schemas(Error, Service::ApiConfig, Service::Information, config::TypeLimit)
     ),
     tags(
         (name = "Media", description = "All media management related api endpoints."),
+        (name = "Album", description = "All album management related api endpoints."),
         (name = "User", description = "All user management related api endpoints."),
         (name = "Stats", description = "All statistical management related api endpoints."),
         (name = "Service", description = "All service related api endpoints."),
@@ -108,13 +172,56 @@ async fn main() -> Result<(), rocket::Error> {
     };
     let database_arc = Arc::new(Mutex::new(database));
 
-    let config_arc = Arc::new(Mutex::new(config.unwrap()));
+    let config = config.unwrap();
+
+    let storage = match storage::backend::open_storage(&config) {
+        Ok(result) => result,
+        Err(error) => panic!("{error}")
+    };
+
+    let keyring = match storage::encryption::Keyring::load(&config) {
+        Ok(result) => Arc::new(result),
+        Err(error) => panic!("{error}")
+    };
+
+    // `backend rotate-key` re-encrypts all stored media with a new key instead of launching
+    if std::env::args().nth(1).as_deref() == Some("rotate-key") {
+        if let Err(error) = storage::encryption::rotate_key(&config, &database_arc.lock().unwrap(), storage) {
+            panic!("{error}")
+        }
+
+        return Ok(())
+    }
+
+    if let Err(error) = database::migration::migrate(&config, &database_arc.lock().unwrap(), &storage, &keyring) {
+        panic!("{error}")
+    }
+
+    // Nothing is being downloaded yet, so media whose last download was cut short can be purged
+    apis::media_utils::expire_media(&database_arc, storage.as_ref(), true);
+    // Nor are any chunks being received, so sessions left reserved by an interrupted one are released
+    Resumable::release_sessions(&database_arc.lock().unwrap());
+
+    let config_arc = Arc::new(Mutex::new(config));
+
+    spawn_cleanup(config_arc.clone(), database_arc.clone(), storage.clone());
 
     let doc = &mut ApiDoc::openapi();
     ApiDoc::modify(&ApiDoc, doc);
 
+    // Multipart uploads are authenticated after the form is read,
+    // so they are capped by the instance's largest per upload size limit
+    let upload_size_limit = apis::media_utils::largest_upload_size(&config_arc.lock().unwrap());
+    let file_limit = if upload_size_limit > 0 {
+        ByteUnit::Megabyte(upload_size_limit as u64)
+    } else {
+        ByteUnit::max_value()
+    };
+
     let limits = Limits::new()
-        .limit("json", ByteUnit::max_value());
+        .limit("json", ByteUnit::max_value())
+        .limit("file", file_limit)
+        .limit("data-form", file_limit + ByteUnit::Megabyte(1));
 
     let mut figment = rocket::Config::figment()
         .merge(("address", "0.0.0.0"))
@@ -144,6 +251,10 @@ async fn main() -> Result<(), rocket::Error> {
         .configure(figment)
         .manage(config_arc)
         .manage(database_arc)
+        .manage(storage)
+        .manage(keyring)
+        .manage(Arc::new(processing::text::Highlighter::load()))
+        .attach(fairings::security::SecurityHeaders)
         .mount(
             "/",
             SwaggerUi::new("/swagger/<_..>").url("/api-doc/openapi.json", doc.to_owned()),
@@ -152,12 +263,45 @@ async fn main() -> Result<(), rocket::Error> {
             "/api/media",
             routes![
                     Media::info,
+                    Media::unlock,
+                    Media::sign,
                     Media::download,
+                    Media::download_head,
+                    Media::thumbnail,
+                    Media::transform,
+                    Media::text_raw,
+                    Media::text_preview,
+                    Media::text_highlight,
+                    Media::archive_list,
+                    Media::archive_extract,
                     Media::search,
                     Media::upload,
+                    Media::upload_multipart,
+                    Media::upload_raw,
+                    Media::upload_text,
                     Media::delete,
                     Media::edit,
-                    Media::tags
+                    Media::grant,
+                    Media::revoke,
+                    Media::grants,
+                    Media::replace,
+                    Media::revisions,
+                    Media::delete_revision,
+                    Media::tags,
+                    Resumable::create,
+                    Resumable::offset,
+                    Resumable::append,
+                    Resumable::cancel
+                ]
+        )
+        .mount(
+            "/api/album",
+            routes![
+                    Album::info,
+                    Album::search,
+                    Album::create,
+                    Album::edit,
+                    Album::delete
                 ]
         )
         .mount(
@@ -198,6 +342,28 @@ async fn main() -> Result<(), rocket::Error> {
     Ok(())
 }
 
+/// Periodically removes stale data such as expired media & resumable uploads
+fn spawn_cleanup(config_arc: Arc<Mutex<Config>>, database_arc: Arc<Mutex<sled::Db>>, storage: Arc<dyn storage::backend::Storage>) {
+    thread::spawn(move || loop {
+        // Locks the database itself, so storage isn't waited on while holding it
+        apis::media_utils::expire_media(&database_arc, storage.as_ref(), false);
+
+        // Cloned so the config lock isn't held while waiting on the database
+        let config = config_arc.lock().map(|config| config.clone());
+
+        match (config, database_arc.lock()) {
+            (Ok(config), Ok(database)) => {
+                Resumable::expire_sessions(&config, &database);
+                apis::media_utils::expire_unlock_tokens(&database);
+            },
+            _ => warn!("[Cleanup] Failed to lock config or database!")
+        }
+
+        // Often enough for media to be gone about when it expires
+        thread::sleep(Duration::from_secs(60));
+    });
+}
+
 impl Modify for ApiDoc {
     fn modify(&self, openapi: &mut openapi::OpenApi) {
         openapi.info.title = String::from("Centix Backend");
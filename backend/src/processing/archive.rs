@@ -0,0 +1,412 @@
+// Listing & extraction of single entries from zip, tar & tar.gz uploads, read straight from the stored blob.
+//
+// Archives come from users so nothing they declare is trusted:
+// entries with paths escaping the archive (e.g. ../ or absolute paths) are left out,
+// extracted entries are cut off at their declared size, which has to be within the instance's limit,
+// and no more than that limit is decompressed while looking through a tar.gz.
+use std::io::{self, Read, Seek, Write};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use flate2::read::GzDecoder;
+use zip::{result::ZipError, ZipArchive};
+
+use crate::apis::media::Media::ArchiveEntry;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz
+}
+
+impl ArchiveFormat {
+    /// Format of an upload with the given mime type, gzip is assumed to hold a tar
+    pub fn from_mime_type(mime_type: &str) -> Option<ArchiveFormat> {
+        match mime_type {
+            "application/zip" => Some(ArchiveFormat::Zip),
+            "application/x-tar" => Some(ArchiveFormat::Tar),
+            "application/gzip" => Some(ArchiveFormat::TarGz),
+            _ => None
+        }
+    }
+}
+
+/// Guards against archives made to exhaust the server, such as zip bombs
+#[derive(Clone, Copy, Debug)]
+pub struct ArchiveLimits {
+    /// Most entries listed
+    pub max_entries: usize,
+    /// Largest entry that can be extracted, as well as the most a tar.gz is decompressed while reading it
+    pub max_size: u64
+}
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// Not an archive of the expected format, or one using unsupported features such as encryption
+    Malformed,
+    /// Reading it would go past the instance's limits
+    TooLarge,
+    Io(io::Error)
+}
+
+impl From<ZipError> for ArchiveError {
+    fn from(error: ZipError) -> Self {
+        match error {
+            ZipError::Io(error) => ArchiveError::Io(error),
+            _ => ArchiveError::Malformed
+        }
+    }
+}
+
+impl From<io::Error> for ArchiveError {
+    fn from(error: io::Error) -> Self {
+        // Tar only reports malformed archives through io errors
+        match error.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput | io::ErrorKind::UnexpectedEof | io::ErrorKind::Other => ArchiveError::Malformed,
+            _ => ArchiveError::Io(error)
+        }
+    }
+}
+
+/// Entries of an archive, in the order they're stored
+pub struct Listing {
+    pub entries: Vec<ArchiveEntry>,
+    /// Whether there were more entries than listed
+    pub truncated: bool,
+    /// Entries left out for having unsafe paths
+    pub skipped: u32
+}
+
+/// Path of an entry relative to the archive's root, `None` when it could point anywhere outside of it
+pub fn safe_path(name: &str) -> Option<String> {
+    // Archives made on Windows may separate with backslashes
+    let name = name.replace('\\', "/");
+
+    if name.starts_with('/') || name.contains('\0') {
+        return None
+    }
+
+    let mut components = Vec::new();
+    for component in name.split('/') {
+        match component {
+            "" | "." => continue,
+            ".." => return None,
+            // Drive letters such as C:
+            component if components.is_empty() && component.ends_with(':') => return None,
+            component => components.push(component)
+        }
+    }
+
+    if components.is_empty() {
+        return None
+    }
+
+    Some(components.join("/"))
+}
+
+/// Lists an archive's files & directories
+pub fn list<R: Read + Seek>(reader: R, format: ArchiveFormat, limits: ArchiveLimits) -> Result<Listing, ArchiveError> {
+    let mut listing = Listing {
+        entries: Vec::new(),
+        truncated: false,
+        skipped: 0
+    };
+
+    let mut add = |entry: Option<ArchiveEntry>| {
+        if listing.entries.len() >= limits.max_entries {
+            listing.truncated = true;
+            return false
+        }
+
+        match entry {
+            Some(entry) => listing.entries.push(entry),
+            None => listing.skipped += 1
+        }
+
+        true
+    };
+
+    match format {
+        ArchiveFormat::Zip => {
+            let mut archive = ZipArchive::new(reader)?;
+
+            for index in 0..archive.len() {
+                let file = archive.by_index_raw(index)?;
+                let entry = safe_path(file.name()).map(|path| ArchiveEntry {
+                    path,
+                    size: file.size(),
+                    modified: zip_date(file.last_modified()),
+                    directory: file.is_dir()
+                });
+
+                if !add(entry) {
+                    break
+                }
+            }
+        },
+        ArchiveFormat::Tar | ArchiveFormat::TarGz => {
+            walk_tar(reader, format, limits, |entry, _| Ok(add(entry)))?;
+        }
+    }
+
+    Ok(listing)
+}
+
+/// Finds a file within an archive by its path, failing when it's larger than the instance allows
+pub fn find<R: Read + Seek>(reader: R, format: ArchiveFormat, path: &str, limits: ArchiveLimits) -> Result<Option<ArchiveEntry>, ArchiveError> {
+    let mut found = None;
+    read_file(reader, format, path, limits, |entry, _| {
+        found = Some(entry);
+        Ok(())
+    })?;
+
+    match found {
+        Some(entry) if entry.size > limits.max_size => Err(ArchiveError::TooLarge),
+        found => Ok(found)
+    }
+}
+
+/// Writes out the contents of a file within an archive, up to its declared size.
+/// Returns whether the file was found
+pub fn extract<R: Read + Seek>(reader: R, format: ArchiveFormat, path: &str, limits: ArchiveLimits, writer: &mut dyn Write) -> Result<bool, ArchiveError> {
+    read_file(reader, format, path, limits, |entry, contents| {
+        if entry.size > limits.max_size {
+            return Err(ArchiveError::TooLarge)
+        }
+
+        io::copy(&mut contents.take(entry.size), writer)?;
+        Ok(())
+    })
+}
+
+/// Hands the regular file at `path` over to `read`, returning whether it was found
+fn read_file<R: Read + Seek>(
+    reader: R,
+    format: ArchiveFormat,
+    path: &str,
+    limits: ArchiveLimits,
+    mut read: impl FnMut(ArchiveEntry, &mut dyn Read) -> Result<(), ArchiveError>
+) -> Result<bool, ArchiveError> {
+    let path = match safe_path(path) {
+        Some(result) => result,
+        None => return Ok(false)
+    };
+
+    match format {
+        ArchiveFormat::Zip => {
+            let mut archive = ZipArchive::new(reader)?;
+
+            // Names are compared after being made safe, the same way they're listed
+            let name = archive.file_names()
+                .find(|name| safe_path(name).as_deref() == Some(path.as_str()))
+                .map(String::from);
+
+            let name = match name {
+                Some(result) => result,
+                None => return Ok(false)
+            };
+
+            let mut file = archive.by_name(&name)?;
+            if !file.is_file() {
+                return Ok(false)
+            }
+
+            let entry = ArchiveEntry {
+                path,
+                size: file.size(),
+                modified: zip_date(file.last_modified()),
+                directory: false
+            };
+
+            read(entry, &mut file)?;
+            Ok(true)
+        },
+        ArchiveFormat::Tar | ArchiveFormat::TarGz => {
+            let mut found = false;
+
+            walk_tar(reader, format, limits, |entry, contents| match (entry, contents) {
+                (Some(entry), Some(contents)) if entry.path == path => {
+                    read(entry, contents)?;
+                    found = true;
+                    Ok(false)
+                },
+                _ => Ok(true)
+            })?;
+
+            Ok(found)
+        }
+    }
+}
+
+/// Counts the bytes read through it, failing once more than `limit` have been read
+struct LimitedReader<R> {
+    inner: R,
+    limit: u64,
+    read: u64
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buffer)?;
+        self.read += read as u64;
+
+        if self.read > self.limit {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Archive decompresses past the limit"))
+        }
+
+        Ok(read)
+    }
+}
+
+/// Goes through the entries of a tar, handing each one to `visit` until it returns false.
+///
+/// Entries with unsafe paths are handed over as `None`, contents are only handed over for regular files
+fn walk_tar<R: Read>(
+    reader: R,
+    format: ArchiveFormat,
+    limits: ArchiveLimits,
+    mut visit: impl FnMut(Option<ArchiveEntry>, Option<&mut dyn Read>) -> Result<bool, ArchiveError>
+) -> Result<(), ArchiveError> {
+    let reader: Box<dyn Read> = match format {
+        ArchiveFormat::TarGz => Box::new(GzDecoder::new(reader)),
+        _ => Box::new(reader)
+    };
+
+    let mut archive = tar::Archive::new(LimitedReader {
+        inner: reader,
+        limit: limits.max_size,
+        read: 0
+    });
+
+    let walked = walk_tar_entries(&mut archive, &mut visit);
+
+    if archive.into_inner().read > limits.max_size {
+        return Err(ArchiveError::TooLarge)
+    }
+
+    walked
+}
+
+fn walk_tar_entries<R: Read>(
+    archive: &mut tar::Archive<R>,
+    visit: &mut impl FnMut(Option<ArchiveEntry>, Option<&mut dyn Read>) -> Result<bool, ArchiveError>
+) -> Result<(), ArchiveError> {
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+
+        let kind = entry.header().entry_type();
+        // Links, devices & metadata entries such as pax headers aren't listed
+        if !kind.is_file() && !kind.is_dir() {
+            continue
+        }
+
+        let listed = safe_path(&String::from_utf8_lossy(&entry.path_bytes())).map(|path| ArchiveEntry {
+            path,
+            size: if kind.is_dir() { 0 } else { entry.header().size().unwrap_or(0) },
+            modified: entry.header().mtime().ok()
+                .and_then(|mtime| i64::try_from(mtime).ok())
+                .and_then(|mtime| NaiveDateTime::from_timestamp_opt(mtime, 0)),
+            directory: kind.is_dir()
+        });
+
+        let more = match kind.is_file() {
+            true => visit(listed, Some(&mut entry)),
+            false => visit(listed, None)
+        }?;
+
+        if !more {
+            break
+        }
+    }
+
+    Ok(())
+}
+
+/// Zip dates are stored in local time without telling its time zone
+fn zip_date(date: zip::DateTime) -> Option<NaiveDateTime> {
+    NaiveDate::from_ymd_opt(date.year() as i32, date.month() as u32, date.day() as u32)?
+        .and_hms_opt(date.hour() as u32, date.minute() as u32, date.second() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    /// A tar holding a single file of `size` zeroes, which compresses down to next to nothing
+    fn zeroes_tar(path: &str, size: usize) -> Vec<u8> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(size as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+
+        let mut builder = tar::Builder::new(Vec::new());
+        builder.append_data(&mut header, path, io::repeat(0).take(size as u64)).unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn safe_path_refuses_parent_directories() {
+        assert_eq!(safe_path("../etc/passwd"), None);
+        assert_eq!(safe_path("docs/../../etc/passwd"), None);
+        assert_eq!(safe_path("docs\\..\\..\\secret"), None);
+        assert_eq!(safe_path("/etc/passwd"), None);
+        assert_eq!(safe_path("./"), None);
+    }
+
+    #[test]
+    fn safe_path_refuses_drive_letters() {
+        assert_eq!(safe_path("C:/Windows/win.ini"), None);
+        assert_eq!(safe_path("C:\\Windows\\win.ini"), None);
+        assert_eq!(safe_path("c:"), None);
+        // Only the first component could be a drive
+        assert_eq!(safe_path("notes/c:").as_deref(), Some("notes/c:"));
+    }
+
+    #[test]
+    fn safe_path_normalizes_separators() {
+        assert_eq!(safe_path("docs\\readme.txt").as_deref(), Some("docs/readme.txt"));
+        assert_eq!(safe_path("./docs//readme.txt").as_deref(), Some("docs/readme.txt"));
+    }
+
+    #[test]
+    fn tar_gz_stops_decompressing_at_the_limit() {
+        let archive = gzip(&zeroes_tar("bomb.bin", 4_000_000));
+        let limits = ArchiveLimits {
+            max_entries: 10,
+            max_size: 1_000_000
+        };
+        assert!(archive.len() < 100_000);
+
+        assert!(matches!(list(Cursor::new(&archive), ArchiveFormat::TarGz, limits), Err(ArchiveError::TooLarge)));
+        assert!(matches!(extract(Cursor::new(&archive), ArchiveFormat::TarGz, "bomb.bin", limits, &mut io::sink()), Err(ArchiveError::TooLarge)));
+    }
+
+    #[test]
+    fn entries_past_the_limit_are_refused() {
+        let archive = zeroes_tar("large.bin", 2_000);
+        let limits = ArchiveLimits {
+            max_entries: 10,
+            max_size: 1_000
+        };
+
+        assert!(matches!(find(Cursor::new(&archive), ArchiveFormat::Tar, "large.bin", limits), Err(ArchiveError::TooLarge)));
+
+        let limits = ArchiveLimits {
+            max_entries: 10,
+            max_size: 10_000
+        };
+        let mut contents = Vec::new();
+        assert!(extract(Cursor::new(&archive), ArchiveFormat::Tar, "large.bin", limits, &mut contents).unwrap());
+        assert_eq!(contents.len(), 2_000);
+    }
+}
@@ -0,0 +1,144 @@
+// Details read from uploads when they're stored, shown through ContentInfo & filterable through search.
+//
+// Only container headers are parsed, nothing is decoded.
+use std::{fs::File, io::BufReader, path::Path};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use exif::{In, Tag, Value};
+use lofty::{AudioFile, FileType, Probe, TaggedFileExt};
+use matroska::{Matroska, Settings, Tracktype};
+use mp4::{MediaType, TrackType};
+
+use crate::apis::media::Media::ContentMetadata;
+
+/// Reads an image's dimensions, orientation & capture date, leaving out whichever aren't available
+pub fn read_image(path: &Path) -> ContentMetadata {
+    let mut metadata = ContentMetadata::default();
+
+    if let Ok(reader) = image::io::Reader::open(path).and_then(|reader| reader.with_guessed_format()) {
+        if let Ok((width, height)) = reader.into_dimensions() {
+            metadata.width = Some(width);
+            metadata.height = Some(height);
+        }
+    }
+
+    let exif = match File::open(path) {
+        Ok(file) => match exif::Reader::new().read_from_container(&mut BufReader::new(file)) {
+            Ok(result) => result,
+            Err(_) => return metadata
+        },
+        Err(_) => return metadata
+    };
+
+    metadata.orientation = exif.get_field(Tag::Orientation, In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .and_then(|orientation| u16::try_from(orientation).ok())
+        .filter(|orientation| (1..=8).contains(orientation));
+
+    // The time the photo was taken, falling back on when the file was last changed
+    metadata.capture_date = [Tag::DateTimeOriginal, Tag::DateTime].iter()
+        .filter_map(|tag| exif.get_field(*tag, In::PRIMARY))
+        .find_map(|field| match &field.value {
+            Value::Ascii(values) => values.first().and_then(|value| exif_date(value)),
+            _ => None
+        });
+
+    metadata
+}
+
+/// Parses an EXIF date, which doesn't tell its time zone
+fn exif_date(value: &[u8]) -> Option<NaiveDateTime> {
+    let date = exif::DateTime::from_ascii(value).ok()?;
+
+    NaiveDate::from_ymd_opt(date.year as i32, date.month as u32, date.day as u32)?
+        .and_hms_opt(date.hour as u32, date.minute as u32, date.second as u32)
+}
+
+/// Reads a video's duration along with the resolution & codec of its first video track
+pub fn read_video(path: &Path, mime_type: &str) -> ContentMetadata {
+    let metadata = match mime_type {
+        "video/mp4" | "video/x-m4v" | "video/quicktime" => read_mp4(path),
+        "video/webm" | "video/x-matroska" => read_matroska(path),
+        _ => None
+    };
+
+    metadata.unwrap_or_default()
+}
+
+fn read_mp4(path: &Path) -> Option<ContentMetadata> {
+    let file = File::open(path).ok()?;
+    let size = file.metadata().ok()?.len();
+    let mp4 = mp4::Mp4Reader::read_header(BufReader::new(file), size).ok()?;
+
+    let mut metadata = ContentMetadata {
+        duration: Some(mp4.duration().as_secs_f64()),
+        ..Default::default()
+    };
+
+    if let Some(track) = mp4.tracks().values().find(|track| matches!(track.track_type(), Ok(TrackType::Video))) {
+        metadata.width = Some(track.width() as u32);
+        metadata.height = Some(track.height() as u32);
+        metadata.codec = match track.media_type() {
+            Ok(MediaType::H264) => Some(String::from("h264")),
+            Ok(MediaType::H265) => Some(String::from("h265")),
+            Ok(MediaType::VP9) => Some(String::from("vp9")),
+            _ => None
+        };
+    }
+
+    Some(metadata)
+}
+
+fn read_matroska(path: &Path) -> Option<ContentMetadata> {
+    let matroska = Matroska::open(File::open(path).ok()?).ok()?;
+
+    let mut metadata = ContentMetadata {
+        duration: matroska.info.duration.map(|duration| duration.as_secs_f64()),
+        ..Default::default()
+    };
+
+    if let Some(track) = matroska.tracks.iter().find(|track| track.tracktype == Tracktype::Video) {
+        if let Settings::Video(video) = &track.settings {
+            metadata.width = u32::try_from(video.pixel_width).ok();
+            metadata.height = u32::try_from(video.pixel_height).ok();
+        }
+
+        // Codec ids look like V_VP9 or V_MPEG4/ISO/AVC
+        metadata.codec = Some(match track.codec_id.as_str() {
+            "V_MPEG4/ISO/AVC" => String::from("h264"),
+            "V_MPEGH/ISO/HEVC" => String::from("h265"),
+            codec_id => codec_id.trim_start_matches("V_").to_lowercase()
+        });
+    }
+
+    Some(metadata)
+}
+
+/// Reads an audio file's duration, bitrate & codec
+pub fn read_audio(path: &Path) -> ContentMetadata {
+    // Staged uploads have no extension, the format is sniffed instead
+    let file = match Probe::open(path).and_then(|probe| Ok(probe.guess_file_type()?)).and_then(|probe| probe.read()) {
+        Ok(result) => result,
+        Err(_) => return ContentMetadata::default()
+    };
+
+    let properties = file.properties();
+
+    ContentMetadata {
+        duration: Some(properties.duration().as_secs_f64()),
+        bitrate: properties.audio_bitrate().or_else(|| properties.overall_bitrate()),
+        codec: match file.file_type() {
+            FileType::Aac | FileType::Mp4 => Some(String::from("aac")),
+            FileType::Aiff | FileType::Wav => Some(String::from("pcm")),
+            FileType::Ape => Some(String::from("ape")),
+            FileType::Flac => Some(String::from("flac")),
+            FileType::Mpeg => Some(String::from("mp3")),
+            FileType::Opus => Some(String::from("opus")),
+            FileType::Vorbis => Some(String::from("vorbis")),
+            FileType::Speex => Some(String::from("speex")),
+            FileType::WavPack => Some(String::from("wavpack")),
+            _ => None
+        },
+        ..Default::default()
+    }
+}
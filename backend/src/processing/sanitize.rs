@@ -0,0 +1,208 @@
+// Removes anything able to run scripts from SVG uploads before they're stored.
+//
+// Media is served from the same origin as the API, so a script within an SVG opened in the browser could act on behalf of whoever opened it.
+// Scripts, embedded documents & event handlers are dropped, as are links to javascript: urls & the doctype (whose entities could smuggle in markup),
+// everything else is written back as it was.
+use std::{fs, io, path::Path};
+
+use quick_xml::{
+    events::{attributes::Attribute, BytesStart, Event},
+    Reader, Writer
+};
+
+/// Elements removed along with everything within them
+const REMOVED_ELEMENTS: &[&str] = &["script", "foreignobject", "iframe", "frame", "object", "embed", "applet", "handler", "listener", "base", "meta", "link"];
+
+/// Animation elements, which can set attributes such as href after the document has loaded
+const ANIMATION_ELEMENTS: &[&str] = &["animate", "set", "animatemotion", "animatetransform"];
+
+/// Whether a sample from the start of a file is an SVG document, which `infer` recognizes as XML or not at all
+pub fn is_svg(sample: &[u8]) -> bool {
+    let sample = sample.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(sample);
+    // The sample may end part way through a character
+    let text = match std::str::from_utf8(sample) {
+        Ok(result) => result,
+        Err(error) => match std::str::from_utf8(&sample[..error.valid_up_to()]) {
+            Ok(result) => result,
+            Err(_) => return false
+        }
+    };
+
+    let mut reader = Reader::from_str(text);
+
+    // Errors past the declaration, comments & doctype leading up to the root element are most likely the sample being cut off
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element) | Event::Empty(element)) => return element.local_name().as_ref().eq_ignore_ascii_case(b"svg"),
+            Ok(Event::Decl(_) | Event::Comment(_) | Event::DocType(_) | Event::PI(_)) => continue,
+            Ok(Event::Text(text)) if text.iter().all(u8::is_ascii_whitespace) => continue,
+            _ => return false
+        }
+    }
+}
+
+/// Sanitizes an SVG file in place, other files are left untouched.
+///
+/// Returns whether anything was removed, fails with `ErrorKind::InvalidData` when the SVG can't be parsed
+pub fn sanitize_file(path: &Path, mime_type: &str) -> io::Result<bool> {
+    if mime_type != "image/svg+xml" {
+        return Ok(false)
+    }
+
+    let data = String::from_utf8(fs::read(path)?)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "SVG isn't valid UTF-8"))?;
+
+    let sanitized = match sanitize_svg(&data) {
+        Some(result) if result != data => result,
+        Some(_) => return Ok(false),
+        None => return Err(io::Error::new(io::ErrorKind::InvalidData, "SVG couldn't be parsed"))
+    };
+
+    let sanitized_path = path.with_extension("sanitized");
+    fs::write(&sanitized_path, sanitized)?;
+    fs::rename(&sanitized_path, path)?;
+
+    Ok(true)
+}
+
+/// Rewrites an SVG without anything able to run scripts, `None` when it isn't well-formed
+pub fn sanitize_svg(svg: &str) -> Option<String> {
+    let mut reader = Reader::from_str(svg);
+    let mut writer = Writer::new(Vec::with_capacity(svg.len()));
+    // Depth within a removed element, whose contents are all left out
+    let mut removed_depth = 0;
+
+    loop {
+        let event = reader.read_event().ok()?;
+
+        if removed_depth > 0 {
+            match event {
+                Event::Start(_) => removed_depth += 1,
+                Event::End(_) => removed_depth -= 1,
+                Event::Eof => return None,
+                _ => {}
+            }
+
+            continue
+        }
+
+        match event {
+            Event::Start(element) => match sanitize_element(&element) {
+                Some(element) => writer.write_event(Event::Start(element)).ok()?,
+                None => removed_depth = 1
+            },
+            Event::Empty(element) => {
+                if let Some(element) = sanitize_element(&element) {
+                    writer.write_event(Event::Empty(element)).ok()?;
+                }
+            },
+            // Processing instructions can pull in stylesheets (XSLT included) & the doctype can declare entities expanding into markup
+            Event::PI(_) | Event::DocType(_) => {},
+            Event::Eof => break,
+            event => writer.write_event(event).ok()?
+        }
+    }
+
+    String::from_utf8(writer.into_inner()).ok()
+}
+
+/// The element without its unsafe attributes, `None` when the element itself has to go
+fn sanitize_element(element: &BytesStart) -> Option<BytesStart<'static>> {
+    let name = String::from_utf8_lossy(element.local_name().as_ref()).to_lowercase();
+
+    if REMOVED_ELEMENTS.contains(&name.as_str()) {
+        return None
+    }
+
+    let mut sanitized = BytesStart::new(String::from_utf8_lossy(element.name().as_ref()).to_string());
+
+    for attribute in element.attributes() {
+        let attribute = attribute.ok()?;
+        let key = String::from_utf8_lossy(attribute.key.local_name().as_ref()).to_lowercase();
+
+        // Values with unknown entities can't be checked
+        let value = match attribute.unescape_value() {
+            Ok(result) => result,
+            Err(_) => continue
+        };
+
+        // Animating an event handler or link is the same as setting it
+        if ANIMATION_ELEMENTS.contains(&name.as_str()) && key == "attributename" {
+            let target = value.trim().to_lowercase();
+            if target.starts_with("on") || target.ends_with("href") {
+                return None
+            }
+        }
+
+        if key.starts_with("on") || !is_safe_value(&key, &value) {
+            continue
+        }
+
+        // Written back still escaped
+        sanitized.push_attribute(Attribute {
+            key: attribute.key,
+            value: attribute.value
+        });
+    }
+
+    Some(sanitized)
+}
+
+/// Whether an attribute's value can't point at a script, browsers ignore whitespace & control characters within urls
+fn is_safe_value(key: &str, value: &str) -> bool {
+    let value: String = value.chars()
+        .filter(|character| !character.is_whitespace() && !character.is_control())
+        .collect::<String>()
+        .to_lowercase();
+
+    if value.contains("javascript:") || value.contains("vbscript:") {
+        return false
+    }
+
+    // Only images may be embedded as data urls, anything else could be a document of its own
+    match key {
+        "href" | "src" => !value.starts_with("data:") || ["data:image/png", "data:image/jpeg", "data:image/gif", "data:image/webp"]
+            .iter()
+            .any(|prefix| value.starts_with(prefix)),
+        _ => true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_safe_value_sees_through_whitespace() {
+        assert!(!is_safe_value("href", "javascript:alert(1)"));
+        assert!(!is_safe_value("href", " JaVa\tScRiPt:alert(1)"));
+        assert!(!is_safe_value("href", "java\nscript:alert(1)"));
+        assert!(!is_safe_value("href", "java\u{0}script:alert(1)"));
+        assert!(!is_safe_value("style", "background: url(' javascript :alert(1)')"));
+        assert!(is_safe_value("href", "https://example.com/script.js"));
+    }
+
+    #[test]
+    fn is_safe_value_only_embeds_images() {
+        assert!(is_safe_value("href", "data:image/png;base64,AAAA"));
+        assert!(!is_safe_value("href", "data:image/svg+xml;base64,AAAA"));
+        assert!(!is_safe_value("src", "data:text/html,<script>alert(1)</script>"));
+    }
+
+    #[test]
+    fn sanitize_svg_drops_escaped_javascript_links() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg"><a href="java&#x09;script:alert(1)"><rect width="1" height="1"/></a><a href="jav&#10;ascript:alert(1)"/></svg>"#;
+        let sanitized = sanitize_svg(svg).unwrap();
+
+        assert!(!sanitized.contains("href"));
+        assert!(sanitized.contains(r#"<rect width="1" height="1"/>"#));
+    }
+
+    #[test]
+    fn sanitize_svg_drops_scripts_and_handlers() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" onload="alert(1)"><script>alert(1)</script><set attributeName="onclick" to="alert(1)"/><circle r="1"/></svg>"#;
+        let sanitized = sanitize_svg(svg).unwrap();
+
+        assert_eq!(sanitized, r#"<svg xmlns="http://www.w3.org/2000/svg"><circle r="1"/></svg>"#);
+    }
+}
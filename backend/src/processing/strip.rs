@@ -0,0 +1,246 @@
+// Removes EXIF, XMP & IPTC metadata (GPS coordinates, camera serials, ...) from images before they're stored.
+//
+// Only the container is rewritten, image data is copied as-is so nothing is re-encoded.
+// The orientation is written back within a minimal EXIF block since viewers need it to display the image upright.
+use std::{fs, io, path::Path};
+
+use flate2::Crc;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Strips the metadata of a JPEG, PNG or WebP file in place, other files are left untouched.
+///
+/// Returns whether anything was stripped, malformed images are left as they are
+pub fn strip_file(path: &Path, mime_type: &str, orientation: Option<u16>) -> io::Result<bool> {
+    let strip: fn(&[u8], Option<u16>) -> Option<Vec<u8>> = match mime_type {
+        "image/jpeg" => strip_jpeg,
+        "image/png" => strip_png,
+        "image/webp" => strip_webp,
+        _ => return Ok(false)
+    };
+
+    let data = fs::read(path)?;
+    let stripped = match strip(&data, orientation) {
+        Some(result) if result != data => result,
+        _ => return Ok(false)
+    };
+
+    let stripped_path = path.with_extension("stripped");
+    fs::write(&stripped_path, stripped)?;
+    fs::rename(&stripped_path, path)?;
+
+    Ok(true)
+}
+
+/// TIFF structure holding nothing but the orientation, as embedded in EXIF blocks
+fn orientation_tiff(orientation: Option<u16>) -> Option<Vec<u8>> {
+    let orientation = match orientation {
+        // 1 is upright, which is what viewers assume anyway
+        Some(result) if (2..=8).contains(&result) => result,
+        _ => return None
+    };
+
+    let mut tiff = Vec::with_capacity(26);
+    tiff.extend_from_slice(b"MM\x00\x2a");
+    // Offset of the first & only IFD, holding a single entry
+    tiff.extend_from_slice(&8u32.to_be_bytes());
+    tiff.extend_from_slice(&1u16.to_be_bytes());
+    // Orientation tag, SHORT type, 1 value
+    tiff.extend_from_slice(&0x0112u16.to_be_bytes());
+    tiff.extend_from_slice(&3u16.to_be_bytes());
+    tiff.extend_from_slice(&1u32.to_be_bytes());
+    tiff.extend_from_slice(&orientation.to_be_bytes());
+    tiff.extend_from_slice(&[0, 0]);
+    // No further IFDs
+    tiff.extend_from_slice(&0u32.to_be_bytes());
+
+    Some(tiff)
+}
+
+fn strip_jpeg(data: &[u8], orientation: Option<u16>) -> Option<Vec<u8>> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None
+    }
+
+    let mut stripped = Vec::with_capacity(data.len());
+    stripped.extend_from_slice(&data[..2]);
+
+    let mut exif = orientation_tiff(orientation).map(|tiff| {
+        let mut segment = vec![0xFF, 0xE1];
+        segment.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        segment.extend_from_slice(b"Exif\x00\x00");
+        segment.extend_from_slice(&tiff);
+        segment
+    });
+
+    let mut position = 2;
+    loop {
+        if data.get(position) != Some(&0xFF) {
+            return None
+        }
+
+        let marker = *data.get(position + 1)?;
+
+        // JFIF has to stay first, the orientation goes right after it
+        if marker != 0xE0 && marker != 0xFF {
+            if let Some(segment) = exif.take() {
+                stripped.extend_from_slice(&segment);
+            }
+        }
+
+        match marker {
+            // Fill byte
+            0xFF => {
+                position += 1;
+                continue
+            },
+            // Anything trailing the end of image, such as extra pictures carrying their own EXIF, is dropped
+            0xD9 => {
+                stripped.extend_from_slice(&[0xFF, 0xD9]);
+                return Some(stripped)
+            },
+            // Markers without a length
+            0x01 | 0xD0..=0xD7 => {
+                stripped.extend_from_slice(&data[position..position + 2]);
+                position += 2;
+                continue
+            },
+            _ => {}
+        }
+
+        let length = u16::from_be_bytes([*data.get(position + 2)?, *data.get(position + 3)?]) as usize;
+        if length < 2 {
+            return None
+        }
+
+        let end = position + 2 + length;
+        let segment = data.get(position..end)?;
+        let payload = &segment[4..];
+
+        let keep = match marker {
+            // ICC color profile
+            0xE2 => payload.starts_with(b"ICC_PROFILE\x00"),
+            // Adobe color transform, needed to decode CMYK images
+            0xEE => payload.starts_with(b"Adobe"),
+            // EXIF, XMP, IPTC & the remaining application segments
+            0xE1..=0xEF => false,
+            // Comments
+            0xFE => false,
+            _ => true
+        };
+
+        if keep {
+            stripped.extend_from_slice(segment);
+        }
+
+        position = end;
+
+        // Scans are followed by image data, which runs up to the next marker.
+        // Within it 0xFF is only ever followed by 0x00 or a restart marker
+        if marker == 0xDA {
+            while position + 1 < data.len() && (data[position] != 0xFF || matches!(data[position + 1], 0x00 | 0xD0..=0xD7)) {
+                position += 1;
+            }
+
+            if position + 1 >= data.len() {
+                // Truncated, the image data is kept as it is
+                stripped.extend_from_slice(&data[end..]);
+                return Some(stripped)
+            }
+
+            stripped.extend_from_slice(&data[end..position]);
+        }
+    }
+}
+
+fn strip_png(data: &[u8], orientation: Option<u16>) -> Option<Vec<u8>> {
+    if !data.starts_with(PNG_SIGNATURE) {
+        return None
+    }
+
+    let mut stripped = Vec::with_capacity(data.len());
+    stripped.extend_from_slice(PNG_SIGNATURE);
+
+    let mut position = PNG_SIGNATURE.len();
+    while position < data.len() {
+        let length = u32::from_be_bytes(data.get(position..position + 4)?.try_into().ok()?) as usize;
+        let end = position + 12 + length;
+        let chunk = data.get(position..end)?;
+        let chunk_type = &chunk[4..8];
+
+        // Text chunks hold XMP & free form metadata
+        if !matches!(chunk_type, b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME") {
+            stripped.extend_from_slice(chunk);
+        }
+
+        // Metadata has to come before the image data, right after the header does
+        if chunk_type == b"IHDR" {
+            if let Some(tiff) = orientation_tiff(orientation) {
+                let mut crc = Crc::new();
+                crc.update(b"eXIf");
+                crc.update(&tiff);
+
+                stripped.extend_from_slice(&(tiff.len() as u32).to_be_bytes());
+                stripped.extend_from_slice(b"eXIf");
+                stripped.extend_from_slice(&tiff);
+                stripped.extend_from_slice(&crc.sum().to_be_bytes());
+            }
+        }
+
+        position = end;
+
+        if chunk_type == b"IEND" {
+            break
+        }
+    }
+
+    Some(stripped)
+}
+
+fn strip_webp(data: &[u8], orientation: Option<u16>) -> Option<Vec<u8>> {
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return None
+    }
+
+    let mut stripped = Vec::with_capacity(data.len());
+    stripped.extend_from_slice(&data[..12]);
+
+    // Only the extended format (VP8X) can carry metadata
+    let mut extended_flags = None;
+
+    let mut position = 12;
+    while position + 8 <= data.len() {
+        let fourcc = &data[position..position + 4];
+        let length = u32::from_le_bytes(data[position + 4..position + 8].try_into().ok()?) as usize;
+        // Chunks are padded to an even length
+        let end = position + 8 + length + (length & 1);
+        let chunk = data.get(position..end.min(data.len()))?;
+
+        if fourcc == b"VP8X" {
+            extended_flags = Some(stripped.len() + 8);
+        }
+
+        if fourcc != b"EXIF" && fourcc != b"XMP " {
+            stripped.extend_from_slice(chunk);
+        }
+
+        position = end;
+    }
+
+    if let Some(flags) = extended_flags {
+        // Clears the EXIF & XMP flags
+        *stripped.get_mut(flags)? &= !0x0C;
+
+        if let Some(tiff) = orientation_tiff(orientation) {
+            stripped[flags] |= 0x08;
+            stripped.extend_from_slice(b"EXIF");
+            stripped.extend_from_slice(&(tiff.len() as u32).to_le_bytes());
+            stripped.extend_from_slice(&tiff);
+        }
+    }
+
+    let riff_length = (stripped.len() - 8) as u32;
+    stripped[4..8].copy_from_slice(&riff_length.to_le_bytes());
+
+    Some(stripped)
+}
@@ -0,0 +1,184 @@
+// Detection, previews & syntax highlighting of plain text uploads, which carry no magic bytes for `infer` to recognize.
+use std::{fs::File, io::{self, Read}, path::Path, sync::Arc};
+
+use log::warn;
+use sha2::{Digest, Sha256};
+use sled::Tree;
+use syntect::{highlighting::{Theme, ThemeSet}, html, parsing::SyntaxSet};
+
+use crate::apis::media_utils;
+use crate::database::database::Media;
+use crate::storage::{backend::Storage, encryption::Keyring, variant};
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Most bytes read for a preview, so a single huge line isn't pulled in whole
+pub const PREVIEW_LENGTH: usize = 64 * 1024;
+
+/// Whether a sample from the start of a file looks like UTF-8 text.
+///
+/// NUL bytes never show up in text, other control characters are tolerated as long as they're rare
+/// since logs tend to carry the odd escape sequence
+pub fn is_text(sample: &[u8]) -> bool {
+    let text = valid_prefix(sample.strip_prefix(UTF8_BOM).unwrap_or(sample));
+
+    let text = match text {
+        Some(result) => result,
+        None => return false
+    };
+
+    if text.contains('\0') {
+        return false
+    }
+
+    let control_characters = text.chars()
+        .filter(|character| character.is_control() && !matches!(character, '\t' | '\n' | '\r' | '\x0C' | '\x1B'))
+        .count();
+
+    control_characters * 100 <= text.len()
+}
+
+/// The sample as text, leaving out a character cut off at its end.
+/// Fails when the sample isn't UTF-8
+fn valid_prefix(sample: &[u8]) -> Option<&str> {
+    match std::str::from_utf8(sample) {
+        Ok(result) => Some(result),
+        Err(error) if error.error_len().is_none() => std::str::from_utf8(&sample[..error.valid_up_to()]).ok(),
+        Err(_) => None
+    }
+}
+
+/// Whether a file is entirely valid UTF-8, read in chunks so large files aren't held in memory
+pub fn is_utf8_file(path: &Path) -> io::Result<bool> {
+    let mut file = File::open(path)?;
+    let mut buffer = vec![0; 64 * 1024];
+    // Bytes of a character split across reads, moved to the front of the buffer
+    let mut carried = 0;
+
+    loop {
+        let read = file.read(&mut buffer[carried..])?;
+        if read == 0 {
+            return Ok(carried == 0)
+        }
+
+        let filled = carried + read;
+        match std::str::from_utf8(&buffer[..filled]) {
+            Ok(_) => carried = 0,
+            Err(error) if error.error_len().is_none() => {
+                let valid = error.valid_up_to();
+                buffer.copy_within(valid..filled, 0);
+                carried = filled - valid;
+            },
+            Err(_) => return Ok(false)
+        }
+    }
+}
+
+/// First `lines` lines of a text along with whether anything comes after them,
+/// only the first `PREVIEW_LENGTH` bytes are looked at
+pub fn preview(reader: impl Read, lines: usize) -> io::Result<(String, bool)> {
+    let mut sample = Vec::with_capacity(PREVIEW_LENGTH);
+    reader.take(PREVIEW_LENGTH as u64 + 1).read_to_end(&mut sample)?;
+
+    let cut = sample.len() > PREVIEW_LENGTH;
+    sample.truncate(PREVIEW_LENGTH);
+
+    let text = match valid_prefix(&sample) {
+        Some(result) => result.to_string(),
+        // Text detected on upload is only known to be UTF-8 at its start
+        None => String::from_utf8_lossy(&sample).to_string()
+    };
+
+    let mut remaining = text.split_inclusive('\n');
+    let preview: String = remaining.by_ref().take(lines).collect();
+
+    Ok((preview, cut || remaining.next().is_some()))
+}
+
+/// Syntaxes & theme text is highlighted with, loaded once on launch since parsing them is slow
+pub struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme
+}
+
+impl Highlighter {
+    pub fn load() -> Self {
+        let mut themes = ThemeSet::load_defaults();
+
+        Highlighter {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme: themes.themes.remove("InspiredGitHub").unwrap_or_default()
+        }
+    }
+
+    /// Name of the language matching a name or file extension (e.g. "rust" or "rs" both give "Rust")
+    pub fn language(&self, language: &str) -> Option<String> {
+        self.syntaxes.find_syntax_by_token(language.trim())
+            .map(|syntax| syntax.name.clone())
+    }
+
+    /// Renders text as a standalone HTML page, unknown & unset languages are shown as plain text
+    pub fn highlight(&self, text: &str, language: Option<&str>, title: &str) -> io::Result<String> {
+        let syntax = language
+            .and_then(|language| self.syntaxes.find_syntax_by_token(language))
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text());
+
+        let highlighted = html::highlighted_html_for_string(text, &self.syntaxes, syntax, &self.theme)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+
+        Ok(format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+            escape_html(title), highlighted
+        ))
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.chars()
+        .map(|character| match character {
+            '&' => String::from("&amp;"),
+            '<' => String::from("&lt;"),
+            '>' => String::from("&gt;"),
+            '"' => String::from("&quot;"),
+            '\'' => String::from("&#39;"),
+            character => character.to_string()
+        })
+        .collect()
+}
+
+/// Variant name highlighted text is cached under, deduplicated media may differ in language & title
+pub fn highlight_variant_name(language: Option<&str>, title: &str) -> String {
+    let parameters = format!("{}:{}", language.unwrap_or_default(), title);
+    format!("highlight-{}.html", &format!("{:x}", Sha256::digest(parameters.as_bytes()))[..16])
+}
+
+/// Returns the media highlighted as HTML, highlighting & caching it when it isn't cached yet.
+///
+/// `staged_path` is used for writing the variant before it's handed to storage
+pub fn cached_highlight(
+    variant_database: &Tree,
+    storage: &Arc<dyn Storage>,
+    keyring: &Keyring,
+    highlighter: &Highlighter,
+    media: &Media,
+    staged_path: &Path
+) -> io::Result<Vec<u8>> {
+    let title = format!("{}.{}", media.name, media.extension);
+    let key = variant::variant_key(&media_utils::storage_key(media), &highlight_variant_name(media.language.as_deref(), &title));
+
+    if let Some(result) = variant::fetch(storage.as_ref(), keyring, &key) {
+        return Ok(result)
+    }
+
+    let mut original = Vec::new();
+    media_utils::open_blob(storage, keyring, media)?.read_to_end(&mut original)?;
+
+    let highlighted = highlighter.highlight(&String::from_utf8_lossy(&original), media.language.as_deref(), &title)?
+        .into_bytes();
+
+    if let Err(error) = variant::store(variant_database, storage.as_ref(), keyring, &key, &highlighted, staged_path) {
+        warn!("[Highlight] Failed to cache {}: {}", key, error);
+    }
+
+    Ok(highlighted)
+}
@@ -0,0 +1,33 @@
+// Downscaled previews of image uploads.
+//
+// Thumbnails are cached as variants of the blob they were made from,
+// deduplicated media share their thumbnails the same way they share blobs.
+use crate::apis::media::Media::{ImageFit, ImageFormat};
+use crate::processing::transform::{self, Transform, DEFAULT_QUALITY};
+
+/// Bounding box sizes thumbnails are made in, requested sizes are rounded up to one of these
+pub const THUMBNAIL_SIZES: [u32; 4] = [64, 128, 256, 512];
+
+/// Smallest thumbnail size covering the requested one
+pub fn thumbnail_size(requested: u32) -> u32 {
+    THUMBNAIL_SIZES.iter()
+        .copied()
+        .find(|size| *size >= requested)
+        .unwrap_or(THUMBNAIL_SIZES[THUMBNAIL_SIZES.len() - 1])
+}
+
+/// Variant name a thumbnail is cached under
+pub fn variant_name(size: u32, format: ImageFormat) -> String {
+    format!("thumbnail-{}.{}", size, transform::extension(format))
+}
+
+/// Scales an image down to fit within a `size` by `size` box
+pub fn thumbnail_transform(size: u32, format: ImageFormat) -> Transform {
+    Transform {
+        width: Some(size),
+        height: Some(size),
+        fit: ImageFit::Contain,
+        format,
+        quality: DEFAULT_QUALITY
+    }
+}
@@ -0,0 +1,165 @@
+// Resizing & re-encoding of image uploads, results are cached as variants of the original blob.
+use std::{io::{self, Cursor, Read}, path::Path, sync::Arc};
+
+use image::{
+    codecs::{jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    ColorType, DynamicImage, ImageEncoder
+};
+use log::warn;
+use sha2::{Digest, Sha256};
+use sled::Tree;
+
+use crate::apis::media::Media::{ImageFit, ImageFormat};
+use crate::apis::media_utils;
+use crate::database::database::Media;
+use crate::storage::{backend::Storage, encryption::Keyring, variant};
+
+pub const DEFAULT_QUALITY: u8 = 80;
+
+/// Describes how an image should be transformed
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    /// Box the image is resized into, images are left at their size when unset
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fit: ImageFit,
+    pub format: ImageFormat,
+    /// Only used by jpeg, png & webp are always lossless
+    pub quality: u8
+}
+
+impl Transform {
+    /// Identifies the transform's output, transforms only differing in ignored parameters share it
+    pub fn hash(&self) -> String {
+        let quality = match self.format {
+            ImageFormat::Jpeg => self.quality,
+            _ => 0
+        };
+
+        let parameters = format!("{:?}:{:?}:{:?}:{}:{}", self.width, self.height, self.fit, extension(self.format), quality);
+        format!("{:x}", Sha256::digest(parameters.as_bytes()))[..16].to_string()
+    }
+
+    /// Resizes & encodes an image, failing with `ErrorKind::Unsupported` when it can't be decoded.
+    ///
+    /// The image is turned upright first since transformed images don't carry its EXIF orientation
+    pub fn apply(&self, original: &[u8], orientation: Option<u16>) -> io::Result<Vec<u8>> {
+        let image = image::load_from_memory(original)
+            .map_err(|error| io::Error::new(io::ErrorKind::Unsupported, error.to_string()))?;
+
+        encode(&self.resize(orient(image, orientation)), self.format, self.quality)
+    }
+
+    fn resize(&self, image: DynamicImage) -> DynamicImage {
+        let (width, height) = match (self.width, self.height) {
+            (None, None) => return image,
+            (Some(width), Some(height)) => (width, height),
+            // A single dimension only bounds that side
+            (width, height) => (width.unwrap_or(u32::MAX), height.unwrap_or(u32::MAX))
+        };
+
+        let exact = self.width.is_some() && self.height.is_some();
+
+        match self.fit {
+            ImageFit::Cover if exact => image.resize_to_fill(width, height, FilterType::Triangle),
+            ImageFit::Fill if exact => image.resize_exact(width, height, FilterType::Triangle),
+            // Images are never enlarged to fit
+            _ if image.width() <= width && image.height() <= height => image,
+            _ => image.resize(width, height, FilterType::Triangle)
+        }
+    }
+}
+
+/// Applies an EXIF orientation
+fn orient(image: DynamicImage, orientation: Option<u16>) -> DynamicImage {
+    match orientation {
+        Some(2) => image.fliph(),
+        Some(3) => image.rotate180(),
+        Some(4) => image.flipv(),
+        Some(5) => image.rotate90().fliph(),
+        Some(6) => image.rotate90(),
+        Some(7) => image.rotate270().fliph(),
+        Some(8) => image.rotate270(),
+        _ => image
+    }
+}
+
+pub fn extension(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => "png",
+        ImageFormat::Jpeg => "jpg",
+        ImageFormat::Webp => "webp"
+    }
+}
+
+pub fn mime_type(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => "image/png",
+        ImageFormat::Jpeg => "image/jpeg",
+        ImageFormat::Webp => "image/webp"
+    }
+}
+
+/// Format matching the media's mime type, if it's one images can be transformed into
+pub fn media_format(media: &Media) -> Option<ImageFormat> {
+    match media_utils::mime_type(media).as_str() {
+        "image/png" => Some(ImageFormat::Png),
+        "image/jpeg" => Some(ImageFormat::Jpeg),
+        "image/webp" => Some(ImageFormat::Webp),
+        _ => None
+    }
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat, quality: u8) -> io::Result<Vec<u8>> {
+    let mut encoded = Cursor::new(Vec::new());
+
+    let result = match format {
+        ImageFormat::Png => {
+            let image = image.to_rgba8();
+            PngEncoder::new(&mut encoded).write_image(image.as_raw(), image.width(), image.height(), ColorType::Rgba8)
+        },
+        ImageFormat::Jpeg => JpegEncoder::new_with_quality(&mut encoded, quality)
+            .encode_image(&DynamicImage::ImageRgb8(image.to_rgb8())),
+        ImageFormat::Webp => {
+            let image = image.to_rgba8();
+            WebPEncoder::new_lossless(&mut encoded).encode(image.as_raw(), image.width(), image.height(), ColorType::Rgba8)
+        }
+    };
+
+    match result {
+        Ok(_) => Ok(encoded.into_inner()),
+        Err(error) => Err(io::Error::new(io::ErrorKind::InvalidData, error.to_string()))
+    }
+}
+
+/// Returns a cached variant of the media named `name`, transforming the media & caching it when it isn't cached yet.
+///
+/// `staged_path` is used for writing the variant before it's handed to storage
+pub fn cached_transform(
+    variant_database: &Tree,
+    storage: &Arc<dyn Storage>,
+    keyring: &Keyring,
+    media: &Media,
+    name: &str,
+    transform: &Transform,
+    staged_path: &Path
+) -> io::Result<Vec<u8>> {
+    let key = variant::variant_key(&media_utils::storage_key(media), name);
+
+    if let Some(result) = variant::fetch(storage.as_ref(), keyring, &key) {
+        return Ok(result)
+    }
+
+    let mut original = Vec::new();
+    media_utils::open_blob(storage, keyring, media)?.read_to_end(&mut original)?;
+
+    let orientation = media.metadata.as_ref().and_then(|metadata| metadata.orientation);
+    let transformed = transform.apply(&original, orientation)?;
+
+    if let Err(error) = variant::store(variant_database, storage.as_ref(), keyring, &key, &transformed, staged_path) {
+        warn!("[Transform] Failed to cache {}: {}", key, error);
+    }
+
+    Ok(transformed)
+}
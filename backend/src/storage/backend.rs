@@ -0,0 +1,47 @@
+use std::{io::{self, Read}, path::Path, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Config;
+use crate::apis::media_utils;
+use crate::storage::{local::LocalStorage, s3::S3Storage};
+
+/// Backend media blobs are stored in
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StorageBackend {
+    #[default]
+    #[serde(rename = "local")]
+    Local,
+    #[serde(rename = "s3")]
+    S3
+}
+
+/// Blob store media is kept in, addressed by keys such as `Video/<name>`
+///
+/// Calls are blocking, async callers should move them onto a blocking thread
+/// or go through `blob::spawn_reader`
+pub trait Storage: Send + Sync {
+    /// Stores the file at `source` under `key`, the source file may be moved in the process
+    fn put(&self, key: &str, source: &Path) -> io::Result<()>;
+
+    /// Reads an entire blob into memory
+    fn get(&self, key: &str) -> io::Result<Vec<u8>>;
+
+    /// Opens a blob for reading from `start` up to and including `end`, or its end
+    fn stream(&self, key: &str, start: u64, end: Option<u64>) -> io::Result<Box<dyn Read + Send>>;
+
+    fn delete(&self, key: &str) -> io::Result<()>;
+
+    fn exists(&self, key: &str) -> io::Result<bool>;
+
+    /// Stored size of a blob in bytes
+    fn size(&self, key: &str) -> io::Result<u64>;
+}
+
+/// Creates the storage backend selected in the config
+pub fn open_storage(config: &Config) -> Result<Arc<dyn Storage>, String> {
+    Ok(match config.backend_storage {
+        StorageBackend::Local => Arc::new(LocalStorage::new(media_utils::content_directory(config))),
+        StorageBackend::S3 => Arc::new(S3Storage::new(config)?)
+    })
+}
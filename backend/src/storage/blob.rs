@@ -0,0 +1,523 @@
+// Stored media is either kept as-is or as a seekable compressed stream,
+// either way it's read back through the configured `Storage` backend
+// and optionally encrypted on top (see `encryption`).
+//
+// The seekable layout splits the input into `BLOCK_SIZE` blocks that can each
+// be decompressed on their own. With zlib it's a regular zlib stream that is
+// fully flushed after every block, a full flush byte aligns the output and
+// resets the compression dictionary. With zstd & brotli every block is its
+// own frame written back to back.
+// The compressed offset of each block is kept on the media record which lets
+// ranges be served by only decompressing the blocks they overlap.
+use std::{
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll}
+};
+
+use brotli::enc::BrotliEncoderParams;
+use flate2::{read::ZlibDecoder, Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use rocket::tokio::{
+    self,
+    io::{AsyncRead, AsyncSeek, AsyncWriteExt, ReadBuf}
+};
+use serde::{Deserialize, Serialize};
+
+use crate::storage::{backend::Storage, encryption::{self, BlobCipher}};
+
+/// Amount of uncompressed bytes stored in each independently inflatable block
+pub const BLOCK_SIZE: usize = 256 * 1024;
+
+/// Zstd compression level, high levels only slow down compression
+const ZSTD_LEVEL: i32 = 19;
+
+/// Brotli compression quality & window size
+const BROTLI_QUALITY: i32 = 11;
+const BROTLI_WINDOW: i32 = 22;
+
+/// Buffer size used by the brotli decoder
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+/// Length of the zlib header preceding the first block
+const ZLIB_HEADER_LENGTH: u64 = 2;
+
+/// Amount of bytes moved between the blocking reader and the response at a time
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Furthest a plain blob is read ahead when seeking forward, rather than reopening it from storage
+const SEEK_SKIP_LENGTH: u64 = 64 * 1024;
+
+/// Codec the blocks of a seekable blob are compressed with
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Codec {
+    #[default]
+    #[serde(rename = "zlib")]
+    Zlib,
+    #[serde(rename = "zstd")]
+    Zstd,
+    #[serde(rename = "brotli")]
+    Brotli
+}
+
+/// Describes how a blob is laid out on disk
+#[derive(Clone, Debug)]
+pub enum Layout {
+    /// Stored as-is
+    Plain,
+    /// Seekable stream, offsets point to the start of each block
+    Blocks(Codec, Vec<u64>),
+    /// Zlib stream without an index, only readable from the start
+    Stream
+}
+
+/// `Content-Encoding` the blob's bytes can be sent as without decompressing them, if any
+pub fn content_encoding(layout: &Layout) -> Option<&'static str> {
+    match layout {
+        Layout::Plain => None,
+        // HTTP's deflate coding is a zlib stream
+        Layout::Stream | Layout::Blocks(Codec::Zlib, _) => Some("deflate"),
+        // Consecutive zstd frames decode as a single stream
+        Layout::Blocks(Codec::Zstd, offsets) if !offsets.is_empty() => Some("zstd"),
+        // Brotli streams can't be concatenated, leaving only single block blobs valid
+        Layout::Blocks(Codec::Brotli, offsets) if offsets.len() == 1 => Some("br"),
+        Layout::Blocks(..) => None
+    }
+}
+
+/// Fills `buffer` as far as possible, only returning less at the end of `reader`
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+
+    while read < buffer.len() {
+        match reader.read(&mut buffer[read..]) {
+            Ok(0) => break,
+            Ok(result) => read += result,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err)
+        }
+    }
+
+    Ok(read)
+}
+
+/// Writes `source` into `destination` using the seekable layout
+///
+/// Returns the amount of compressed bytes written along with the offset of each block
+pub fn compress_seekable(source: &mut impl Read, destination: &mut impl Write, codec: Codec) -> io::Result<(u64, Vec<u64>)> {
+    match codec {
+        Codec::Zlib => compress_zlib(source, destination),
+        _ => compress_frames(source, destination, codec)
+    }
+}
+
+/// Seekable layout for zlib, a single stream fully flushed after every block
+fn compress_zlib(source: &mut impl Read, destination: &mut impl Write) -> io::Result<(u64, Vec<u64>)> {
+    let mut compress = Compress::new(Compression::best(), true);
+    let mut input = vec![0u8; BLOCK_SIZE];
+    let mut output: Vec<u8> = Vec::with_capacity(BLOCK_SIZE + 1024);
+
+    let mut offsets = Vec::new();
+    let mut written: u64 = 0;
+
+    loop {
+        let read = read_full(source, &mut input)?;
+        let last = read < BLOCK_SIZE;
+
+        if read > 0 {
+            offsets.push(written.max(ZLIB_HEADER_LENGTH));
+        }
+
+        let flush = if last { FlushCompress::Finish } else { FlushCompress::Full };
+        let mut consumed = 0;
+
+        loop {
+            output.clear();
+
+            let total_in = compress.total_in();
+            let status = compress.compress_vec(&input[consumed..read], &mut output, flush)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            consumed += (compress.total_in() - total_in) as usize;
+
+            destination.write_all(&output)?;
+            written += output.len() as u64;
+
+            if status == Status::StreamEnd {
+                break
+            }
+
+            // The flush has completed once the encoder stops filling the output
+            if !last && consumed == read && output.len() < output.capacity() {
+                break
+            }
+        }
+
+        if last {
+            break
+        }
+    }
+
+    Ok((written, offsets))
+}
+
+/// Seekable layout for codecs writing every block as its own frame
+fn compress_frames(source: &mut impl Read, destination: &mut impl Write, codec: Codec) -> io::Result<(u64, Vec<u64>)> {
+    let mut input = vec![0u8; BLOCK_SIZE];
+
+    let mut offsets = Vec::new();
+    let mut written: u64 = 0;
+
+    loop {
+        let read = read_full(source, &mut input)?;
+        if read == 0 {
+            break
+        }
+
+        let compressed = match codec {
+            Codec::Zstd => zstd::bulk::compress(&input[..read], ZSTD_LEVEL)?,
+            Codec::Brotli => {
+                let params = BrotliEncoderParams {
+                    quality: BROTLI_QUALITY,
+                    lgwin: BROTLI_WINDOW,
+                    size_hint: read,
+                    ..Default::default()
+                };
+
+                let mut compressed = Vec::new();
+                brotli::BrotliCompress(&mut &input[..read], &mut compressed, &params)?;
+                compressed
+            },
+            Codec::Zlib => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Zlib blocks aren't framed"))
+        };
+
+        offsets.push(written);
+        destination.write_all(&compressed)?;
+        written += compressed.len() as u64;
+
+        if read < BLOCK_SIZE {
+            break
+        }
+    }
+
+    Ok((written, offsets))
+}
+
+/// Decompresses a single block of the seekable layout
+fn decompress_block(codec: Codec, compressed: &[u8], length: usize) -> io::Result<Vec<u8>> {
+    let output = match codec {
+        Codec::Zlib => inflate_block(compressed, length)?,
+        Codec::Zstd => zstd::bulk::decompress(compressed, length)?,
+        Codec::Brotli => {
+            let mut output = Vec::with_capacity(length);
+            brotli::Decompressor::new(compressed, BROTLI_BUFFER_SIZE)
+                .take(length as u64 + 1)
+                .read_to_end(&mut output)?;
+            output
+        }
+    };
+
+    if output.len() != length {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Block decompressed to an unexpected length"))
+    }
+
+    Ok(output)
+}
+
+/// Inflates a single block of the zlib seekable layout
+fn inflate_block(compressed: &[u8], length: usize) -> io::Result<Vec<u8>> {
+    let mut decompress = Decompress::new(false);
+    let mut output = Vec::with_capacity(length);
+
+    while output.len() < length {
+        let total_in = decompress.total_in();
+        let produced = output.len();
+
+        let status = decompress.decompress_vec(&compressed[total_in as usize..], &mut output, FlushDecompress::None)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        if status == Status::StreamEnd || (output.len() == produced && decompress.total_in() == total_in) {
+            break
+        }
+    }
+
+    Ok(output)
+}
+
+/// Opens a blob's stored bytes from `start` up to and including `end`, or their end, decrypting them if needed
+pub fn open_stored(storage: &dyn Storage, cipher: Option<&BlobCipher>, key: &str, start: u64, end: Option<u64>) -> io::Result<Box<dyn Read + Send>> {
+    match cipher {
+        Some(cipher) => cipher.stream(storage, key, start, end),
+        None => storage.stream(key, start, end)
+    }
+}
+
+/// Zlib decoder over a blob read from the start
+type StreamDecoder = ZlibDecoder<BufReader<Box<dyn Read + Send>>>;
+
+enum Source {
+    /// Stored bytes as-is, opened on the first read
+    Plain(Option<Box<dyn Read + Send>>),
+    Blocks {
+        codec: Codec,
+        offsets: Vec<u64>
+    },
+    /// Opened on the first read, along with how far it has been decoded
+    Stream(Option<Box<StreamDecoder>>, u64)
+}
+
+/// Reads the original contents of a stored blob within a byte range
+///
+/// Nothing is read from storage until the first read, letting the reader be
+/// set up on an async task and driven from a blocking one
+pub struct BlobReader {
+    storage: Arc<dyn Storage>,
+    key: String,
+    cipher: Option<BlobCipher>,
+    source: Source,
+    // Total uncompressed length of the blob
+    length: u64,
+    // Current position within the uncompressed blob
+    position: u64,
+    // Position reads stop at
+    end: u64,
+    block: Vec<u8>,
+    block_index: Option<usize>
+}
+
+impl BlobReader {
+    /// Opens a blob for reading, `length` is the blob's uncompressed length
+    /// which is only unknown for legacy uploads
+    pub fn open(storage: Arc<dyn Storage>, key: &str, cipher: Option<BlobCipher>, layout: &Layout, length: Option<u64>) -> io::Result<BlobReader> {
+        let (source, length) = match layout {
+            Layout::Plain => (Source::Plain(None), match (length, &cipher) {
+                (Some(result), _) => result,
+                (None, Some(_)) => encryption::decrypted_length(storage.size(key)?),
+                (None, None) => storage.size(key)?
+            }),
+            Layout::Blocks(codec, offsets) => {
+                let length = match length {
+                    Some(result) => result,
+                    None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Seekable blobs require a known length"))
+                };
+
+                (Source::Blocks {
+                    codec: *codec,
+                    offsets: offsets.clone()
+                }, length)
+            },
+            Layout::Stream => (Source::Stream(None, 0), length.unwrap_or(u64::MAX))
+        };
+
+        Ok(BlobReader {
+            storage,
+            key: key.to_string(),
+            cipher,
+            source,
+            length,
+            position: 0,
+            end: length,
+            block: Vec::new(),
+            block_index: None
+        })
+    }
+
+    /// Restricts reading to the inclusive range `start..=end`
+    pub fn set_range(&mut self, start: u64, end: u64) {
+        self.end = (end + 1).min(self.length);
+
+        match &mut self.source {
+            Source::Plain(reader) => *reader = None,
+            Source::Blocks { .. } => {},
+            // Zlib streams can't be rewound, only restarted
+            Source::Stream(decoder, decoded) => if start < *decoded {
+                *decoder = None;
+                *decoded = 0;
+            }
+        }
+
+        self.position = start;
+    }
+
+    fn load_block(&mut self, index: usize) -> io::Result<()> {
+        if self.block_index == Some(index) {
+            return Ok(())
+        }
+
+        if let Source::Blocks { codec, offsets } = &self.source {
+            let start = offsets[index];
+            let end = offsets.get(index + 1).map(|offset| offset - 1);
+
+            let mut compressed = Vec::new();
+            open_stored(self.storage.as_ref(), self.cipher.as_ref(), &self.key, start, end)?.read_to_end(&mut compressed)?;
+
+            let block_start = index as u64 * BLOCK_SIZE as u64;
+            let block_length = (self.length - block_start).min(BLOCK_SIZE as u64) as usize;
+
+            self.block = decompress_block(*codec, &compressed, block_length)?;
+            self.block_index = Some(index);
+        }
+
+        Ok(())
+    }
+}
+
+impl Read for BlobReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.end || buffer.is_empty() {
+            return Ok(0)
+        }
+
+        let wanted = (self.end - self.position).min(buffer.len() as u64) as usize;
+
+        let read = match &mut self.source {
+            Source::Plain(reader) => {
+                if reader.is_none() {
+                    *reader = Some(open_stored(self.storage.as_ref(), self.cipher.as_ref(), &self.key, self.position, Some(self.end - 1))?);
+                }
+
+                match reader {
+                    Some(reader) => reader.read(&mut buffer[..wanted])?,
+                    None => 0
+                }
+            },
+            Source::Stream(decoder, decoded) => {
+                if decoder.is_none() {
+                    let stored = open_stored(self.storage.as_ref(), self.cipher.as_ref(), &self.key, 0, None)?;
+                    *decoder = Some(Box::new(ZlibDecoder::new(BufReader::new(stored))));
+                    *decoded = 0;
+                }
+
+                let decoder = match decoder {
+                    Some(decoder) => decoder,
+                    None => return Ok(0)
+                };
+
+                if *decoded < self.position {
+                    *decoded += io::copy(&mut (&mut **decoder).take(self.position - *decoded), &mut io::sink())?;
+                }
+
+                let read = decoder.read(&mut buffer[..wanted])?;
+                *decoded += read as u64;
+                read
+            },
+            Source::Blocks { .. } => {
+                let index = (self.position / BLOCK_SIZE as u64) as usize;
+                self.load_block(index)?;
+
+                let block_offset = (self.position % BLOCK_SIZE as u64) as usize;
+                let read = wanted.min(self.block.len() - block_offset);
+
+                buffer[..read].copy_from_slice(&self.block[block_offset..block_offset + read]);
+                read
+            }
+        };
+
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+/// Seeking lifts a range set before, reads continue up to the end of the blob
+impl Seek for BlobReader {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let target = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            // Legacy compressed uploads don't know their length
+            SeekFrom::End(_) if self.length == u64::MAX => return Err(io::Error::new(io::ErrorKind::Unsupported, "Blob's length is unknown")),
+            SeekFrom::End(offset) => self.length.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset)
+        };
+
+        let target = match target {
+            Some(result) => result,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Can't seek before the start of the blob"))
+        };
+
+        let skip = matches!(self.source, Source::Plain(Some(_)))
+            && self.end == self.length
+            && target >= self.position
+            && target <= self.length
+            && target - self.position <= SEEK_SKIP_LENGTH;
+
+        if skip {
+            let ahead = target - self.position;
+            io::copy(&mut self.by_ref().take(ahead), &mut io::sink())?;
+        }
+
+        if self.position != target || self.end != self.length {
+            self.set_range(target, self.length.saturating_sub(1));
+        }
+
+        Ok(target)
+    }
+}
+
+/// Lets a stream with an already known length be used as a sized response body,
+/// Rocket only seeks a body when its size wasn't provided up front
+pub struct KnownLength<R>(pub R);
+
+impl<R: AsyncRead + Unpin> AsyncRead for KnownLength<R> {
+    fn poll_read(mut self: Pin<&mut Self>, context: &mut Context<'_>, buffer: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(context, buffer)
+    }
+}
+
+impl<R: Unpin> AsyncSeek for KnownLength<R> {
+    fn start_seek(self: Pin<&mut Self>, _position: SeekFrom) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "Stream can't be seeked"))
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _context: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        Poll::Ready(Ok(0))
+    }
+}
+
+/// Hands what's written to it over to an async stream, blocking until the stream took it
+struct BlockingWriter(tokio::io::DuplexStream, tokio::runtime::Handle);
+
+impl Write for BlockingWriter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.1.block_on(self.0.write(buffer))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs a blocking writer on its own thread, streaming what it writes back asynchronously.
+/// The stream ends early when `write` fails
+pub fn spawn_writer(write: impl FnOnce(&mut dyn Write) -> io::Result<()> + Send + 'static) -> impl AsyncRead + Send + Unpin {
+    let (writer, stream) = tokio::io::duplex(STREAM_CHUNK_SIZE);
+    let handle = tokio::runtime::Handle::current();
+
+    tokio::task::spawn_blocking(move || {
+        let _ = write(&mut BlockingWriter(writer, handle));
+    });
+
+    stream
+}
+
+/// Moves a blocking reader onto its own thread, streaming its output back asynchronously
+pub fn spawn_reader(mut reader: impl Read + Send + 'static) -> impl AsyncRead + Send + Unpin {
+    let (mut writer, stream) = tokio::io::duplex(STREAM_CHUNK_SIZE);
+    let handle = tokio::runtime::Handle::current();
+
+    tokio::task::spawn_blocking(move || {
+        let mut chunk = vec![0u8; STREAM_CHUNK_SIZE];
+
+        loop {
+            let read = match reader.read(&mut chunk) {
+                Ok(0) | Err(_) => break,
+                Ok(result) => result
+            };
+
+            // Stops early once the client has gone away
+            if handle.block_on(writer.write_all(&chunk[..read])).is_err() {
+                break
+            }
+        }
+    });
+
+    stream
+}
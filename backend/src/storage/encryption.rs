@@ -0,0 +1,690 @@
+// Optional encryption at rest.
+//
+// The stored bytes of a blob (after compression) are split into `SEGMENT_SIZE`
+// segments that are each sealed with XChaCha20-Poly1305, so ranges can still be
+// served by only decrypting the segments they overlap.
+// Every blob gets its own random nonce, a segment's nonce is the blob's nonce
+// with its last 8 bytes xored with the segment's index.
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::Arc
+};
+
+use chacha20poly1305::{aead::{Aead, KeyInit}, Key, XChaCha20Poly1305, XNonce};
+use log::{info, warn};
+use rand::RngCore;
+use rand_core::OsRng;
+use sha2::{Digest, Sha256};
+use sled::{Db, IVec};
+
+use crate::Config;
+use crate::apis::media_utils;
+use crate::database::database::{Blob, Media, MediaRevision};
+use crate::storage::{backend::Storage, blob, variant};
+
+/// Amount of stored bytes sealed together
+pub const SEGMENT_SIZE: u64 = 64 * 1024;
+
+const TAG_LENGTH: u64 = 16;
+const NONCE_LENGTH: usize = 24;
+const KEY_LENGTH: usize = 32;
+
+/// Length of a sealed segment
+const SEALED_SEGMENT_SIZE: u64 = SEGMENT_SIZE + TAG_LENGTH;
+
+#[derive(Clone)]
+pub struct EncryptionKey {
+    /// Identifies the key on media records without revealing it
+    pub id: String,
+    cipher: XChaCha20Poly1305
+}
+
+impl EncryptionKey {
+    fn new(key: &[u8]) -> EncryptionKey {
+        EncryptionKey {
+            id: to_hex(&Sha256::digest(key)[..8]),
+            cipher: XChaCha20Poly1305::new(Key::from_slice(key))
+        }
+    }
+
+    /// Reads a hex encoded key file
+    fn read(path: &Path) -> Result<EncryptionKey, String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(result) => result,
+            Err(error) => return Err(format!("Couldn't read encryption key {:?}: {}", path, error))
+        };
+
+        match from_hex(contents.trim()) {
+            Some(key) if key.len() == KEY_LENGTH => Ok(EncryptionKey::new(&key)),
+            _ => Err(format!("Encryption key {:?} must hold {} hex encoded bytes", path, KEY_LENGTH))
+        }
+    }
+
+    /// Generates a new key, saving it to `path`
+    fn generate(path: &Path) -> Result<EncryptionKey, String> {
+        let mut key = [0u8; KEY_LENGTH];
+        OsRng.fill_bytes(&mut key);
+
+        if let Err(error) = fs::write(path, to_hex(&key)) {
+            return Err(format!("Couldn't write encryption key {:?}: {}", path, error))
+        }
+
+        Ok(EncryptionKey::new(&key))
+    }
+}
+
+/// Keys blobs can be encrypted with, the first one is used for new blobs
+#[derive(Default)]
+pub struct Keyring {
+    keys: Vec<EncryptionKey>
+}
+
+impl Keyring {
+    /// Loads the key file named in the config, generating it when it doesn't exist yet.
+    /// The key of an unfinished rotation is loaded as well
+    pub fn load(config: &Config) -> Result<Keyring, String> {
+        let path = match &config.backend_encryption_key_path {
+            Some(result) => Path::new(result),
+            None => return Ok(Keyring::default())
+        };
+
+        let mut keys = vec![if path.exists() {
+            EncryptionKey::read(path)?
+        } else {
+            warn!("[Encryption] Generating a new key at {:?}", path);
+            EncryptionKey::generate(path)?
+        }];
+
+        let pending_path = pending_key_path(path);
+        if pending_path.exists() {
+            warn!("[Encryption] Found the key of an unfinished rotation, run rotate-key again to finish it");
+            keys.push(EncryptionKey::read(&pending_path)?);
+        }
+
+        Ok(Keyring {
+            keys
+        })
+    }
+
+    /// Key new blobs are encrypted with, if encryption is enabled
+    pub fn current(&self) -> Option<&EncryptionKey> {
+        self.keys.first()
+    }
+
+    /// Cipher for a blob stored with the given key & nonce, unencrypted blobs have neither.
+    ///
+    /// `sealed_length` is the blob's recorded stored size, reads ending short of it fail as the blob was truncated
+    pub fn blob_cipher(&self, key_id: &Option<String>, nonce: &Option<String>, sealed_length: Option<u64>) -> io::Result<Option<BlobCipher>> {
+        let nonce = match nonce {
+            Some(result) => result,
+            None => return Ok(None)
+        };
+
+        let nonce = match from_hex(nonce) {
+            Some(result) if result.len() == NONCE_LENGTH => result,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "Malformed blob nonce"))
+        };
+
+        // The blob's own key goes first, the rest cover a blob rotated right before being interrupted
+        let mut ciphers: Vec<XChaCha20Poly1305> = Vec::with_capacity(self.keys.len());
+        ciphers.extend(self.keys.iter().filter(|key| Some(&key.id) == key_id.as_ref()).map(|key| key.cipher.clone()));
+        ciphers.extend(self.keys.iter().filter(|key| Some(&key.id) != key_id.as_ref()).map(|key| key.cipher.clone()));
+
+        if ciphers.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Blob is encrypted but no encryption key is configured"))
+        }
+
+        Ok(Some(BlobCipher {
+            ciphers: Arc::new(ciphers),
+            nonce: XNonce::clone_from_slice(&nonce),
+            sealed_length
+        }))
+    }
+
+    /// Seals a small standalone object, such as a cached thumbnail, with the current key.
+    /// The object's nonce is stored in front of it, objects are left as-is when encryption is disabled
+    pub fn seal(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let key = match self.current() {
+            Some(result) => result,
+            None => return Ok(data.to_vec())
+        };
+
+        let nonce = generate_nonce();
+        let mut sealed = from_hex(&nonce).unwrap_or_default();
+        encrypt(key, &nonce, &mut &data[..], &mut sealed)?;
+
+        Ok(sealed)
+    }
+
+    /// Opens an object sealed through `seal`
+    pub fn open(&self, sealed: &[u8]) -> io::Result<Vec<u8>> {
+        if self.keys.is_empty() {
+            return Ok(sealed.to_vec())
+        }
+
+        if sealed.len() < NONCE_LENGTH {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Sealed object is missing its nonce"))
+        }
+
+        let (nonce, sealed) = sealed.split_at(NONCE_LENGTH);
+        let cipher = BlobCipher {
+            ciphers: Arc::new(self.keys.iter().map(|key| key.cipher.clone()).collect()),
+            nonce: XNonce::clone_from_slice(nonce),
+            sealed_length: None
+        };
+
+        let mut data = Vec::with_capacity(sealed.len());
+        DecryptReader {
+            sealed: Box::new(io::Cursor::new(sealed.to_vec())),
+            cipher,
+            index: 0,
+            segment: Vec::new(),
+            offset: 0,
+            skip: 0,
+            remaining: None,
+            sealed_remaining: None
+        }.read_to_end(&mut data)?;
+
+        Ok(data)
+    }
+}
+
+/// Decrypts a single blob
+#[derive(Clone)]
+pub struct BlobCipher {
+    ciphers: Arc<Vec<XChaCha20Poly1305>>,
+    nonce: XNonce,
+    // Nothing marks a blob's last segment, so a blob cut off between segments is only noticed through its stored size
+    sealed_length: Option<u64>
+}
+
+impl BlobCipher {
+    fn open_segment(&self, index: u64, sealed: &[u8]) -> io::Result<Vec<u8>> {
+        let nonce = segment_nonce(&self.nonce, index);
+
+        self.ciphers.iter()
+            .find_map(|cipher| cipher.decrypt(&nonce, sealed).ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Failed to decrypt blob"))
+    }
+
+    /// Opens the decrypted stored bytes from `start` up to and including `end`, or their end
+    pub fn stream(&self, storage: &dyn Storage, key: &str, start: u64, end: Option<u64>) -> io::Result<Box<dyn Read + Send>> {
+        let first_segment = start / SEGMENT_SIZE;
+        let sealed_end = end.map(|end| (end / SEGMENT_SIZE + 1) * SEALED_SEGMENT_SIZE - 1);
+        let sealed_remaining = self.sealed_length.map(|sealed_length| match sealed_end {
+            Some(sealed_end) => sealed_length.min(sealed_end + 1),
+            None => sealed_length
+        }.saturating_sub(first_segment * SEALED_SEGMENT_SIZE));
+
+        Ok(Box::new(DecryptReader {
+            sealed: storage.stream(key, first_segment * SEALED_SEGMENT_SIZE, sealed_end)?,
+            cipher: self.clone(),
+            index: first_segment,
+            segment: Vec::new(),
+            offset: 0,
+            skip: (start - first_segment * SEGMENT_SIZE) as usize,
+            remaining: end.map(|end| end + 1 - start),
+            sealed_remaining
+        }))
+    }
+}
+
+struct DecryptReader {
+    sealed: Box<dyn Read + Send>,
+    cipher: BlobCipher,
+    // Index of the next segment
+    index: u64,
+    segment: Vec<u8>,
+    offset: usize,
+    // Bytes skipped at the start of the first segment
+    skip: usize,
+    remaining: Option<u64>,
+    // Sealed bytes storage should still hand over, when the blob's stored size is known
+    sealed_remaining: Option<u64>
+}
+
+impl Read for DecryptReader {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == Some(0) || buffer.is_empty() {
+            return Ok(0)
+        }
+
+        if self.offset >= self.segment.len() {
+            let mut sealed = Vec::with_capacity(SEALED_SEGMENT_SIZE as usize);
+            (&mut self.sealed).take(SEALED_SEGMENT_SIZE).read_to_end(&mut sealed)?;
+
+            if sealed.is_empty() {
+                if matches!(self.sealed_remaining, Some(sealed_remaining) if sealed_remaining > 0) {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Encrypted blob is truncated"))
+                }
+
+                return Ok(0)
+            }
+
+            if let Some(sealed_remaining) = &mut self.sealed_remaining {
+                *sealed_remaining = sealed_remaining.saturating_sub(sealed.len() as u64);
+            }
+
+            self.segment = self.cipher.open_segment(self.index, &sealed)?;
+            self.index += 1;
+            self.offset = self.skip.min(self.segment.len());
+            self.skip = 0;
+        }
+
+        let mut read = buffer.len().min(self.segment.len() - self.offset);
+        if let Some(remaining) = self.remaining {
+            read = read.min(remaining as usize);
+            self.remaining = Some(remaining - read as u64);
+        }
+
+        buffer[..read].copy_from_slice(&self.segment[self.offset..self.offset + read]);
+        self.offset += read;
+
+        Ok(read)
+    }
+}
+
+fn segment_nonce(nonce: &XNonce, index: u64) -> XNonce {
+    let mut nonce = *nonce;
+
+    for (byte, index_byte) in nonce[NONCE_LENGTH - 8..].iter_mut().zip(index.to_le_bytes()) {
+        *byte ^= index_byte;
+    }
+
+    nonce
+}
+
+/// Random nonce for a new blob, hex encoded
+pub fn generate_nonce() -> String {
+    let mut nonce = [0u8; NONCE_LENGTH];
+    OsRng.fill_bytes(&mut nonce);
+
+    to_hex(&nonce)
+}
+
+/// Seals `source` into `destination`, returning the amount of bytes written
+pub fn encrypt(key: &EncryptionKey, nonce: &str, source: &mut impl Read, destination: &mut impl Write) -> io::Result<u64> {
+    let nonce = match from_hex(nonce) {
+        Some(result) if result.len() == NONCE_LENGTH => XNonce::clone_from_slice(&result),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "Malformed blob nonce"))
+    };
+
+    let mut written = 0;
+    let mut segment = Vec::with_capacity(SEGMENT_SIZE as usize);
+
+    for index in 0.. {
+        segment.clear();
+        source.take(SEGMENT_SIZE).read_to_end(&mut segment)?;
+
+        if segment.is_empty() {
+            break
+        }
+
+        let sealed = key.cipher.encrypt(&segment_nonce(&nonce, index), segment.as_slice())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Failed to encrypt blob"))?;
+
+        destination.write_all(&sealed)?;
+        written += sealed.len() as u64;
+
+        if (segment.len() as u64) < SEGMENT_SIZE {
+            break
+        }
+    }
+
+    Ok(written)
+}
+
+/// Amount of stored bytes sealed within an encrypted blob of the given length
+pub fn decrypted_length(encrypted_length: u64) -> u64 {
+    if encrypted_length == 0 {
+        return 0
+    }
+
+    // Only the last segment can be shorter
+    let segments = (encrypted_length - 1) / SEALED_SEGMENT_SIZE + 1;
+    encrypted_length - segments * TAG_LENGTH
+}
+
+/// Where the new key is kept while a rotation is underway
+fn pending_key_path(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.new", path.display()))
+}
+
+/// Re-encrypts every stored blob with a newly generated key, which replaces the configured key once done.
+///
+/// Blobs stored before encryption was enabled are encrypted as well.
+/// Run through `backend rotate-key` while the backend is stopped, an interrupted rotation can simply be run again
+pub fn rotate_key(config: &Config, database: &Db, storage: Arc<dyn Storage>) -> Result<(), String> {
+    let path = match &config.backend_encryption_key_path {
+        Some(result) => Path::new(result),
+        None => return Err(String::from("Encryption isn't enabled, set backend_encryption_key_path first"))
+    };
+
+    let mut keyring = Keyring::load(config)?;
+
+    let pending_path = pending_key_path(path);
+    let new_key = if pending_path.exists() {
+        EncryptionKey::read(&pending_path)?
+    } else {
+        EncryptionKey::generate(&pending_path)?
+    };
+
+    if !keyring.keys.iter().any(|key| key.id == new_key.id) {
+        keyring.keys.push(new_key.clone());
+    }
+
+    let (media_database, blob_database, variant_database) = match (database.open_tree("media"), database.open_tree("blob"), database.open_tree("variant")) {
+        (Ok(media_database), Ok(blob_database), Ok(variant_database)) => (media_database, blob_database, variant_database),
+        _ => return Err(String::from("Couldn't open the media database"))
+    };
+
+    let revision_database = match database.open_tree("revision") {
+        Ok(result) => result,
+        Err(_) => return Err(String::from("Couldn't open the revision database"))
+    };
+
+    // Deduplicated media share blobs, which only need to be rotated once
+    let mut blobs: BTreeMap<PathBuf, Vec<Media>> = BTreeMap::new();
+    for media in media_database.iter()
+        .filter_map(|item| item.ok())
+        .filter_map(|item| serde_json::from_str::<Media>(&String::from_utf8_lossy(&item.1)).ok()) {
+        blobs.entry(media.data_path.clone()).or_default().push(media);
+    }
+
+    // Revisions may hold the only reference to their blob
+    let mut revisions: BTreeMap<PathBuf, Vec<MediaRevision>> = BTreeMap::new();
+    for revision in revision_database.iter()
+        .filter_map(|item| item.ok())
+        .filter_map(|item| serde_json::from_str::<MediaRevision>(&String::from_utf8_lossy(&item.1)).ok()) {
+        blobs.entry(revision.media.data_path.clone()).or_default();
+        revisions.entry(revision.media.data_path.clone()).or_default().push(revision);
+    }
+
+    let staging_directory = match media_utils::staging_directory(config) {
+        Ok(result) => result,
+        Err(_) => return Err(String::from("Couldn't create the staging directory"))
+    };
+
+    for (data_path, medias) in blobs {
+        let blob_revisions = revisions.remove(&data_path).unwrap_or_default();
+        let media = match medias.first().or_else(|| blob_revisions.first().map(|revision| &revision.media)) {
+            Some(result) => result,
+            None => continue
+        };
+
+        if media.data_key_id.as_ref() == Some(&new_key.id) {
+            continue
+        }
+
+        let data_key = media_utils::storage_key(media);
+        let staged_path = staging_directory.join(format!("rotate-{}", new_key.id));
+
+        // Encrypted blobs keep their nonce, a nonce only has to be unique per key
+        let nonce = media.data_nonce.clone().unwrap_or_else(generate_nonce);
+        let rotated = rotate_blob(&keyring, storage.as_ref(), media, &new_key, &nonce, &staged_path);
+        let _ = fs::remove_file(&staged_path);
+
+        let (data_size, stored_length) = match rotated {
+            Ok(result) => result,
+            Err(error) => return Err(format!("Failed to rotate {:?}: {}", data_path, error))
+        };
+
+        let update = |media: &mut Media| {
+            media.data_nonce = Some(nonce.clone());
+            media.data_key_id = Some(new_key.id.clone());
+            media.data_size = data_size as i32;
+
+            // Plain blobs no longer tell their length through their stored size
+            if !media.data_compressed && media.data_length.is_none() {
+                media.data_length = Some(stored_length);
+            }
+        };
+
+        for media in &medias {
+            let updated = media_database.update_and_fetch(&media.id, |media_vec| {
+                let mut edited_media: Media = serde_json::from_str(&String::from_utf8_lossy(media_vec?)).ok()?;
+                update(&mut edited_media);
+
+                Some(IVec::from(match serde_json::to_vec(&edited_media) {
+                    Ok(result) => result,
+                    Err(_) => return None
+                }))
+            });
+
+            if updated.is_err() {
+                return Err(format!("Failed to update media {}", media.id))
+            }
+        }
+
+        for revision in &blob_revisions {
+            let updated = revision_database.update_and_fetch(media_utils::revision_key(&revision.media_id, revision.revision), |revision_vec| {
+                let mut edited_revision: MediaRevision = serde_json::from_str(&String::from_utf8_lossy(revision_vec?)).ok()?;
+                update(&mut edited_revision.media);
+
+                Some(IVec::from(match serde_json::to_vec(&edited_revision) {
+                    Ok(result) => result,
+                    Err(_) => return None
+                }))
+            });
+
+            if updated.is_err() {
+                return Err(format!("Failed to update revision {} of media {}", revision.revision, revision.media_id))
+            }
+        }
+
+        let updated = blob_database.update_and_fetch(&data_key, |blob_vec| {
+            let mut blob: Blob = serde_json::from_str(&String::from_utf8_lossy(blob_vec?)).ok()?;
+            blob.data_nonce = Some(nonce.clone());
+            blob.data_key_id = Some(new_key.id.clone());
+            blob.data_size = data_size as i32;
+
+            Some(IVec::from(match serde_json::to_vec(&blob) {
+                Ok(result) => result,
+                Err(_) => return None
+            }))
+        });
+
+        if updated.is_err() || database.flush().is_err() {
+            return Err(format!("Failed to update blob {:?}", data_path))
+        }
+
+        // Cached variants are still sealed with the old key
+        media_utils::delete_released(storage.as_ref(), &variant::remove_all(&variant_database, &data_key));
+
+        info!("[Encryption] Rotated {:?} ({} media, {} revisions)", data_path, medias.len(), blob_revisions.len());
+    }
+
+    if fs::rename(&pending_path, path).is_err() {
+        return Err(format!("Failed to replace {:?} with the new key {:?}", path, pending_path))
+    }
+
+    info!("[Encryption] Rotation finished, key {} is now in use", new_key.id);
+    Ok(())
+}
+
+/// Encrypts a blob's stored bytes with the new key, returning its new stored size along with the amount of bytes sealed
+fn rotate_blob(
+    keyring: &Keyring,
+    storage: &dyn Storage,
+    media: &Media,
+    new_key: &EncryptionKey,
+    nonce: &str,
+    staged_path: &Path
+) -> io::Result<(u64, u64)> {
+    let data_key = media_utils::storage_key(media);
+    let cipher = keyring.blob_cipher(&media.data_key_id, &media.data_nonce, u64::try_from(media.data_size).ok())?;
+
+    let mut stored = blob::open_stored(storage, cipher.as_ref(), &data_key, 0, None)?;
+    let mut counted = CountingReader(&mut stored, 0);
+
+    let mut staged_file = BufWriter::new(File::create(staged_path)?);
+    let data_size = encrypt(new_key, nonce, &mut counted, &mut staged_file)?;
+    staged_file.flush()?;
+    drop(staged_file);
+
+    let stored_length = counted.1;
+    storage.put(&data_key, staged_path)?;
+
+    Ok((data_size, stored_length))
+}
+
+/// Counts the bytes read through it
+struct CountingReader<R>(R, u64);
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let read = self.0.read(buffer)?;
+        self.1 += read as u64;
+        Ok(read)
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(value: &str) -> Option<Vec<u8>> {
+    value.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [high, low] => u8::from_str_radix(std::str::from_utf8(&[*high, *low]).ok()?, 16).ok(),
+            _ => None
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_key() -> EncryptionKey {
+        EncryptionKey::new(&[7u8; KEY_LENGTH])
+    }
+
+    fn test_cipher(key: &EncryptionKey, nonce: &str) -> BlobCipher {
+        BlobCipher {
+            ciphers: Arc::new(vec![key.cipher.clone()]),
+            nonce: XNonce::clone_from_slice(&from_hex(nonce).unwrap()),
+            sealed_length: None
+        }
+    }
+
+    fn decrypt(cipher: BlobCipher, sealed: Vec<u8>) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        DecryptReader {
+            sealed: Box::new(io::Cursor::new(sealed)),
+            cipher,
+            index: 0,
+            segment: Vec::new(),
+            offset: 0,
+            skip: 0,
+            remaining: None,
+            sealed_remaining: None
+        }.read_to_end(&mut data)?;
+
+        Ok(data)
+    }
+
+    #[test]
+    fn decrypted_length_takes_off_every_tag() {
+        assert_eq!(decrypted_length(0), 0);
+        assert_eq!(decrypted_length(TAG_LENGTH + 1), 1);
+        assert_eq!(decrypted_length(SEALED_SEGMENT_SIZE), SEGMENT_SIZE);
+        assert_eq!(decrypted_length(SEALED_SEGMENT_SIZE + TAG_LENGTH + 1), SEGMENT_SIZE + 1);
+        assert_eq!(decrypted_length(3 * SEALED_SEGMENT_SIZE), 3 * SEGMENT_SIZE);
+    }
+
+    #[test]
+    fn decrypted_length_matches_encrypt() {
+        let key = test_key();
+
+        for length in [0, 1, SEGMENT_SIZE - 1, SEGMENT_SIZE, SEGMENT_SIZE + 1, 2 * SEGMENT_SIZE + 5] {
+            let data = vec![1u8; length as usize];
+            let mut sealed = Vec::new();
+            let written = encrypt(&key, &generate_nonce(), &mut &data[..], &mut sealed).unwrap();
+
+            assert_eq!(written, sealed.len() as u64);
+            assert_eq!(decrypted_length(written), length);
+        }
+    }
+
+    #[test]
+    fn segment_nonces_differ_in_their_last_bytes() {
+        let nonce = XNonce::clone_from_slice(&[0xAAu8; NONCE_LENGTH]);
+
+        assert_eq!(segment_nonce(&nonce, 0), nonce);
+
+        let first = segment_nonce(&nonce, 1);
+        let second = segment_nonce(&nonce, 2);
+        assert_ne!(first, second);
+        assert_eq!(first[..NONCE_LENGTH - 8], nonce[..NONCE_LENGTH - 8]);
+        assert_eq!(first[NONCE_LENGTH - 8], 0xAA ^ 1);
+
+        // The whole index is mixed in, not just its lowest byte
+        assert_ne!(segment_nonce(&nonce, 1 << 40), nonce);
+        assert_eq!(segment_nonce(&segment_nonce(&nonce, 12345), 12345), nonce);
+    }
+
+    #[test]
+    fn reordered_segments_fail_to_decrypt() {
+        let key = test_key();
+        let nonce = generate_nonce();
+        let data: Vec<u8> = (0..2 * SEGMENT_SIZE).map(|index| (index / SEGMENT_SIZE) as u8).collect();
+
+        let mut sealed = Vec::new();
+        encrypt(&key, &nonce, &mut &data[..], &mut sealed).unwrap();
+        assert_eq!(decrypt(test_cipher(&key, &nonce), sealed.clone()).unwrap(), data);
+
+        let (first, second) = sealed.split_at(SEALED_SEGMENT_SIZE as usize);
+        let swapped = [second, first].concat();
+        assert!(decrypt(test_cipher(&key, &nonce), swapped).is_err());
+    }
+
+    #[test]
+    fn blobs_cut_off_between_segments_fail() {
+        let directory = std::env::temp_dir().join(format!("centix-test-{}", generate_nonce()));
+        let config = Config {
+            backend_media_directory: Some(directory.to_string_lossy().to_string()),
+            ..Config::default()
+        };
+        let storage = crate::storage::backend::open_storage(&config).unwrap();
+
+        let key = test_key();
+        let keyring = Keyring {
+            keys: vec![key.clone()]
+        };
+        let nonce = generate_nonce();
+        let data = vec![3u8; (2 * SEGMENT_SIZE) as usize];
+
+        let mut sealed = Vec::new();
+        let sealed_length = encrypt(&key, &nonce, &mut &data[..], &mut sealed).unwrap();
+
+        fs::create_dir_all(&directory).unwrap();
+        let staged_path = directory.join("staged");
+        fs::write(&staged_path, &sealed).unwrap();
+        storage.put("Other/whole", &staged_path).unwrap();
+        fs::write(&staged_path, &sealed[..SEALED_SEGMENT_SIZE as usize]).unwrap();
+        storage.put("Other/truncated", &staged_path).unwrap();
+
+        let cipher = keyring.blob_cipher(&Some(key.id.clone()), &Some(nonce), Some(sealed_length)).unwrap().unwrap();
+        let read = |key: &str, start: u64, end: Option<u64>| -> io::Result<Vec<u8>> {
+            let mut read = Vec::new();
+            cipher.stream(storage.as_ref(), key, start, end)?.read_to_end(&mut read)?;
+            Ok(read)
+        };
+
+        assert_eq!(read("Other/whole", 0, None).unwrap(), data);
+        assert_eq!(read("Other/whole", SEGMENT_SIZE - 1, Some(SEGMENT_SIZE)).unwrap().len(), 2);
+
+        // Ranges within what's left still read fine, anything reaching past it fails
+        assert_eq!(read("Other/truncated", 0, Some(99)).unwrap().len(), 100);
+        assert_eq!(read("Other/truncated", 0, None).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(read("Other/truncated", SEGMENT_SIZE - 1, Some(SEGMENT_SIZE)).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(read("Other/truncated", SEGMENT_SIZE, None).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+
+        let _ = fs::remove_dir_all(directory);
+    }
+}
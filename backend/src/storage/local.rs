@@ -0,0 +1,74 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf}
+};
+
+use crate::storage::backend::Storage;
+
+/// Stores blobs as files under the content directory
+pub struct LocalStorage {
+    root: PathBuf
+}
+
+impl LocalStorage {
+    pub fn new(root: PathBuf) -> LocalStorage {
+        LocalStorage {
+            root
+        }
+    }
+
+    /// Resolves a key to its file, uploads predating storage backends recorded their full path instead
+    fn path(&self, key: &str) -> PathBuf {
+        let path = Path::new(key);
+
+        if path.is_absolute() || path.starts_with(&self.root) {
+            path.to_path_buf()
+        } else {
+            self.root.join(path)
+        }
+    }
+}
+
+impl Storage for LocalStorage {
+    fn put(&self, key: &str, source: &Path) -> io::Result<()> {
+        let path = self.path(key);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Renaming fails across file systems, in which case fall back to copying
+        if fs::rename(source, &path).is_err() {
+            fs::copy(source, &path)?;
+        }
+
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.path(key))
+    }
+
+    fn stream(&self, key: &str, start: u64, end: Option<u64>) -> io::Result<Box<dyn Read + Send>> {
+        let mut file = File::open(self.path(key))?;
+        file.seek(SeekFrom::Start(start))?;
+
+        Ok(match end {
+            Some(end) => Box::new(file.take((end + 1).saturating_sub(start))),
+            None => Box::new(file)
+        })
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        fs::remove_file(self.path(key))
+    }
+
+    fn exists(&self, key: &str) -> io::Result<bool> {
+        Ok(self.path(key).is_file())
+    }
+
+    fn size(&self, key: &str) -> io::Result<u64> {
+        Ok(fs::metadata(self.path(key))?.len())
+    }
+}
@@ -0,0 +1,190 @@
+// Minimal S3 compatible object store client, requests are signed with AWS Signature Version 4
+// (https://docs.aws.amazon.com/AmazonS3/latest/API/sig-v4-authenticating-requests.html)
+use std::{fs::File, io::{self, Read}, path::Path, time::Duration};
+
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use ureq::{Agent, AgentBuilder, Request};
+
+use crate::Config;
+use crate::storage::backend::Storage;
+
+/// Payloads are streamed rather than hashed up front
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// SHA-256 of an empty payload
+const EMPTY_PAYLOAD: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// Stores blobs as objects inside of a bucket
+pub struct S3Storage {
+    agent: Agent,
+    scheme: String,
+    host: String,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    // Addresses buckets as `host/bucket` instead of `bucket.host`, required by most self hosted stores such as MinIO
+    path_style: bool
+}
+
+impl S3Storage {
+    pub fn new(config: &Config) -> Result<S3Storage, String> {
+        let (endpoint, bucket, access_key, secret_key) = match (
+            &config.backend_s3_endpoint,
+            &config.backend_s3_bucket,
+            &config.backend_s3_access_key,
+            &config.backend_s3_secret_key
+        ) {
+            (Some(endpoint), Some(bucket), Some(access_key), Some(secret_key)) => (endpoint, bucket, access_key, secret_key),
+            _ => return Err(String::from("S3 storage requires an endpoint, bucket, access key and secret key"))
+        };
+
+        let (scheme, host) = match endpoint.split_once("://") {
+            Some((scheme, host)) => (scheme, host.trim_end_matches('/')),
+            None => return Err(format!("S3 endpoint {} is missing its scheme (e.g. https://)", endpoint))
+        };
+
+        Ok(S3Storage {
+            agent: AgentBuilder::new()
+                .timeout_connect(Duration::from_secs(10))
+                .build(),
+            scheme: scheme.to_string(),
+            host: host.to_string(),
+            bucket: bucket.clone(),
+            region: config.backend_s3_region.clone(),
+            access_key: access_key.clone(),
+            secret_key: secret_key.clone(),
+            path_style: config.backend_s3_path_style
+        })
+    }
+
+    /// Builds a signed request for an object
+    fn request(&self, method: &str, key: &str, payload_hash: &str) -> Request {
+        let (host, path) = if self.path_style {
+            (self.host.clone(), format!("/{}/{}", uri_encode(&self.bucket), uri_encode(key)))
+        } else {
+            (format!("{}.{}", self.bucket, self.host), format!("/{}", uri_encode(key)))
+        };
+
+        let now = chrono::offset::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let scope = format!("{}/{}/s3/aws4_request", now.format("%Y%m%d"), self.region);
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, host, payload_hash, amz_date, signed_headers, payload_hash
+        );
+
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+            amz_date, scope, Sha256::digest(canonical_request.as_bytes())
+        );
+
+        let mut signing_key = format!("AWS4{}", self.secret_key).into_bytes();
+        for part in scope.split('/') {
+            signing_key = hmac(&signing_key, part);
+        }
+
+        let signature: String = hmac(&signing_key, &string_to_sign).iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        self.agent.request(method, &format!("{}://{}{}", self.scheme, host, path))
+            .set("x-amz-content-sha256", payload_hash)
+            .set("x-amz-date", &amz_date)
+            .set("authorization", &format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                self.access_key, scope, signed_headers, signature
+            ))
+    }
+}
+
+impl Storage for S3Storage {
+    fn put(&self, key: &str, source: &Path) -> io::Result<()> {
+        let file = File::open(source)?;
+        let length = file.metadata()?.len();
+
+        self.request("PUT", key, UNSIGNED_PAYLOAD)
+            .set("content-length", &length.to_string())
+            .send(file)
+            .map_err(request_error)?;
+
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.stream(key, 0, None)?.read_to_end(&mut data)?;
+
+        Ok(data)
+    }
+
+    fn stream(&self, key: &str, start: u64, end: Option<u64>) -> io::Result<Box<dyn Read + Send>> {
+        let mut request = self.request("GET", key, EMPTY_PAYLOAD);
+
+        // Ranges starting at 0 are left off since they can't be satisfied on empty objects
+        request = match end {
+            Some(end) => request.set("range", &format!("bytes={}-{}", start, end)),
+            None if start > 0 => request.set("range", &format!("bytes={}-", start)),
+            None => request
+        };
+
+        Ok(Box::new(request.call().map_err(request_error)?.into_reader()))
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        self.request("DELETE", key, EMPTY_PAYLOAD)
+            .call()
+            .map_err(request_error)?;
+
+        Ok(())
+    }
+
+    fn exists(&self, key: &str) -> io::Result<bool> {
+        match self.size(key) {
+            Ok(_) => Ok(true),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(error) => Err(error)
+        }
+    }
+
+    fn size(&self, key: &str) -> io::Result<u64> {
+        let response = self.request("HEAD", key, EMPTY_PAYLOAD)
+            .call()
+            .map_err(request_error)?;
+
+        match response.header("content-length").and_then(|length| length.parse().ok()) {
+            Some(result) => Ok(result),
+            None => Err(io::Error::new(io::ErrorKind::InvalidData, "Object is missing its content length"))
+        }
+    }
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent encodes everything but unreserved characters & path separators
+fn uri_encode(value: &str) -> String {
+    value.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte)
+        })
+        .collect()
+}
+
+fn request_error(error: ureq::Error) -> io::Error {
+    match error {
+        ureq::Error::Status(404, _) => io::Error::new(io::ErrorKind::NotFound, "Object doesn't exist"),
+        ureq::Error::Status(code, response) => io::Error::new(
+            if code == 403 { io::ErrorKind::PermissionDenied } else { io::ErrorKind::InvalidData },
+            format!("Object store responded with {}: {}", code, response.into_string().unwrap_or_default())
+        ),
+        ureq::Error::Transport(transport) => io::Error::new(io::ErrorKind::ConnectionAborted, transport.to_string())
+    }
+}
@@ -0,0 +1,52 @@
+// Derived copies of blobs, such as thumbnails & transformed images.
+//
+// Variants are stored next to the blob they were made from, under `<blob key>.<name>`,
+// and recorded within the "variant" tree so they can be found again once the blob is removed.
+// They're only a cache, unreadable or missing variants are simply made again.
+use std::{fs, io, path::Path};
+
+use sled::Tree;
+
+use crate::storage::{backend::Storage, encryption::Keyring};
+
+/// Key of a variant of the blob stored under `data_key`
+pub fn variant_key(data_key: &str, name: &str) -> String {
+    format!("{}.{}", data_key, name)
+}
+
+/// Reads a cached variant, if there's a readable one
+pub fn fetch(storage: &dyn Storage, keyring: &Keyring, key: &str) -> Option<Vec<u8>> {
+    let sealed = storage.get(key).ok()?;
+    keyring.open(&sealed).ok()
+}
+
+/// Caches a variant, sealing it when encryption is enabled.
+///
+/// `staged_path` is used for writing the variant before it's handed to storage
+pub fn store(variant_database: &Tree, storage: &dyn Storage, keyring: &Keyring, key: &str, data: &[u8], staged_path: &Path) -> io::Result<()> {
+    // Recorded up front so a variant is never stored without being found on removal
+    variant_database.insert(key, &[])
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+
+    let stored = fs::write(staged_path, keyring.seal(data)?)
+        .and_then(|_| storage.put(key, staged_path));
+    let _ = fs::remove_file(staged_path);
+
+    stored
+}
+
+/// Forgets every cached variant of the blob stored under `data_key`,
+/// returning their keys so they can be deleted from storage once the database is unlocked
+pub fn remove_all(variant_database: &Tree, data_key: &str) -> Vec<String> {
+    let keys: Vec<String> = variant_database.scan_prefix(variant_key(data_key, ""))
+        .keys()
+        .filter_map(|key| key.ok())
+        .map(|key| String::from_utf8_lossy(&key).to_string())
+        .collect();
+
+    for key in &keys {
+        let _ = variant_database.remove(key);
+    }
+
+    keys
+}